@@ -0,0 +1,202 @@
+//! Builds the slider attack tables, so that they are ready without any work at startup,
+//! and records the details of the build that are reported by the engine.
+
+use std::{
+    env,
+    fmt::Write,
+    fs,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Magic numbers that map the blockers of every square to a slot of its attacks table,
+/// with no two different attacks sharing a slot. They were found by trying sparse random numbers.
+#[rustfmt::skip]
+const ROOK_MAGIC_NUMBERS: [u64; 64] = [
+    0x0080_0080_2840_0010, 0x2040_0040_2000_1002, 0x3480_0860_0030_0084, 0x0100_2500_0810_0020,
+    0x8080_1280_0400_0800, 0x4A00_0600_0104_8810, 0x0880_0500_4200_1080, 0x1A00_0084_0200_6041,
+    0x1002_8000_8020_4000, 0x8000_4001_5004_2008, 0x100A_8020_0284_9004, 0x0021_0020_1000_2900,
+    0x0008_8004_0080_1800, 0x4D06_0018_0A00_1084, 0x0001_0082_0021_0014, 0x4106_0014_024E_0289,
+    0x0230_2180_0840_1080, 0x5DC4_4040_1000_2008, 0x0820_0080_1002_8024, 0x1001_8080_1000_0805,
+    0x0054_8080_0800_2400, 0x0400_8080_0201_0400, 0x0080_2C00_9025_080A, 0x0806_0200_04CF_0984,
+    0x1002_8000_8020_4000, 0x8040_0081_8020_00C0, 0x4852_1000_8020_0080, 0x4010_6863_0010_0300,
+    0x0058_0009_0004_9100, 0x9002_0052_0004_1088, 0x0000_080C_0002_1910, 0x008C_0506_0001_5184,
+    0x0602_C005_2080_0280, 0x0001_4000_8280_2000, 0x0810_00B0_8080_2000, 0x0010_0408_0080_1080,
+    0x0008_8004_0080_1800, 0x2024_0084_8080_0600, 0x0400_8002_0080_0300, 0x5491_1064_0200_1085,
+    0x0590_8040_0021_8002, 0x0900_5000_2004_C000, 0x3022_1000_2000_8080, 0x2410_0081_0008_0800,
+    0x0800_0500_8801_0010, 0x0010_4004_2048_0110, 0x22A2_0108_0644_0050, 0x9000_0084_10C2_0001,
+    0x0041_2100_5080_0500, 0x0008_C002_8220_1080, 0xCC24_8610_0220_0080, 0x4010_6863_0010_0300,
+    0x0002_0800_6411_0100, 0x0000_802A_0014_0180, 0x0002_D810_0102_4400, 0x0010_4264_0091_0200,
+    0x0001_0880_2050_C202, 0x0001_0880_2050_C202, 0x0001_0880_2050_C202, 0x0104_3501_0820_1001,
+    0x6382_0038_04A0_902A, 0xA001_0022_0400_2843, 0x8211_0004_0200_4081, 0x0000_4400_4100_8222,
+];
+
+#[rustfmt::skip]
+const BISHOP_MAGIC_NUMBERS: [u64; 64] = [
+    0x100C_5808_0808_8058, 0x009C_9002_0051_0010, 0x0208_2801_4080_8011, 0x0051_0400_8040_0210,
+    0x0054_5040_0201_0000, 0x0042_0802_2803_8004, 0x100C_5808_0808_8058, 0x0038_8020_903C_2004,
+    0x0151_9010_1009_0250, 0x4000_1004_00C4_0244, 0x1000_0802_0400_3218, 0x0083_2110_C100_2C00,
+    0x2030_8202_1002_2BA0, 0x2002_4082_2020_0000, 0x4010_0401_1828_440A, 0x8440_18A4_0A08_1500,
+    0x0084_0410_5090_0108, 0x0020_0004_0400_9210, 0x000E_0004_0800_1102, 0x0021_0828_0141_0042,
+    0x0001_0001_9040_0020, 0x0000_2001_1008_2000, 0x2100_8000_4208_2010, 0x0011_C002_8448_0810,
+    0xA104_1408_1010_1020, 0x0010_0881_1002_0080, 0x0400_2400_1808_0029, 0x4102_1800_0400_40A8,
+    0xA134_8400_8080_2000, 0x0001_0100_0210_0090, 0x0008_1080_0914_0102, 0x0585_0204_1100_8080,
+    0x8282_0A20_0410_2000, 0x0008_0909_A004_0800, 0x8841_0040_4028_0080, 0x9010_2200_8008_0080,
+    0x08C2_0494_0002_0020, 0x0801_1000_A005_0408, 0x52A1_8102_0924_0205, 0x0285_0311_0080_2416,
+    0x8282_0A20_0410_2000, 0x0400_8094_2102_100A, 0x0800_1201_1000_0500, 0x0E04_00C2_0280_0801,
+    0x4020_0121_2200_0400, 0x0050_2001_A820_0100, 0x0004_3002_0046_0212, 0x1011_020A_1E00_0040,
+    0x100C_5808_0808_8058, 0x4400_4044_1008_1080, 0x6000_0022_0150_0002, 0x0000_801C_8414_0180,
+    0x1008_5010_8202_10C8, 0x0000_5021_0911_0002, 0x100C_5808_0808_8058, 0x009C_9002_0051_0010,
+    0x0038_8020_903C_2004, 0x8440_18A4_0A08_1500, 0x0011_C002_8448_0810, 0x1090_0040_0821_8801,
+    0x0E44_0908_0490_5400, 0xC409_0034_21C4_0102, 0x0151_9010_1009_0250, 0x100C_5808_0808_8058,
+];
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+
+fn on_board(file: i8, rank: i8) -> bool {
+    (0..8).contains(&file) && (0..8).contains(&rank)
+}
+
+/// Squares reached from the square, stopping at the first blocker. Without occupancy,
+/// the last square of each ray is left out, since it can't block anything.
+fn slider_attacks(square: usize, directions: &[(i8, i8)], occupancy: Option<u64>) -> u64 {
+    let mut attacks = 0;
+
+    for (file_step, rank_step) in directions {
+        let mut file = (square % 8) as i8 + file_step;
+        let mut rank = (square / 8) as i8 + rank_step;
+        while on_board(file, rank) {
+            if occupancy.is_none() && !on_board(file + file_step, rank + rank_step) {
+                break;
+            }
+
+            let bit = 1 << (rank * 8 + file);
+            attacks |= bit;
+            if occupancy.unwrap_or(0) & bit != 0 {
+                break;
+            }
+
+            file += file_step;
+            rank += rank_step;
+        }
+    }
+
+    attacks
+}
+
+fn write_tables(out: &mut String, name: &str, directions: &[(i8, i8)], magic_numbers: &[u64; 64]) {
+    let mut magics = String::new();
+    let mut attacks = Vec::new();
+
+    for (square, magic_number) in magic_numbers.iter().enumerate() {
+        let blockers_mask = slider_attacks(square, directions, None);
+        let shift = blockers_mask.count_ones();
+        let offset = attacks.len();
+        attacks.resize(offset + (1 << shift), 0);
+
+        // Visit every subset of the blockers mask.
+        let mut blockers = 0u64;
+        loop {
+            let index = offset + (blockers.wrapping_mul(*magic_number) >> (64 - shift)) as usize;
+            let square_attacks = slider_attacks(square, directions, Some(blockers));
+            assert!(
+                attacks[index] == 0 || attacks[index] == square_attacks,
+                "Magic number of square {} maps different attacks to the same slot",
+                square
+            );
+            attacks[index] = square_attacks;
+
+            blockers = blockers.wrapping_sub(blockers_mask) & blockers_mask;
+            if blockers == 0 {
+                break;
+            }
+        }
+
+        writeln!(
+            magics,
+            "SquareMagic {{ blockers_mask: Bitboard::new({:#x}), shift: {}, \
+             magic_number: Bitboard::new({:#x}), offset: {} }},",
+            blockers_mask, shift, magic_number, offset
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "pub static {}_MAGICS: [SquareMagic; 64] = [\n{}];", name, magics).unwrap();
+    writeln!(
+        out,
+        "pub static {}_ATTACKS: [u64; {}] = [{}];",
+        name,
+        attacks.len(),
+        attacks.iter().map(|bb| format!("{:#x}", bb)).collect::<Vec<_>>().join(",")
+    )
+    .unwrap();
+}
+
+fn git_commit() -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "--short=8", "HEAD"]).output().ok()?;
+    let commit = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !commit.is_empty()).then_some(commit)
+}
+
+/// Day of the build as YYYY-MM-DD, honouring SOURCE_DATE_EPOCH for reproducible builds.
+fn build_date() -> String {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+
+    // Days since the epoch to a civil date, counting years from March so that leap days come last.
+    let days = (seconds / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn write_build_info(out: &mut String) {
+    let target_features = env::var("CARGO_CFG_TARGET_FEATURE").unwrap_or_default();
+    let features = ["bmi2", "avx2", "popcnt"]
+        .into_iter()
+        .filter(|feature| target_features.split(',').any(|enabled| enabled == *feature))
+        .collect::<Vec<_>>();
+
+    writeln!(out, "pub const GIT_COMMIT: &str = {:?};", git_commit().unwrap_or("unknown".into()))
+        .unwrap();
+    writeln!(out, "pub const BUILD_DATE: &str = {:?};", build_date()).unwrap();
+    writeln!(out, "pub const TARGET_FEATURES: &[&str] = &{:?};", features).unwrap();
+    writeln!(out, "pub const PROFILE: &str = {:?};", env::var("PROFILE").unwrap()).unwrap();
+
+    // Rebuild when the checked out commit changes.
+    for path in
+        [".git/HEAD".to_string()].into_iter().chain(fs::read_to_string(".git/HEAD").ok().and_then(
+            |head| head.strip_prefix("ref: ").map(|head| format!(".git/{}", head.trim())),
+        ))
+    {
+        if Path::new(&path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
+
+fn main() {
+    let mut out = String::new();
+    write_tables(&mut out, "ROOK", &ROOK_DIRECTIONS, &ROOK_MAGIC_NUMBERS);
+    write_tables(&mut out, "BISHOP", &BISHOP_DIRECTIONS, &BISHOP_MAGIC_NUMBERS);
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("magics.rs"), out).unwrap();
+
+    let mut build_info = String::new();
+    write_build_info(&mut build_info);
+    fs::write(Path::new(&out_dir).join("build_info.rs"), build_info).unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,253 @@
+#!/usr/bin/env python3
+"""Fits the win/draw/loss model of src/evaluation/wdl.rs on a PGN of Camel's self-play games.
+
+The games must carry the engine scores in the move comments, as the gauntlet runner writes them
+(`{+0.58/5, 0.101s}`), and start from book positions without move counters.
+
+Usage: ./fit_wdl.py books/gauntlet-v1.4.0.pgn
+"""
+
+import math
+import re
+import sys
+from collections import defaultdict
+
+# Same as MIDGAME_RATIOS in src/evaluation/position/mod.rs.
+MIDGAME_RATIOS = {"Q": 38, "R": 20, "B": 9, "N": 5, "P": 1, "K": 0}
+# The book openings carry no move counters, and sit about this many moves in.
+OPENING_MOVES = 4
+# Same as WDL_MAX_FULLMOVES in src/evaluation/wdl.rs.
+MAX_FULLMOVES = 100
+# Scores beyond this are decided games, and tell nothing about the curves.
+MAX_SCORE = 1500
+
+FILES = "abcdefgh"
+KNIGHT_STEPS = [(1, 2), (2, 1), (-1, 2), (-2, 1), (1, -2), (2, -1), (-1, -2), (-2, -1)]
+KING_STEPS = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)]
+DIAGONALS = [(1, 1), (1, -1), (-1, 1), (-1, -1)]
+LINES = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+SAN = re.compile(r"^([NBRQK])?([a-h])?([1-8])?(x)?([a-h][1-8])(=?[NBRQ])?$")
+SCORE_COMMENT = re.compile(r"\{([+-]?)(M?)(\d+(?:\.\d+)?)/(\d+)")
+
+
+def square(file, rank):
+    return rank * 8 + file
+
+
+def parse_fen(fen):
+    fields = fen.split()
+    board = {}
+    for i, row in enumerate(fields[0].split("/")):
+        file = 0
+        for c in row:
+            if c.isdigit():
+                file += int(c)
+            else:
+                board[square(file, 7 - i)] = ("w" if c.isupper() else "b", c.upper())
+                file += 1
+    en_passant = None
+    if len(fields) > 3 and fields[3] != "-":
+        en_passant = square(FILES.index(fields[3][0]), int(fields[3][1]) - 1)
+    return board, fields[1], en_passant
+
+
+def attacked(board, target, color):
+    file, rank = target % 8, target // 8
+
+    def piece_at(f, r):
+        return board.get(square(f, r)) if 0 <= f < 8 and 0 <= r < 8 else None
+
+    if any(piece_at(file + f, rank + r) == (color, "N") for f, r in KNIGHT_STEPS):
+        return True
+    if any(piece_at(file + f, rank + r) == (color, "K") for f, r in KING_STEPS):
+        return True
+    pawn_rank = rank - 1 if color == "w" else rank + 1
+    if any(piece_at(file + f, pawn_rank) == (color, "P") for f in (-1, 1)):
+        return True
+    for directions, pieces in ((DIAGONALS, "BQ"), (LINES, "RQ")):
+        for df, dr in directions:
+            f, r = file + df, rank + dr
+            while 0 <= f < 8 and 0 <= r < 8:
+                piece = board.get(square(f, r))
+                if piece:
+                    if piece[0] == color and piece[1] in pieces:
+                        return True
+                    break
+                f, r = f + df, r + dr
+    return False
+
+
+def reaches(board, piece, origin, target):
+    df, dr = target % 8 - origin % 8, target // 8 - origin // 8
+    if piece == "N":
+        return (abs(df), abs(dr)) in ((1, 2), (2, 1))
+    if piece == "K":
+        return max(abs(df), abs(dr)) == 1
+    if piece == "B" and abs(df) != abs(dr):
+        return False
+    if piece == "R" and df != 0 and dr != 0:
+        return False
+    if piece == "Q" and not (df == 0 or dr == 0 or abs(df) == abs(dr)):
+        return False
+    step_f, step_r = (df > 0) - (df < 0), (dr > 0) - (dr < 0)
+    f, r = origin % 8 + step_f, origin // 8 + step_r
+    while square(f, r) != target:
+        if square(f, r) in board:
+            return False
+        f, r = f + step_f, r + step_r
+    return True
+
+
+def legal(board, side, origin, target, captured):
+    board = dict(board)
+    board.pop(captured, None)
+    board[target] = board.pop(origin)
+    king = next(s for s, piece in board.items() if piece == (side, "K"))
+    return not attacked(board, king, "b" if side == "w" else "w")
+
+
+def play(board, side, en_passant, san):
+    """Plays a move on the board, and returns the new en passant square."""
+    san = san.rstrip("+#!?")
+    back_rank = 0 if side == "w" else 7
+    if san.replace("0", "O") in ("O-O", "O-O-O"):
+        long = san.count("-") == 2
+        king, rook = square(4, back_rank), square(0 if long else 7, back_rank)
+        del board[king], board[rook]
+        board[square(2 if long else 6, back_rank)] = (side, "K")
+        board[square(3 if long else 5, back_rank)] = (side, "R")
+        return None
+
+    piece, file, rank, _, target, promotion = SAN.match(san).groups()
+    piece = piece or "P"
+    target = square(FILES.index(target[0]), int(target[1]) - 1)
+    captured = target if target in board else None
+    forward = 1 if side == "w" else -1
+    if piece == "P" and target == en_passant:
+        captured = target - 8 * forward
+
+    origins = []
+    for origin, occupant in board.items():
+        if occupant != (side, piece):
+            continue
+        if (file and FILES[origin % 8] != file) or (rank and str(origin // 8 + 1) != rank):
+            continue
+        if piece != "P":
+            if reaches(board, piece, origin, target):
+                origins.append(origin)
+            continue
+        df, dr = target % 8 - origin % 8, target // 8 - origin // 8
+        start_rank = 1 if side == "w" else 6
+        if df == 0 and target not in board:
+            if dr == forward or (
+                dr == 2 * forward
+                and origin // 8 == start_rank
+                and origin + 8 * forward not in board
+            ):
+                origins.append(origin)
+        elif abs(df) == 1 and dr == forward and captured is not None:
+            origins.append(origin)
+    if len(origins) > 1:
+        origins = [o for o in origins if legal(board, side, o, target, captured)]
+    (origin,) = origins
+
+    if captured is not None:
+        del board[captured]
+    del board[origin]
+    board[target] = (side, promotion[-1] if promotion else piece)
+    return (origin + target) // 2 if piece == "P" and abs(target - origin) == 16 else None
+
+
+def samples(pgn):
+    """Yields the score, the result, the move number and the material of each scored position,
+    from the side to move."""
+    for game in pgn.split("[Event ")[1:]:
+        fen = re.search(r'\[FEN "([^"]+)"\]', game).group(1)
+        result = {"1-0": 1, "0-1": -1, "1/2-1/2": 0}.get(
+            re.search(r'\[Result "([^"]+)"\]', game).group(1)
+        )
+        if result is None:
+            continue
+        board, side, en_passant = parse_fen(fen)
+        # The runner labels the results by who moved first, not by color.
+        first_mover = side
+
+        tokens = re.findall(r"\{[^}]*\}|[^\s{}]+", game[game.rindex("]") + 1 :])
+        plies = 0
+        for i, token in enumerate(tokens):
+            token = re.sub(r"^\d+\.+", "", token)
+            if not token or token.startswith("{") or token in ("1-0", "0-1", "1/2-1/2", "*"):
+                continue
+            comment = tokens[i + 1] if i + 1 < len(tokens) else ""
+            score = SCORE_COMMENT.match(comment)
+            if score and not score.group(2):
+                value = round(float(score.group(3)) * 100) * (-1 if score.group(1) == "-" else 1)
+                material = min(255, sum(MIDGAME_RATIOS[p] for _, p in board.values()))
+                fullmove = OPENING_MOVES + 1 + plies // 2
+                yield value, result * (1 if side == first_mover else -1), fullmove, material
+            en_passant = play(board, side, en_passant, token)
+            side = "b" if side == "w" else "w"
+            plies += 1
+
+
+def negative_log_likelihood(coefficients, bins):
+    margin_coefficients, scale_coefficients = coefficients[:3], coefficients[3:]
+    total = 0.0
+    for (value, material, fullmove), (wins, draws, losses) in bins.items():
+        features = (1.0, 1.0 - material / 255, fullmove / MAX_FULLMOVES)
+        margin = math.exp(sum(c * f for c, f in zip(margin_coefficients, features)))
+        scale = math.exp(sum(c * f for c, f in zip(scale_coefficients, features)))
+        win = 1 / (1 + math.exp(max(-50, min(50, (margin - value) / scale))))
+        loss = 1 / (1 + math.exp(max(-50, min(50, (margin + value) / scale))))
+        draw = max(1e-9, 1 - win - loss)
+        total -= (
+            wins * math.log(max(win, 1e-12))
+            + draws * math.log(draw)
+            + losses * math.log(max(loss, 1e-12))
+        )
+    return total
+
+
+def fit(coefficients, bins):
+    """Minimizes the negative log likelihood one coefficient at a time, halving the steps once
+    none of them improves it."""
+    best = negative_log_likelihood(coefficients, bins)
+    steps = [0.2] * len(coefficients)
+    for _ in range(200):
+        improved = False
+        for i in range(len(coefficients)):
+            for step in (steps[i], -steps[i]):
+                candidate = list(coefficients)
+                candidate[i] += step
+                likelihood = negative_log_likelihood(candidate, bins)
+                if likelihood < best:
+                    best, coefficients, improved = likelihood, candidate, True
+                    break
+        if not improved:
+            steps = [step / 2 for step in steps]
+            if max(steps) < 0.002:
+                break
+    return coefficients
+
+
+def main(path):
+    # Positions are grouped by rounded score, material and move number, to keep the fit fast.
+    bins = defaultdict(lambda: [0, 0, 0])
+    with open(path) as pgn:
+        for value, result, fullmove, material in samples(pgn.read()):
+            if abs(value) > MAX_SCORE:
+                continue
+            key = (
+                round(value / 20) * 20,
+                material // 16 * 16 + 8,
+                min(fullmove, MAX_FULLMOVES) // 8 * 8 + 4,
+            )
+            bins[key][1 - result] += 1
+
+    coefficients = fit([math.log(50), 0.2, 0.0, math.log(130), 0.0, 0.0], bins)
+    print("WDL_MARGIN_COEFFICIENTS:", [round(c, 3) for c in coefficients[:3]])
+    print("WDL_SCALE_COEFFICIENTS:", [round(c, 3) for c in coefficients[3:]])
+
+
+if __name__ == "__main__":
+    main(sys.argv[1])
@@ -0,0 +1,279 @@
+// Self-play data generation, producing positions labeled with search scores and game results.
+
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use self::selfplay::{Adjudication, Player};
+use crate::{
+    evaluation::{Score, ValueScore},
+    moves::gen::MoveStage,
+    position::{
+        board::{Board, Piece},
+        fen::{FromFen, START_FEN},
+        square::Square,
+        CastlingRights, Color, Position,
+    },
+    search::{params::SearchParams, table::SearchTable},
+};
+
+pub(crate) mod selfplay;
+
+/// Size in bytes of an encoded entry.
+pub const ENTRY_SIZE: usize = 32;
+
+const NO_EN_PASSANT: u8 = 64;
+const TABLE_SIZE_MB: usize = 8;
+const ADJUDICATION: Adjudication = Adjudication { score: 2000, plies: 8 };
+
+/// A position with the score of its search, from the perspective of White, and the game result.
+#[derive(Debug, Copy, Clone)]
+pub struct DataEntry {
+    pub position: Position,
+    pub score: ValueScore,
+    pub winner: Option<Color>,
+}
+
+impl DataEntry {
+    /// Packs the entry as the occupancy bitboard, a nibble per occupied square in ascending order,
+    /// then the score, the result, the side to move, the en passant square,
+    /// the castling rights and the halfmove clock. The fullmove number is not kept.
+    pub fn encode(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        let board = &self.position.board;
+
+        let mut occupancy = 0_u64;
+        let mut nibble = 0;
+        for square in (0..64).map(|index| Square::from(index).unwrap()) {
+            if let Some((piece, color)) = board.piece_color_at(square) {
+                occupancy |= 1 << square as u8;
+                bytes[8 + nibble / 2] |= (piece as u8 | (color as u8) << 3) << (4 * (nibble % 2));
+                nibble += 1;
+            }
+        }
+        bytes[0..8].copy_from_slice(&occupancy.to_le_bytes());
+
+        bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+        bytes[26] = match self.winner {
+            Some(Color::Black) => 0,
+            None => 1,
+            Some(Color::White) => 2,
+        };
+        bytes[27] = self.position.side_to_move as u8 | (self.position.is_chess960 as u8) << 1;
+        bytes[28] = self.position.en_passant_square.map_or(NO_EN_PASSANT, |square| square as u8);
+        bytes[29] = self.position.castling_rights.bits();
+        bytes[30] = self.position.halfmove_clock;
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; ENTRY_SIZE]) -> Option<Self> {
+        let occupancy = u64::from_le_bytes(bytes[0..8].try_into().unwrap());
+        if occupancy.count_ones() > 32 {
+            return None;
+        }
+
+        let mut board = Board::default();
+        for (nibble, index) in (0..64).filter(|index| occupancy & (1 << index) != 0).enumerate() {
+            let code = bytes[8 + nibble / 2] >> (4 * (nibble % 2)) & 0b1111;
+            let piece = Piece::from(code & 0b111)?;
+            let color = Color::from(code >> 3)?;
+            board.set_square(Square::from(index)?, piece, color);
+        }
+
+        let position = Position {
+            board,
+            side_to_move: Color::from(bytes[27] & 1)?,
+            en_passant_square: match bytes[28] {
+                NO_EN_PASSANT => None,
+                square => Some(Square::from(square)?),
+            },
+            castling_rights: CastlingRights::from_bits(bytes[29])?,
+            halfmove_clock: bytes[30],
+            fullmove_number: 1,
+            is_chess960: bytes[27] & 0b10 != 0,
+        };
+
+        let winner = match bytes[26] {
+            0 => Some(Color::Black),
+            1 => None,
+            2 => Some(Color::White),
+            _ => return None,
+        };
+
+        Some(Self { position, score: i16::from_le_bytes([bytes[24], bytes[25]]), winner })
+    }
+}
+
+/// Reads back the entries written by [`generate`].
+pub fn decode_entries(bytes: &[u8]) -> Option<Vec<DataEntry>> {
+    if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+        return None;
+    }
+    bytes
+        .chunks_exact(ENTRY_SIZE)
+        .map(|chunk| DataEntry::decode(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Removes the entries whose position was already seen, keeping the first one.
+/// Positions reached in many games would otherwise weigh more in training.
+pub fn dedupe_entries(entries: &mut Vec<DataEntry>) {
+    let mut seen = HashSet::new();
+    entries.retain(|entry| seen.insert(entry.position.zobrist_hash()));
+}
+
+pub struct DatagenConfig {
+    pub games: usize,
+    /// Nodes searched for each move, as a soft limit.
+    pub nodes: usize,
+    /// Random moves played from the start position before the engine takes over.
+    pub random_plies: usize,
+    pub threads: usize,
+    pub seed: u64,
+}
+
+/// Plays the configured number of games, writing the entries of each finished game.
+/// Returns the number of entries written.
+pub fn generate(config: &DatagenConfig, writer: &mut (dyn Write + Send)) -> io::Result<usize> {
+    let next_game = AtomicUsize::new(0);
+    let written = AtomicUsize::new(0);
+    let writer = Mutex::new(writer);
+
+    thread::scope(|s| {
+        let handles = (0..config.threads.max(1))
+            .map(|_| {
+                s.spawn(|| -> io::Result<()> {
+                    let table = Arc::new(SearchTable::new(TABLE_SIZE_MB));
+
+                    loop {
+                        let game = next_game.fetch_add(1, Ordering::Relaxed);
+                        if game >= config.games {
+                            return Ok(());
+                        }
+
+                        let entries = play_game(config, game as u64, table.clone());
+                        let bytes =
+                            entries.iter().flat_map(|entry| entry.encode()).collect::<Vec<_>>();
+                        writer.lock().unwrap().write_all(&bytes)?;
+                        written.fetch_add(entries.len(), Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter().try_for_each(|handle| handle.join().unwrap())
+    })?;
+
+    writer.into_inner().unwrap().flush()?;
+    Ok(written.into_inner())
+}
+
+pub(crate) fn random_opening(rng: &mut StdRng, plies: usize) -> Option<Position> {
+    let mut position = Position::from_fen(START_FEN).unwrap();
+    for _ in 0..plies {
+        let moves = position.moves(MoveStage::All);
+        if moves.is_empty() {
+            return None;
+        }
+        position = position.make_move(moves[rng.gen_range(0..moves.len())]);
+    }
+    (!position.moves(MoveStage::All).is_empty()).then_some(position)
+}
+
+fn play_game(config: &DatagenConfig, game: u64, table: Arc<SearchTable>) -> Vec<DataEntry> {
+    let mut rng = StdRng::seed_from_u64(config.seed ^ game.wrapping_mul(0x9E3779B97F4A7C15));
+    let Some(opening) = random_opening(&mut rng, config.random_plies) else {
+        return Vec::new();
+    };
+
+    table.clear();
+    let params = SearchParams::default();
+    let player = || Player { params: &params, table: &table };
+    let mut entries = Vec::new();
+
+    let winner = selfplay::play_game(
+        &opening,
+        player(),
+        player(),
+        config.nodes,
+        ADJUDICATION,
+        |position, result| {
+            // Positions in check or with a tactical best move are not quiet enough to label.
+            if let (Some(Score::Value(score)), Some(best_move)) = (result.score, result.best_move) {
+                if !position.is_check() && best_move.flag().is_quiet() {
+                    let score = score * position.side_to_move.sign();
+                    entries.push(DataEntry { position: *position, score, winner: None });
+                }
+            }
+        },
+    );
+
+    entries.iter_mut().for_each(|entry| entry.winner = winner);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_entries, dedupe_entries, generate, DataEntry, DatagenConfig, ENTRY_SIZE};
+    use crate::position::{
+        fen::{FromFen, ToFen, KIWIPETE_WHITE_FEN},
+        Color, Position,
+    };
+
+    #[test]
+    fn entry_round_trip() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let entry = DataEntry { position, score: -42, winner: Some(Color::Black) };
+
+        let decoded = DataEntry::decode(&entry.encode()).unwrap();
+        assert_eq!(decoded.position.to_fen(), position.to_fen());
+        assert_eq!(decoded.score, -42);
+        assert_eq!(decoded.winner, Some(Color::Black));
+    }
+
+    #[test]
+    fn generates_reproducible_games() {
+        let config = DatagenConfig { games: 2, nodes: 500, random_plies: 8, threads: 1, seed: 3 };
+
+        let mut first = Vec::new();
+        let written = generate(&config, &mut first).unwrap();
+        assert!(written > 0);
+        assert_eq!(first.len(), written * ENTRY_SIZE);
+
+        let mut second = Vec::new();
+        generate(&config, &mut second).unwrap();
+        assert_eq!(first, second);
+
+        let entries = decode_entries(&first).unwrap();
+        assert_eq!(entries.len(), written);
+        assert!(entries.iter().all(|entry| !entry.position.is_check()));
+        assert!(decode_entries(&first[1..]).is_none());
+    }
+
+    #[test]
+    fn dedupes_repeated_positions() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let mut other = position;
+        other.side_to_move = Color::Black;
+
+        let mut entries = vec![
+            DataEntry { position, score: 10, winner: None },
+            DataEntry { position: other, score: 20, winner: None },
+            DataEntry { position, score: 30, winner: Some(Color::White) },
+        ];
+        dedupe_entries(&mut entries);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].score, 10);
+        assert_eq!(entries[1].score, 20);
+    }
+}
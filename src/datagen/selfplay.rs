@@ -0,0 +1,101 @@
+use crate::{
+    evaluation::{position::insufficient_material, Evaluable, Score, ValueScore},
+    position::{Color, Position},
+    search::{
+        constraint::SearchConstraint, history::BranchHistory, iterative_deepening,
+        observer::NoObserver, params::SearchParams, table::SearchTable, SearchResult, MAX_DEPTH,
+    },
+};
+use std::sync::{atomic::AtomicU16, Arc};
+
+const MAX_GAME_PLIES: usize = 400;
+
+/// Games are adjudicated once a side is this far ahead for a few moves in a row.
+#[derive(Debug, Copy, Clone)]
+pub struct Adjudication {
+    pub score: ValueScore,
+    pub plies: usize,
+}
+
+/// The search settings of one side of a self-play game.
+pub struct Player<'a> {
+    pub params: &'a SearchParams,
+    pub table: &'a Arc<SearchTable>,
+}
+
+/// Plays a game from the opening, searching each move up to a node limit.
+/// Each position searched is passed on with the result of its search, before its move is played.
+/// Returns the winner, or none if the game was drawn.
+pub fn play_game(
+    opening: &Position,
+    white: Player,
+    black: Player,
+    nodes: usize,
+    adjudication: Adjudication,
+    mut on_search: impl FnMut(&Position, &SearchResult),
+) -> Option<Color> {
+    let mut position = *opening;
+    let mut history = BranchHistory::new(Vec::new());
+    history.visit_position(&position, false);
+    let mut winning_streak = (None, 0);
+
+    loop {
+        if position.halfmove_clock >= 100
+            || history.repeated(&position) >= 3
+            || insufficient_material(&position)
+            || history.entries.len() > MAX_GAME_PLIES
+        {
+            return None;
+        }
+
+        let player = match position.side_to_move {
+            Color::White => &white,
+            Color::Black => &black,
+        };
+        let constraint = SearchConstraint {
+            number_threads: Arc::new(AtomicU16::new(1)),
+            game_history: history.entries.clone(),
+            max_nodes: Some(nodes),
+            search_params: *player.params,
+            ..Default::default()
+        };
+        let current_guess = position.value() * position.side_to_move.sign();
+        let result = iterative_deepening(
+            &position,
+            current_guess,
+            MAX_DEPTH,
+            player.table.clone(),
+            &constraint,
+            &NoObserver,
+        );
+        on_search(&position, &result);
+
+        let Some(best_move) = result.best_move else {
+            // Checkmate or stalemate.
+            return position.is_check().then_some(position.side_to_move.opposite());
+        };
+
+        let leader = match result.score {
+            Some(Score::Mate(color, _)) => Some(color),
+            Some(Score::Value(score)) if score >= adjudication.score => Some(position.side_to_move),
+            Some(Score::Value(score)) if score <= -adjudication.score => {
+                Some(position.side_to_move.opposite())
+            }
+            _ => None,
+        };
+        winning_streak = match (leader, winning_streak) {
+            (Some(leader), (Some(previous), plies)) if leader == previous => {
+                (Some(leader), plies + 1)
+            }
+            (leader, _) => (leader, 1),
+        };
+        if let (Some(leader), plies) = winning_streak {
+            if plies >= adjudication.plies {
+                return Some(leader);
+            }
+        }
+
+        position = position.make_move(best_move);
+        history.visit_position(&position, best_move.flag().is_reversible());
+    }
+}
@@ -0,0 +1,18 @@
+// The build details are written by the build script.
+include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
+/// Version and commit, as reported in the engine name.
+pub fn version() -> String {
+    format!("{} ({})", env!("CARGO_PKG_VERSION"), GIT_COMMIT)
+}
+
+/// Everything else known about the build, to include in bug reports.
+pub fn details() -> String {
+    let features =
+        if TARGET_FEATURES.is_empty() { "none".to_string() } else { TARGET_FEATURES.join(" ") };
+
+    format!(
+        "Built {} ({} profile), cpu features: {}, evaluation: handcrafted",
+        BUILD_DATE, PROFILE, features
+    )
+}
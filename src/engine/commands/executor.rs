@@ -1,23 +1,57 @@
-use crate::engine::{time::get_duration, Engine, DEFAULT_NUMBER_THREADS, MAX_THREADS};
+use crate::engine::{
+    build_info,
+    crash::{enter_search, leave_search, SearchContext},
+    elo::estimate_elo,
+    info::UciPrinter,
+    memory::auto_table_size_mb,
+    record::{start_recording, stop_recording},
+    time::{get_duration, get_hard_duration, update_latency},
+    Engine, ExploreAction, GoOptions, DEFAULT_CLOCK_SAFETY_MILLIS, DEFAULT_CONTEMPT, DEFAULT_ELO,
+    DEFAULT_ENGINE_RATING, DEFAULT_MOVE_OVERHEAD_MILLIS, DEFAULT_NUMBER_THREADS,
+    DEFAULT_PONDER_CREDIT, DEFAULT_VARIETY, DEFAULT_VARIETY_MOVES, EXPLORE_DEPTH, EXPLORE_PLIES,
+    MAX_CONTEMPT, MAX_MULTI_PV, MAX_RATING, MAX_THREADS, MAX_TIME_MARGIN_MILLIS, MAX_VARIETY,
+    MAX_VARIETY_MOVES,
+};
 use camel::{
-    evaluation::Evaluable,
-    moves::gen::{perft, MoveStage},
+    datagen::{decode_entries, dedupe_entries},
+    evaluation::{
+        params::EvalParams,
+        position::{evaluation_breakdown, EvaluationBreakdown},
+        Evaluable, Score, ValueScore,
+    },
+    moves::{
+        gen::{perft, MoveStage},
+        san::parse_san,
+        Move,
+    },
     position::{
-        fen::{FromFen, ToFen, START_FEN},
+        fen::{FromFen, ToFen, KIWIPETE_WHITE_FEN, START_FEN},
+        opening::OpeningTree,
+        pgn::{parse_pgn, split_pgn, PgnFilter},
+        render::{BoardRenderer, PieceSet},
         Color, Position,
     },
     search::{
+        analyze_batch,
         constraint::{SearchConstraint, TimeConstraint},
         history::HistoryEntry,
+        iterative_deepening,
+        montecarlo::monte_carlo,
+        observer::NoObserver,
+        params::SearchParams,
         pvs_aspiration_iterative,
-        table::{DEFAULT_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB, MIN_TABLE_SIZE_MB},
-        Depth, MAX_DEPTH,
+        strength::{StrengthLimit, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO, STRENGTH_LINES},
+        table::{SearchTable, DEFAULT_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB, MIN_TABLE_SIZE_MB},
+        tree::TreeDump,
+        AnalysisLimit, Depth, SearchResult, MAX_DEPTH,
     },
+    tuner::{evaluation_loss, parse_tuned_params},
 };
 use std::{
+    collections::HashSet,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
     thread,
     time::Duration,
@@ -26,6 +60,21 @@ use std::{
 const SMP_DEFAULT_THREADS: u16 = 4;
 const SMP_DEFAULT_TABLE_SIZE_MB: usize = DEFAULT_TABLE_SIZE_MB * SMP_DEFAULT_THREADS as usize;
 
+const RATING_CONTEMPT_DIVISOR: i32 = 20;
+const MAX_RATING_CONTEMPT: i32 = 30;
+const MUST_WIN_CONTEMPT: ValueScore = 50;
+
+const SCALING_THREADS: [u16; 3] = [1, 2, 4];
+const SCALING_MOVE_TIMES_MS: [u64; 3] = [100, 300, 1000];
+const SCALING_FENS: [&str; 6] = [
+    START_FEN,
+    KIWIPETE_WHITE_FEN,
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 9",
+    "2r3k1/1q1nbppp/r3p3/3pP3/pPpP4/P1Q2N2/2RN1PPP/2R4K b - - 0 23",
+    "8/5pk1/6p1/3P4/2P2K2/7P/8/8 w - - 0 45",
+    "r4rk1/1b2qppp/p1n1p3/1p1nP3/3P4/P1B2N2/1P2BPPP/R2Q1RK1 w - - 0 16",
+];
+
 pub fn execute_position(new_position: &Position, game_history: &[Position], engine: &mut Engine) {
     engine.position = *new_position;
     engine.game_history = game_history
@@ -34,72 +83,230 @@ pub fn execute_position(new_position: &Position, game_history: &[Position], engi
         .collect();
 }
 
-pub fn execute_go(
-    engine: &mut Engine,
-    depth: Option<u8>,
-    move_time: Option<Duration>,
-    players_time: (Option<Duration>, Option<Duration>),
-    players_increment: (Option<Duration>, Option<Duration>),
-    ponder: bool,
-) {
+fn game_contempt(engine: &Engine) -> ValueScore {
+    // Draws are worth less against weaker opponents and when only a win will do.
+    let rating_contempt = engine.opponent_rating.map_or(0, |opponent_rating| {
+        ((engine.engine_rating as i32 - opponent_rating as i32) / RATING_CONTEMPT_DIVISOR)
+            .clamp(-MAX_RATING_CONTEMPT, MAX_RATING_CONTEMPT) as ValueScore
+    });
+    let must_win_contempt = if engine.must_win { MUST_WIN_CONTEMPT } else { 0 };
+
+    (engine.contempt + rating_contempt + must_win_contempt).clamp(-MAX_CONTEMPT, MAX_CONTEMPT)
+}
+
+/// The rating, when asked for, takes over the skill level.
+fn strength_limit(engine: &Engine) -> Option<StrengthLimit> {
+    if engine.limit_strength {
+        Some(StrengthLimit::from_elo(engine.elo))
+    } else {
+        StrengthLimit::from_skill_level(engine.skill_level)
+    }
+}
+
+pub fn execute_go(engine: &mut Engine, options: GoOptions) {
     if !engine.stop.load(Ordering::Acquire) {
         return;
     }
 
+    // A stopped search may still be reporting its best move. Wait for it,
+    // so that it does not interfere with the state of the new one.
+    // It may also have panicked, in which case the panic hook already answered.
+    if let Some(search_thread) = engine.search_thread.take() {
+        let _ = search_thread.join();
+    }
+
+    let ponder = options.ponder;
     engine.pondering.store(ponder, Ordering::Release);
 
     let position = engine.position;
 
-    let mut white_time = players_time.0;
-    let mut black_time = players_time.1;
-
-    if white_time.is_some() && black_time.is_none() {
-        black_time = white_time;
-    } else if black_time.is_some() && white_time.is_none() {
-        white_time = black_time;
-    }
-
-    let white_increment = players_increment.0;
-    let black_increment = players_increment.1;
-
-    let calc_move_time = match move_time {
-        Some(t) => Some(t),
-        None if white_time.is_some() => Some(get_duration(
-            &position,
-            white_time.unwrap(),
-            black_time.unwrap(),
-            white_increment,
-            black_increment,
-            ponder,
-        )),
-        None => None,
+    // A single clock is taken to be shared by both players.
+    // Our clock is watched to learn how much the communication with the GUI really costs.
+    // An infinite search ignores the clock.
+    let (our_time, our_increment) = match position.side_to_move {
+        _ if options.infinite => (None, None),
+        Color::White => (options.white_time.or(options.black_time), options.white_increment),
+        Color::Black => (options.black_time.or(options.white_time), options.black_increment),
+    };
+    if let (Some(expected_clock), Some(our_time)) =
+        (engine.expected_clock.lock().unwrap().take(), our_time)
+    {
+        engine.latency = update_latency(engine.latency, expected_clock, our_time)
+            .min(Duration::from_millis(MAX_TIME_MARGIN_MILLIS));
+        if engine.verbose {
+            output!("info string latency {} ms", engine.latency.as_millis());
+        }
+    }
+    let overhead = Duration::from_millis(
+        engine.move_overhead_millis + engine.extra_overhead_millis.load(Ordering::Relaxed),
+    ) + engine.latency;
+
+    // A fixed move time may be used up entirely, whereas a share of the clock is only a target.
+    let time_constraint = match options.move_time.filter(|_| !options.infinite) {
+        Some(t) => Some(TimeConstraint {
+            initial_instant: std::time::Instant::now(),
+            move_time: t.saturating_sub(overhead),
+            soft_time: None,
+        }),
+        None => our_time.map(|our_time| {
+            let soft_time = get_duration(
+                &position,
+                our_time,
+                our_increment,
+                options.moves_to_go,
+                ponder,
+                overhead,
+            );
+            TimeConstraint {
+                initial_instant: std::time::Instant::now(),
+                move_time: get_hard_duration(soft_time, our_time, overhead),
+                soft_time: Some(soft_time),
+            }
+        }),
     };
 
     let stop_now = engine.stop.clone();
     let table = engine.table.clone();
+    engine.search_start = std::time::Instant::now();
+    engine.time_credit_millis = Arc::new(AtomicU64::new(0));
+    let (tree_dump, tree_dump_path) = match options.tree_dump {
+        Some((plies, path)) => (Some(Arc::new(Mutex::new(TreeDump::new(plies)))), Some(path)),
+        None => (None, None),
+    };
+
+    // A limited strength searches a few lines to pick from, within fewer nodes.
+    let strength = strength_limit(engine);
+    let multi_pv = match strength {
+        Some(_) => engine.multi_pv.max(STRENGTH_LINES),
+        None => engine.multi_pv,
+    };
+    let nodes = options
+        .nodes
+        .into_iter()
+        .chain(strength.map(|strength| strength.nodes()))
+        .min()
+        .filter(|_| !options.infinite);
 
     let constraint = SearchConstraint {
         game_history: engine.game_history.clone(),
-        time_constraint: calc_move_time
-            .map(|t| TimeConstraint { initial_instant: std::time::Instant::now(), move_time: t }),
+        time_constraint,
         global_stop: stop_now.clone(),
         threads_stop: Arc::new(AtomicBool::new(false)),
         ponder_mode: engine.pondering.clone(),
+        infinite: options.infinite,
         number_threads: engine.number_threads.clone(),
+        variety_margin: (engine.variety > 0 && position.fullmove_number <= engine.variety_moves)
+            .then_some(engine.variety),
+        tree_dump: tree_dump.clone(),
+        time_credit_millis: engine.time_credit_millis.clone(),
+        contempt: game_contempt(engine),
+        multi_pv,
+        excluded_root_moves: Vec::new(),
+        seldepth: Arc::new(AtomicU8::new(0)),
+        nodes: Arc::new(AtomicUsize::new(0)),
+        best_move_effort: Arc::new(AtomicU16::new(0)),
+        seed: engine.seed,
+        max_nodes: nodes,
+        searched_nodes: Arc::new(AtomicUsize::new(0)),
+        root_move: Arc::new(Mutex::new(None)),
+        mate_limit: options.mate,
+        strength,
+        eval_params: engine.eval_params.clone(),
+        search_params: SearchParams::default(),
     };
 
-    thread::spawn(move || {
-        stop_now.store(false, Ordering::Release);
-        let current_guess = position.value() * position.side_to_move.sign();
-        pvs_aspiration_iterative(
-            &position,
-            current_guess,
-            depth.map_or_else(|| MAX_DEPTH, |d| d as Depth),
-            table.clone(),
-            &constraint,
-        );
-        stop_now.store(true, Ordering::Release);
+    let go_instant = std::time::Instant::now();
+    let extra_overhead_millis = engine.extra_overhead_millis.clone();
+    let expected_clock = engine.expected_clock.clone();
+    let clock_safety = Duration::from_millis(engine.clock_safety_millis);
+    let move_overhead_millis = engine.move_overhead_millis;
+    let verbose = engine.verbose;
+    let show_wdl = engine.show_wdl;
+
+    enter_search(SearchContext {
+        position,
+        position_command: engine.position_command.clone(),
+        table: table.clone(),
+        stop: stop_now.clone(),
+        dump_path: engine.crash_dump_path.clone(),
     });
+
+    stop_now.store(false, Ordering::Release);
+    engine.search_thread = Some(thread::spawn(move || {
+        let current_guess =
+            position.value_with(&constraint.eval_params) * position.side_to_move.sign();
+        let printer = UciPrinter::new(show_wdl);
+        thread::scope(|s| {
+            s.spawn(|| printer.print_progress(&constraint, &table, go_instant));
+            pvs_aspiration_iterative(
+                &position,
+                current_guess,
+                options.depth.filter(|_| !options.infinite).map_or(MAX_DEPTH, |d| d as Depth),
+                table.clone(),
+                &constraint,
+                &printer,
+            );
+            printer.finish();
+        });
+        leave_search();
+
+        // The clock only ran from the start of the search if we were not pondering.
+        if let Some(our_time) = our_time.filter(|_| !ponder) {
+            *expected_clock.lock().unwrap() = Some(
+                (our_time + our_increment.unwrap_or_default()).saturating_sub(go_instant.elapsed()),
+            );
+        }
+
+        // After a ponder hit, the time spent pondering was not charged to our clock either.
+        if let Some(our_time) = our_time.filter(|_| !ponder) {
+            let margin = our_time.saturating_sub(go_instant.elapsed());
+            if verbose {
+                output!("info string clock margin {} ms", margin.as_millis());
+            }
+            if margin < clock_safety {
+                let shortfall = (clock_safety - margin).as_millis() as u64;
+                let extra =
+                    extra_overhead_millis.fetch_add(shortfall, Ordering::Relaxed) + shortfall;
+                output!(
+                    "info string warning: clock margin of {} ms is below {} ms, move overhead raised to {} ms",
+                    margin.as_millis(),
+                    clock_safety.as_millis(),
+                    move_overhead_millis + extra
+                );
+            }
+        }
+
+        if let (Some(tree_dump), Some(path)) = (tree_dump, tree_dump_path) {
+            if std::fs::write(&path, tree_dump.lock().unwrap().to_dot()).is_err() {
+                output!("Could not write search tree to {}", path);
+            }
+        }
+
+        stop_now.store(true, Ordering::Release);
+    }));
+}
+
+pub fn execute_monte_carlo(engine: &mut Engine, playouts: usize, depth: Depth) {
+    let constraint = SearchConstraint {
+        seed: engine.seed,
+        eval_params: engine.eval_params.clone(),
+        ..Default::default()
+    };
+    let stats = monte_carlo(&engine.position, playouts, depth, &constraint);
+
+    for stats in &stats {
+        output!(
+            "info string montecarlo move {} visits {} probability {:.3} score {:.3}",
+            stats.mov,
+            stats.visits,
+            stats.visits as f64 / playouts.max(1) as f64,
+            stats.score()
+        );
+    }
+    match stats.first() {
+        Some(stats) => output!("bestmove {}", stats.mov),
+        None => output!("bestmove 0000"),
+    }
 }
 
 pub fn execute_stop(engine: &mut Engine) {
@@ -108,66 +315,295 @@ pub fn execute_stop(engine: &mut Engine) {
 }
 
 pub fn execute_ponderhit(engine: &mut Engine) {
+    // Part of the time spent pondering is given back to the move time budget.
+    let pondered_millis = engine.search_start.elapsed().as_millis() as u64;
+    engine
+        .time_credit_millis
+        .store(pondered_millis * engine.ponder_credit as u64 / 100, Ordering::Relaxed);
     engine.pondering.store(false, Ordering::Release);
 }
 
 pub fn execute_uci() {
-    println!("id name Camel {}", env!("CARGO_PKG_VERSION"));
-    println!("id author Bruno Mendes");
+    output!("id name Camel {}", build_info::version());
+    output!("id author Bruno Mendes");
+    output!("info string {}", build_info::details());
 
-    println!(
+    output!(
         "option name Threads type spin default {} min 1 max {}",
-        DEFAULT_NUMBER_THREADS, MAX_THREADS
+        DEFAULT_NUMBER_THREADS,
+        MAX_THREADS
     );
-    println!(
+    output!(
         "option name Hash type spin default {} min {} max {}",
-        DEFAULT_TABLE_SIZE_MB, MIN_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB
+        DEFAULT_TABLE_SIZE_MB,
+        MIN_TABLE_SIZE_MB,
+        MAX_TABLE_SIZE_MB
+    );
+    output!("option name HugePages type check default false");
+    output!("option name Variety type spin default {} min 0 max {}", DEFAULT_VARIETY, MAX_VARIETY);
+    output!(
+        "option name VarietyMoves type spin default {} min 1 max {}",
+        DEFAULT_VARIETY_MOVES,
+        MAX_VARIETY_MOVES
+    );
+    output!("option name PonderCredit type spin default {} min 0 max 100", DEFAULT_PONDER_CREDIT);
+    output!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV);
+    output!(
+        "option name Contempt type spin default {} min -{} max {}",
+        DEFAULT_CONTEMPT,
+        MAX_CONTEMPT,
+        MAX_CONTEMPT
     );
-    println!("option name UCI_Chess960 type check default true",);
-    println!("option name Ponder type check default true",);
+    output!(
+        "option name EngineRating type spin default {} min 0 max {}",
+        DEFAULT_ENGINE_RATING,
+        MAX_RATING
+    );
+    output!(
+        "option name Move Overhead type spin default {} min 0 max {}",
+        DEFAULT_MOVE_OVERHEAD_MILLIS,
+        MAX_TIME_MARGIN_MILLIS
+    );
+    output!(
+        "option name ClockSafety type spin default {} min 0 max {}",
+        DEFAULT_CLOCK_SAFETY_MILLIS,
+        MAX_TIME_MARGIN_MILLIS
+    );
+    output!("option name Seed type spin default 0 min 0 max {}", u32::MAX);
+    output!("option name MustWin type check default false");
+    output!("option name CrashDump type string default <empty>");
+    output!("option name EvalFile type string default <empty>");
+    output!("option name EvalBreakdown type check default false");
+    output!("option name PieceSet type combo default unicode var unicode var ascii var fen");
+    output!("option name Verbose type check default false");
+    output!("option name UCI_Opponent type string default none");
+    output!("option name UCI_ShowWDL type check default false");
+    output!("option name UCI_LimitStrength type check default false");
+    output!(
+        "option name UCI_Elo type spin default {} min {} max {}",
+        DEFAULT_ELO,
+        MIN_ELO,
+        MAX_ELO
+    );
+    output!(
+        "option name Skill Level type spin default {} min 0 max {}",
+        MAX_SKILL_LEVEL,
+        MAX_SKILL_LEVEL
+    );
+    output!("option name UCI_Chess960 type check default true",);
+    output!("option name Ponder type check default true",);
 
-    println!("uciok");
+    output!("uciok");
 }
 
 pub fn execute_is_ready() {
-    println!("readyok");
+    output!("readyok");
 }
 
 pub fn execute_debug(_: bool) {}
 
 pub fn execute_set_option(name: &str, value: &str, engine: &mut Engine) {
     if name == "Hash" {
-        if let Ok(size) = value.parse::<usize>() {
-            engine.table.set_size(size.clamp(MIN_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB));
+        if value == "auto" {
+            let size = auto_table_size_mb(engine.available_memory_mb);
+            let huge_pages = engine.huge_pages;
+            resize_table(engine, size, huge_pages);
+            output!("info string hash set to {} MB", size);
+        } else if let Ok(size) = value.parse::<usize>() {
+            let huge_pages = engine.huge_pages;
+            resize_table(engine, size.clamp(MIN_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB), huge_pages);
+        }
+    } else if name == "HugePages" {
+        if let Ok(huge_pages) = value.parse::<bool>() {
+            let size = engine.table.size_mb();
+            resize_table(engine, size, huge_pages);
         }
     } else if name == "Threads" {
         if let Ok(threads) = value.parse::<u16>() {
             engine.number_threads.store(threads.clamp(1, MAX_THREADS), Ordering::Relaxed);
         }
+    } else if name == "Variety" {
+        if let Ok(variety) = value.parse::<ValueScore>() {
+            engine.variety = variety.clamp(0, MAX_VARIETY);
+        }
+    } else if name == "VarietyMoves" {
+        if let Ok(moves) = value.parse::<u16>() {
+            engine.variety_moves = moves.clamp(1, MAX_VARIETY_MOVES);
+        }
+    } else if name == "PonderCredit" {
+        if let Ok(credit) = value.parse::<u16>() {
+            engine.ponder_credit = credit.min(100);
+        }
+    } else if name == "MultiPV" {
+        if let Ok(multi_pv) = value.parse::<u8>() {
+            engine.multi_pv = multi_pv.clamp(1, MAX_MULTI_PV);
+        }
+    } else if name == "Contempt" {
+        if let Ok(contempt) = value.parse::<ValueScore>() {
+            engine.contempt = contempt.clamp(-MAX_CONTEMPT, MAX_CONTEMPT);
+        }
+    } else if name == "EngineRating" {
+        if let Ok(rating) = value.parse::<u16>() {
+            engine.engine_rating = rating.min(MAX_RATING);
+        }
+    } else if name == "Move Overhead" || name == "MoveOverhead" {
+        if let Ok(overhead) = value.parse::<u64>() {
+            engine.move_overhead_millis = overhead.min(MAX_TIME_MARGIN_MILLIS);
+        }
+    } else if name == "ClockSafety" {
+        if let Ok(safety) = value.parse::<u64>() {
+            engine.clock_safety_millis = safety.min(MAX_TIME_MARGIN_MILLIS);
+        }
+    } else if name == "Seed" {
+        // Zero keeps the random choices of the search unpredictable.
+        if let Ok(seed) = value.parse::<u32>() {
+            engine.seed = (seed != 0).then_some(seed as u64);
+        }
+    } else if name == "CrashDump" {
+        engine.crash_dump_path = (value != "<empty>").then_some(value.to_string());
+    } else if name == "EvalFile" {
+        // The file holds the parameters written by `tune --out`; empty restores the defaults.
+        if value == "<empty>" {
+            engine.eval_params = Arc::default();
+        } else if let Some(params) =
+            std::fs::read_to_string(value).ok().and_then(|text| parse_tuned_params(&text))
+        {
+            engine.eval_params = Arc::new(params);
+        } else {
+            output!("info string could not read evaluation parameters from {}", value);
+        }
+    } else if name == "MustWin" {
+        if let Ok(must_win) = value.parse::<bool>() {
+            engine.must_win = must_win;
+        }
+    } else if name == "EvalBreakdown" {
+        if let Ok(eval_breakdown) = value.parse::<bool>() {
+            engine.eval_breakdown = eval_breakdown;
+        }
+    } else if name == "PieceSet" {
+        if let Some(piece_set) = PieceSet::from_name(value) {
+            engine.piece_set = piece_set;
+        }
+    } else if name == "Verbose" {
+        if let Ok(verbose) = value.parse::<bool>() {
+            engine.verbose = verbose;
+        }
+    } else if name == "UCI_Opponent" {
+        // The value has the form "<title> <rating> <computer|human> <name>",
+        // where the rating may be "none".
+        engine.opponent_rating = value
+            .split_whitespace()
+            .nth(1)
+            .and_then(|rating| rating.parse::<u16>().ok())
+            .map(|rating| rating.min(MAX_RATING));
+    } else if name == "UCI_ShowWDL" {
+        if let Ok(show_wdl) = value.parse::<bool>() {
+            engine.show_wdl = show_wdl;
+        }
+    } else if name == "UCI_LimitStrength" {
+        if let Ok(limit_strength) = value.parse::<bool>() {
+            engine.limit_strength = limit_strength;
+        }
+    } else if name == "UCI_Elo" {
+        if let Ok(elo) = value.parse::<u16>() {
+            engine.elo = elo.clamp(MIN_ELO, MAX_ELO);
+        }
+    } else if name == "Skill Level" || name == "SkillLevel" {
+        if let Ok(level) = value.parse::<u8>() {
+            engine.skill_level = level.min(MAX_SKILL_LEVEL);
+        }
     } else if name == "Ponder" || name == "UCI_Chess960" {
         // The time management bonus already takes pondering into account, so do nothing.
         // The engine is compliant with Chess 960 by design, so do nothing.
     } else {
-        println!("Option not supported: {}", name);
+        output!("Option not supported: {}", name);
     }
 }
 
+/// Replaces the table by a new one, unless it already has the size and kind of pages asked for,
+/// since allocating large tables takes a while. A running search keeps the table it started
+/// with, so the new one takes effect from the next search.
+fn resize_table(engine: &mut Engine, size_mb: usize, huge_pages: bool) {
+    if engine.table.size_mb() == size_mb && engine.huge_pages == huge_pages {
+        return;
+    }
+
+    engine.table = Arc::new(SearchTable::with_huge_pages(size_mb, huge_pages));
+    engine.huge_pages = huge_pages;
+}
+
 pub fn execute_uci_new_game(engine: &mut Engine) {
     engine.position = Position::from_fen(START_FEN).unwrap();
     engine.game_history = Vec::new();
     engine.table.clear();
+    engine.extra_overhead_millis.store(0, Ordering::Relaxed);
+    *engine.expected_clock.lock().unwrap() = None;
 }
 
 pub fn execute_smp(engine: &mut Engine) {
-    engine.table.set_size(SMP_DEFAULT_TABLE_SIZE_MB);
-    println!("Changed hash size to {}.", SMP_DEFAULT_TABLE_SIZE_MB);
+    let huge_pages = engine.huge_pages;
+    resize_table(engine, SMP_DEFAULT_TABLE_SIZE_MB, huge_pages);
+    output!("Changed hash size to {}.", SMP_DEFAULT_TABLE_SIZE_MB);
 
     engine.number_threads.store(SMP_DEFAULT_THREADS, Ordering::Relaxed);
-    println!("Changed number of threads to {}.", SMP_DEFAULT_THREADS);
+    output!("Changed number of threads to {}.", SMP_DEFAULT_THREADS);
+}
+
+pub fn execute_scaling() {
+    output!("Scaling will run in the background and report results when done.");
+
+    thread::spawn(move || {
+        for threads in SCALING_THREADS {
+            for move_time in SCALING_MOVE_TIMES_MS.map(Duration::from_millis) {
+                let table = Arc::new(SearchTable::new(DEFAULT_TABLE_SIZE_MB * threads as usize));
+                let (mut depth, mut nodes, mut score, mut scored) = (0, 0, 0, 0);
+                let start = std::time::Instant::now();
+
+                for fen in SCALING_FENS {
+                    let position = Position::from_fen(fen).unwrap();
+                    let constraint = SearchConstraint {
+                        time_constraint: Some(TimeConstraint {
+                            initial_instant: std::time::Instant::now(),
+                            move_time,
+                            soft_time: None,
+                        }),
+                        number_threads: Arc::new(AtomicU16::new(threads)),
+                        ..Default::default()
+                    };
+
+                    table.clear();
+                    let result = iterative_deepening(
+                        &position,
+                        0,
+                        MAX_DEPTH,
+                        table.clone(),
+                        &constraint,
+                        &NoObserver,
+                    );
+
+                    depth += result.depth as usize;
+                    nodes += result.nodes;
+                    if let Some(Score::Value(value)) = result.score {
+                        score += value as i64;
+                        scored += 1;
+                    }
+                }
+
+                output!(
+                    "Threads {}, move time {}ms -> depth {:.1}, nps {}, score {}",
+                    threads,
+                    move_time.as_millis(),
+                    depth as f64 / SCALING_FENS.len() as f64,
+                    (nodes as f64 / start.elapsed().as_secs_f64()) as usize,
+                    score / scored.max(1),
+                );
+            }
+        }
+    });
 }
 
 pub fn execute_perft(depth: u8, position: &Position) {
-    println!("Perft will run in the background and report results when done.");
+    output!("Perft will run in the background and report results when done.");
 
     let position = *position;
 
@@ -176,28 +612,130 @@ pub fn execute_perft(depth: u8, position: &Position) {
         let nodes = perft::<false, true>(&position, depth);
         let elapsed = start.elapsed();
 
-        println!("Perft results for depth {}", depth);
-        println!("-> Nodes: {}", nodes);
-        println!("-> Time: {}s", elapsed.as_secs_f32());
-        println!("-> Mnps: {}", nodes as f64 / elapsed.as_secs_f64() / 1000000.0);
+        output!("Perft results for depth {}", depth);
+        output!("-> Nodes: {}", nodes);
+        output!("-> Time: {}s", elapsed.as_secs_f32());
+        output!("-> Mnps: {}", nodes as f64 / elapsed.as_secs_f64() / 1000000.0);
     });
 }
 
-pub fn execute_do_move(mov_str: &str, position: &mut Position) {
-    if let Some(mov) = position.moves(MoveStage::All).iter().find(|mov| mov.to_string() == mov_str)
-    {
-        *position = position.make_move(*mov);
+/// A legal move of the position, in uci or algebraic notation.
+fn find_move(position: &Position, mov_str: &str) -> Option<Move> {
+    position
+        .moves(MoveStage::All)
+        .into_iter()
+        .find(|mov| mov.to_string() == mov_str)
+        .or_else(|| parse_san(position, mov_str))
+}
+
+pub fn execute_do_move(mov_str: &str, engine: &mut Engine) {
+    let position = engine.position;
+    if let Some(mov) = find_move(&position, mov_str) {
+        engine.position = position.make_move(mov);
+
+        if engine.verbose {
+            output!("Last move: {}", mov.to_san(&position));
+            execute_display(engine);
+        }
     } else {
-        println!("Illegal move: {}", mov_str);
+        output!("Illegal move: {}", mov_str);
+    }
+}
+
+fn static_evaluation(position: &Position, params: &EvalParams) -> String {
+    // A centipawn score means nothing once the game is over.
+    if position.moves(MoveStage::All).is_empty() {
+        if position.is_check() {
+            return "checkmate".to_string();
+        }
+        return "stalemate/draw".to_string();
+    }
+    if position.halfmove_clock >= 100 {
+        return "stalemate/draw".to_string();
+    }
+
+    position.value_with(params).to_string()
+}
+
+pub fn execute_evaluate(engine: &Engine, json: bool) {
+    let breakdown = evaluation_breakdown(&engine.position, &engine.eval_params);
+    if json {
+        output!("{}", breakdown_json(&breakdown));
+        return;
+    }
+
+    output!("Static evaluation: {}", static_evaluation(&engine.position, &engine.eval_params));
+    if engine.eval_breakdown {
+        print_breakdown(&breakdown);
+    }
+}
+
+fn print_breakdown(breakdown: &EvaluationBreakdown) {
+    let phases = [&breakdown.midgame, &breakdown.endgame, &breakdown.blended];
+    let columns =
+        |values: [ValueScore; 3]| format!("{:>6}{:>6}{:>7}", values[0], values[1], values[2]);
+
+    let header = format!("{:>12} | {:^19} | {:^19} | {:^19}", "Term", "White", "Black", "Total");
+    output!("{}", header.trim_end());
+    output!("{:>12} | {1} | {1} | {1}", "", format!("{:>6}{:>6}{:>7}", "MG", "EG", "Blend"));
+
+    let terms = breakdown.blended[0].terms().map(|(name, _)| name);
+    for (index, name) in terms.iter().enumerate() {
+        let side = |color: usize| phases.map(|phase| phase[color].terms()[index].1);
+        let (white, black) = (side(0), side(1));
+        let total = [0, 1, 2].map(|phase| white[phase] - black[phase]);
+        output!("{:>12} | {} | {} | {}", name, columns(white), columns(black), columns(total));
+    }
+
+    let white = phases.map(|phase| phase[0].total());
+    let black = phases.map(|phase| phase[1].total());
+    let total = [0, 1, 2].map(|phase| white[phase] - black[phase]);
+    output!("{:>12} | {} | {} | {}", "total", columns(white), columns(black), columns(total));
+    output!("Sides are scored from their own perspective, totals from White's.");
+    output!("Midgame ratio: {}/255", breakdown.midgame_ratio);
+    if breakdown.value != total[2] {
+        output!("Endgame rules set the evaluation to {}.", breakdown.value);
     }
 }
 
-pub fn execute_display(position: &Position) {
-    print!("{}", position.board);
-    println!("{}", position.to_fen());
-    println!("Static evaluation: {}", position.value());
-    println!("Chess960: {}", position.is_chess960);
-    println!(
+fn breakdown_json(breakdown: &EvaluationBreakdown) -> String {
+    let side = |color: usize| {
+        let phases = [&breakdown.midgame, &breakdown.endgame, &breakdown.blended];
+        let term = |values: [ValueScore; 3]| {
+            format!("{{\"mg\":{},\"eg\":{},\"blended\":{}}}", values[0], values[1], values[2])
+        };
+        let mut terms = breakdown.blended[color]
+            .terms()
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| {
+                let values = phases.map(|phase| phase[color].terms()[index].1);
+                format!("\"{}\":{}", name.replace(' ', "_"), term(values))
+            })
+            .collect::<Vec<_>>();
+        terms.push(format!("\"total\":{}", term(phases.map(|phase| phase[color].total()))));
+        format!("{{{}}}", terms.join(","))
+    };
+
+    format!(
+        "{{\"value\":{},\"midgame_ratio\":{},\"white\":{},\"black\":{}}}",
+        breakdown.value,
+        breakdown.midgame_ratio,
+        side(0),
+        side(1)
+    )
+}
+
+pub fn execute_display(engine: &Engine) {
+    let position = &engine.position;
+    let piece_set = engine.piece_set;
+    #[cfg(feature = "terminal-color")]
+    let piece_set = camel::position::render::Colored(piece_set);
+    output!("{}", piece_set.render(&position.board).trim_end_matches('\n'));
+    output!("{}", position.to_fen());
+    output!("Static evaluation: {}", static_evaluation(position, &engine.eval_params));
+    output!("Chess960: {}", position.is_chess960);
+    output!(
         "{} to play.",
         match position.side_to_move {
             Color::White => "White",
@@ -206,29 +744,329 @@ pub fn execute_display(position: &Position) {
     );
 }
 
-pub fn execute_all_moves(position: &Position) {
+pub fn execute_all_moves(engine: &Engine, depth: Option<u8>) {
+    let position = engine.position;
     let moves = position.moves(MoveStage::All);
-    for mov in moves {
-        print!("{} ", mov);
+
+    let Some(depth) = depth else {
+        output!("{}", moves.iter().map(|mov| mov.to_string()).collect::<Vec<_>>().join(" "));
+        return;
+    };
+
+    let positions = moves.iter().map(|mov| position.make_move(*mov)).collect::<Vec<_>>();
+    let number_threads = engine.number_threads.load(Ordering::Relaxed) as usize;
+    let results = analyze_batch(&positions, AnalysisLimit::Depth(depth), number_threads);
+
+    let mut scored_moves = moves
+        .iter()
+        .zip(results)
+        .map(|(mov, result)| (*mov, move_score(&position, *mov, &result)))
+        .collect::<Vec<_>>();
+    scored_moves.sort_by_key(|(_, score)| std::cmp::Reverse(move_score_key(*score, &position)));
+
+    output!("{:<8}{:>8}", "Move", "Eval");
+    for (mov, score) in scored_moves {
+        output!("{:<8}{:>8}", mov.to_san(&position), format_move_score(score, &position));
+    }
+    output!(
+        "Evaluations are for {} to play, searched to depth {}.",
+        match position.side_to_move {
+            Color::White => "White",
+            Color::Black => "Black",
+        },
+        depth
+    );
+}
+
+/// Score of a move, from the search of the position it leaves, for the side that plays it.
+/// Values are in centipawns for that side. Moves that end the game are scored here,
+/// since there is nothing to search after them.
+fn move_score(position: &Position, mov: Move, result: &SearchResult) -> Option<Score> {
+    let new_position = position.make_move(mov);
+    if new_position.moves(MoveStage::All).is_empty() {
+        return Some(if new_position.is_check() {
+            Score::Mate(position.side_to_move, 1)
+        } else {
+            Score::Value(0)
+        });
+    }
+
+    match result.score {
+        Some(Score::Value(value)) => Some(Score::Value(-value)),
+        score => score,
+    }
+}
+
+fn move_score_key(score: Option<Score>, position: &Position) -> i32 {
+    match score {
+        Some(Score::Value(value)) => value as i32,
+        Some(Score::Mate(color, moves)) if color == position.side_to_move => {
+            i32::MAX - moves as i32
+        }
+        Some(Score::Mate(_, moves)) => i32::MIN + 1 + moves as i32,
+        None => i32::MIN,
+    }
+}
+
+fn format_move_score(score: Option<Score>, position: &Position) -> String {
+    match score {
+        Some(Score::Value(value)) => format!("{:+}", value),
+        Some(Score::Mate(color, moves)) if color == position.side_to_move => {
+            format!("#{}", moves)
+        }
+        Some(Score::Mate(_, moves)) => format!("#-{}", moves),
+        None => "-".to_string(),
     }
-    println!();
 }
 
 pub fn execute_help() {
-    println!("================================================================================");
-    println!("Camel is a UCI-compatible chess engine, primarily meant to be used inside a GUI.");
-    println!("You can review the UCI standard in https://backscattering.de/chess/uci/.");
-    println!("Camel also bundles support for custom commands, for debugging purposes:");
-    println!("   'smp': quickly enable multithreading with sensible defaults");
-    println!("   'perft <depth>': run perft on the current position with the given depth");
-    println!("   'move <move>': perform given move in uci notation on the current board");
-    println!("   'list': list legal moves available on the current position");
-    println!("   'display': print current position");
-    println!("   'help': print this help message");
-    println!("   'clear': clear the screen");
-    println!("   'quit': exit the program");
-    println!("For more information, please visit https://github.com/bdmendes/camel/.");
-    println!("================================================================================");
+    output!("================================================================================");
+    output!("Camel is a UCI-compatible chess engine, primarily meant to be used inside a GUI.");
+    output!("You can review the UCI standard in https://backscattering.de/chess/uci/.");
+    output!("Camel also bundles support for custom commands, for debugging purposes:");
+    output!("   'smp': quickly enable multithreading with sensible defaults");
+    output!("   'setoption name Hash value auto': size the hash table by the available memory");
+    output!("   'scaling': report search depth, speed and score over time and threads");
+    output!("   'perft <depth>': run perft on the current position with the given depth");
+    output!("   'go ... dump <plies> <file>': write the searched tree as a graphviz file");
+    output!("   'go montecarlo [playouts N] [depth N]': sample the moves with random playouts");
+    output!("   'position pgn <file>|<movetext>': set the position at the end of the game");
+    output!("   'position epd <line>': set the position of an EPD line, ignoring its opcodes");
+    output!("   'move <move>': perform given move, in uci or algebraic notation");
+    output!("   'list [scores [depth]]': list legal moves available on the current position,");
+    output!("      or rank them by a shallow search of each");
+    output!("   'display': print current position");
+    output!("   'evaluate [json]': print the static evaluation of the current position,");
+    output!("      split by side, term and phase with EvalBreakdown or as JSON");
+    output!("   'tune [--epd <file>] [--out <file>] [--positions|--threads|--epochs N]':");
+    output!("      tune the evaluation parameters and tables on labeled positions");
+    output!("   'shrink perft|search <depth> startpos|fen <fen> [moves ...]':");
+    output!("      remove moves and pieces while the given action still panics");
+    output!("   'evaltest [file]': print the evaluation loss over labeled EPD or .bin positions");
+    output!("   'elo <wins> <draws> <losses>': estimate the elo difference of a match result");
+    output!("   'record <file>|off': record the following input and output, with timestamps");
+    output!("   'replay <file> [notiming]': feed back recorded commands");
+    output!("   'pgnfilter <in> <out> [fens] [minelo|result|timecontrol|minply|maxply <value>]':");
+    output!(
+        "      keep the games matching all conditions, as PGN or as the FENs of their positions"
+    );
+    output!("   'dedupe <in> <out> [plies <n>]': drop the PGN games transposing into an");
+    output!("      earlier one after n plies, or the repeated positions of EPD and .bin files");
+    output!("   'explore load <file>|<move>|back': walk the openings of the games of a PGN");
+    output!("      file, with results and evaluations; 'explore' shows the current moves");
+    output!("   'help': print this help message");
+    output!("   'clear': clear the screen");
+    output!("   'quit': exit the program");
+    output!("Self-play training data is generated with 'camel datagen --out <file> [--games N]'");
+    output!("   [--nodes N] [--plies N] [--threads N] [--seed N], outside of this prompt.");
+    output!("Search parameters are tuned in self-play with 'camel spsa [--iterations N]'");
+    output!("   [--pairs N] [--nodes N] [--plies N] [--threads N] [--seed N] [--rate X].");
+    output!("For more information, please visit https://github.com/bdmendes/camel/.");
+    output!("================================================================================");
+}
+
+pub fn execute_eval_test(path: Option<&str>) {
+    match evaluation_loss(path) {
+        Some(loss) => output!("Evaluation loss: {:.6}", loss),
+        None => output!("Could not read labeled positions from {}", path.unwrap_or("the book")),
+    }
+}
+
+pub fn execute_elo(wins: u32, draws: u32, losses: u32) {
+    if let Some(estimate) = estimate_elo(wins, draws, losses) {
+        output!("Elo difference: {:.2} +/- {:.2}", estimate.elo, estimate.error);
+        output!("LOS: {:.2}%", estimate.los * 100.0);
+    } else {
+        output!("No games to estimate from.");
+    }
+}
+
+pub fn execute_record(path: Option<String>) {
+    match path {
+        Some(path) => {
+            if start_recording(&path).is_err() {
+                output!("Could not create recording file {}", path);
+            }
+        }
+        None => stop_recording(),
+    }
+}
+
+pub fn execute_pgn_filter(input: &str, output: &str, filter: &PgnFilter, fens: bool) {
+    let Ok(pgn) = std::fs::read_to_string(input) else {
+        output!("Could not read games from {}", input);
+        return;
+    };
+
+    let games = split_pgn(&pgn);
+    let mut kept = 0;
+    let mut contents = String::new();
+
+    for text in &games {
+        let Some(game) = parse_pgn(text) else {
+            continue;
+        };
+        if !filter.accepts(&game) {
+            continue;
+        }
+
+        kept += 1;
+        if fens {
+            for position in &game.positions {
+                contents.push_str(&position.to_fen());
+                contents.push('\n');
+            }
+        } else {
+            contents.push_str(text);
+            contents.push_str("\n\n");
+        }
+    }
+
+    if std::fs::write(output, contents).is_err() {
+        output!("Could not write to {}", output);
+        return;
+    }
+    output!("Kept {} of {} games", kept, games.len());
+}
+
+/// Games of a PGN text without those transposing into an earlier one after the given plies,
+/// with the number of games kept and read.
+fn dedupe_games(pgn: &str, plies: usize) -> (String, usize, usize) {
+    let games = split_pgn(pgn);
+    let mut seen = HashSet::new();
+    let mut kept = 0;
+    let mut contents = String::new();
+
+    for text in &games {
+        let Some(game) = parse_pgn(text) else {
+            continue;
+        };
+        if seen.insert(game.transposition_key(plies)) {
+            kept += 1;
+            contents.push_str(text);
+            contents.push_str("\n\n");
+        }
+    }
+
+    (contents, kept, games.len())
+}
+
+/// Lines of an EPD text without those repeating the position of an earlier one,
+/// with the number of lines kept and read. Lines without a position are kept.
+fn dedupe_lines(epd: &str) -> (String, usize, usize) {
+    let mut seen = HashSet::new();
+    let mut kept = 0;
+    let mut contents = String::new();
+
+    for line in epd.lines() {
+        let fen = line.split_whitespace().take(4).collect::<Vec<_>>().join(" ");
+        if Position::from_fen(&fen).is_none_or(|position| seen.insert(position.zobrist_hash())) {
+            kept += 1;
+            contents.push_str(line);
+            contents.push('\n');
+        }
+    }
+
+    (contents, kept, epd.lines().count())
+}
+
+pub fn execute_dedupe(input: &str, output: &str, plies: usize) {
+    let (contents, kept, total) = if input.ends_with(".bin") {
+        let Some(mut entries) = std::fs::read(input).ok().and_then(|bytes| decode_entries(&bytes))
+        else {
+            output!("Could not read entries from {}", input);
+            return;
+        };
+        let total = entries.len();
+        dedupe_entries(&mut entries);
+        let bytes = entries.iter().flat_map(|entry| entry.encode()).collect::<Vec<_>>();
+        (bytes, entries.len(), total)
+    } else {
+        let Ok(text) = std::fs::read_to_string(input) else {
+            output!("Could not read {}", input);
+            return;
+        };
+        let (contents, kept, total) =
+            if input.ends_with(".pgn") { dedupe_games(&text, plies) } else { dedupe_lines(&text) };
+        (contents.into_bytes(), kept, total)
+    };
+
+    if std::fs::write(output, contents).is_err() {
+        output!("Could not write to {}", output);
+        return;
+    }
+    output!("Kept {} of {}", kept, total);
+}
+
+pub fn execute_explore(action: ExploreAction, engine: &mut Engine) {
+    match action {
+        ExploreAction::Load(path) => {
+            let Ok(pgn) = std::fs::read_to_string(&path) else {
+                output!("Could not read games from {}", path);
+                return;
+            };
+            let tree = OpeningTree::from_pgn(&pgn, EXPLORE_PLIES);
+            output!("Loaded the first {} plies of {} games", EXPLORE_PLIES, tree.games());
+            engine.opening_tree = Some(tree);
+            engine.explored_positions.clear();
+        }
+        ExploreAction::Show => (),
+        ExploreAction::Move(mov_str) => {
+            let Some(mov) = find_move(&engine.position, &mov_str) else {
+                output!("Illegal move: {}", mov_str);
+                return;
+            };
+            engine.explored_positions.push(engine.position);
+            engine.position = engine.position.make_move(mov);
+        }
+        ExploreAction::Back => {
+            let Some(position) = engine.explored_positions.pop() else {
+                output!("No explored move to take back");
+                return;
+            };
+            engine.position = position;
+        }
+    }
+
+    print_explored_moves(engine);
+}
+
+fn print_explored_moves(engine: &Engine) {
+    let Some(tree) = &engine.opening_tree else {
+        output!("No games loaded; use 'explore load <file>' first");
+        return;
+    };
+
+    let position = engine.position;
+    let moves = tree.moves(&position);
+    if moves.is_empty() {
+        output!("No loaded game reaches this position");
+        return;
+    }
+
+    let positions = moves.iter().map(|(mov, _)| position.make_move(*mov)).collect::<Vec<_>>();
+    let number_threads = engine.number_threads.load(Ordering::Relaxed) as usize;
+    let results = analyze_batch(&positions, AnalysisLimit::Depth(EXPLORE_DEPTH), number_threads);
+
+    output!("{:<8}{:>8}{:>8}{:>8}", "Move", "Games", "Score", "Eval");
+    for ((mov, stats), result) in moves.iter().zip(results) {
+        let eval = format_move_score(move_score(&position, *mov, &result), &position);
+        output!(
+            "{:<8}{:>8}{:>7.1}%{:>8}",
+            mov.to_san(&position),
+            stats.games,
+            stats.score(position.side_to_move) * 100.0,
+            eval
+        );
+    }
+    output!(
+        "Scores and evaluations are for {} to play, searched to depth {}.",
+        match position.side_to_move {
+            Color::White => "White",
+            Color::Black => "Black",
+        },
+        EXPLORE_DEPTH
+    );
 }
 
 pub fn execute_clear() {
@@ -236,7 +1074,3 @@ pub fn execute_clear() {
         std::process::Command::new("cls");
     }
 }
-
-pub fn execute_quit() {
-    std::process::exit(0);
-}
@@ -1,18 +1,21 @@
-use std::collections::VecDeque;
-
-use camel::tuner::texel_tune;
+use std::{collections::VecDeque, time::Instant};
 
 use self::{
     executor::{
-        execute_all_moves, execute_clear, execute_debug, execute_display, execute_do_move,
-        execute_go, execute_help, execute_is_ready, execute_perft, execute_ponderhit,
-        execute_position, execute_quit, execute_set_option, execute_smp, execute_stop, execute_uci,
-        execute_uci_new_game,
+        execute_all_moves, execute_clear, execute_debug, execute_dedupe, execute_display,
+        execute_do_move, execute_elo, execute_eval_test, execute_evaluate, execute_explore,
+        execute_go, execute_help, execute_is_ready, execute_monte_carlo, execute_perft,
+        execute_pgn_filter, execute_ponderhit, execute_position, execute_record, execute_scaling,
+        execute_set_option, execute_smp, execute_stop, execute_uci, execute_uci_new_game,
+    },
+    parser::{
+        parse_debug, parse_dedupe, parse_elo, parse_explore, parse_go, parse_list, parse_move,
+        parse_perft, parse_pgn_filter, parse_position, parse_record, parse_replay,
+        parse_set_option,
     },
-    parser::{parse_debug, parse_go, parse_move, parse_perft, parse_position, parse_set_option},
 };
 
-use super::{Command, Engine};
+use super::{record::read_recording, run_shrink, run_tune, Command, Engine, ExploreAction};
 
 mod executor;
 mod parser;
@@ -38,12 +41,22 @@ pub fn parse_command(input: &str) -> Result<Command, ()> {
         "perft" => parse_perft(&mut words),
         "move" | "m" => parse_move(&mut words),
         "smp" => Ok(Command::Smp),
+        "scaling" => Ok(Command::Scaling),
         "display" | "d" => Ok(Command::Display),
-        "list" | "l" => Ok(Command::ListMoves),
+        "evaluate" | "eval" => Ok(Command::Evaluate { json: words.pop_front() == Some("json") }),
+        "list" | "l" => parse_list(&mut words),
         "help" | "h" => Ok(Command::Help),
         "clear" | "c" => Ok(Command::Clear),
         "quit" | "q" => Ok(Command::Quit),
-        "tune" => Ok(Command::Tune),
+        "tune" => Ok(Command::Tune(words.drain(..).map(str::to_string).collect())),
+        "shrink" => Ok(Command::Shrink(words.drain(..).map(str::to_string).collect())),
+        "evaltest" => Ok(Command::EvalTest(words.pop_front().map(str::to_string))),
+        "elo" => parse_elo(&mut words),
+        "record" => parse_record(&mut words),
+        "pgnfilter" => parse_pgn_filter(&mut words),
+        "dedupe" => parse_dedupe(&mut words),
+        "replay" => parse_replay(&mut words),
+        "explore" => parse_explore(&mut words),
         _ => Err(()),
     }
 }
@@ -53,22 +66,8 @@ pub fn execute_command(command: Command, engine: &mut Engine) {
         Command::Position { position, game_history } => {
             execute_position(&position, &game_history, engine)
         }
-        Command::Go {
-            depth,
-            move_time,
-            white_time,
-            black_time,
-            white_increment,
-            black_increment,
-            ponder,
-        } => execute_go(
-            engine,
-            depth,
-            move_time,
-            (white_time, black_time),
-            (white_increment, black_increment),
-            ponder,
-        ),
+        Command::Go(options) => execute_go(engine, options),
+        Command::MonteCarlo { playouts, depth } => execute_monte_carlo(engine, playouts, depth),
         Command::Stop => execute_stop(engine),
         Command::PonderHit => execute_ponderhit(engine),
         Command::Uci => execute_uci(),
@@ -79,15 +78,51 @@ pub fn execute_command(command: Command, engine: &mut Engine) {
         Command::IsReady => execute_is_ready(),
         Command::UCINewGame => execute_uci_new_game(engine),
         Command::Smp => execute_smp(engine),
+        Command::Scaling => execute_scaling(),
         Command::Perft(depth) => execute_perft(depth, &engine.position),
-        Command::DoMove { mov_str } => execute_do_move(&mov_str, &mut engine.position),
-        Command::Display => execute_display(&engine.position),
-        Command::ListMoves => execute_all_moves(&engine.position),
+        Command::DoMove { mov_str } => execute_do_move(&mov_str, engine),
+        Command::Display => execute_display(engine),
+        Command::Evaluate { json } => execute_evaluate(engine, json),
+        Command::ListMoves { depth } => execute_all_moves(engine, depth),
         Command::Help => execute_help(),
         Command::Clear => execute_clear(),
-        Command::Quit => execute_quit(),
-        Command::Tune => {
-            texel_tune();
+        // The input loop ends on quit, so that a running search is stopped and joined first.
+        Command::Quit => (),
+        Command::Tune(args) => run_tune(&args),
+        Command::Shrink(args) => run_shrink(&args),
+        Command::EvalTest(path) => execute_eval_test(path.as_deref()),
+        Command::Elo { wins, draws, losses } => execute_elo(wins, draws, losses),
+        Command::Record(path) => execute_record(path),
+        Command::PgnFilter { input, output, filter, fens } => {
+            execute_pgn_filter(&input, &output, &filter, fens)
+        }
+        Command::Dedupe { input, output, plies } => execute_dedupe(&input, &output, plies),
+        Command::Replay { path, timed } => execute_replay(&path, timed, engine),
+        Command::Explore(action) => execute_explore(action, engine),
+    }
+}
+
+fn execute_replay(path: &str, timed: bool, engine: &mut Engine) {
+    let entries = match read_recording(path) {
+        Ok(entries) => entries,
+        Err(_) => {
+            output!("Could not read recording from {}", path);
+            return;
+        }
+    };
+
+    let start = Instant::now();
+
+    for (timestamp, input) in entries {
+        if timed {
+            std::thread::sleep(timestamp.saturating_sub(start.elapsed()));
+        }
+
+        match parse_command(&input) {
+            Ok(Command::Record(_) | Command::Replay { .. }) => (),
+            Ok(Command::Quit) => break,
+            Ok(command) => execute_command(command, engine),
+            Err(_) => output!("Invalid recorded command: {}", input),
         }
     }
 }
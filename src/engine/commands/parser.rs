@@ -1,10 +1,13 @@
-use super::Command;
+use super::{Command, ExploreAction};
+use crate::engine::{GoOptions, DEDUPE_PLIES, LIST_DEPTH};
 use camel::{
     moves::gen::MoveStage,
     position::{
         fen::{FromFen, KIWIPETE_WHITE_FEN, START_FEN},
+        pgn::{parse_pgn, PgnFilter},
         Position,
     },
+    search::montecarlo::{DEFAULT_PLAYOUTS, DEFAULT_PLAYOUT_DEPTH},
 };
 use std::{collections::VecDeque, time::Duration};
 
@@ -15,7 +18,7 @@ pub fn parse_position(words: &mut VecDeque<&str>) -> Result<Command, ()> {
 
     while let Some(word) = words.pop_front() {
         match word {
-            "fen" => {
+            "fen" | "epd" => {
                 while let Some(word) = words.pop_front() {
                     if word == "moves" {
                         words.push_front(word);
@@ -42,6 +45,21 @@ pub fn parse_position(words: &mut VecDeque<&str>) -> Result<Command, ()> {
                     }
                 }
             }
+            "pgn" => {
+                // Either a game file or the movetext itself, played from the start position.
+                let rest = words.drain(..).collect::<Vec<_>>();
+                let pgn = match rest.as_slice() {
+                    [path] if std::path::Path::new(path).is_file() => {
+                        std::fs::read_to_string(path).map_err(|_| ())?
+                    }
+                    _ => rest.join(" "),
+                };
+
+                // The position at the end of the game, with its history for repetitions.
+                let game = parse_pgn(&pgn).ok_or(())?;
+                position = *game.positions.last().unwrap();
+                game_history = game.positions[1..].to_vec();
+            }
             "kiwi" | "kiwipete" => {
                 let kiwipete_position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
                 position = kiwipete_position;
@@ -55,13 +73,12 @@ pub fn parse_position(words: &mut VecDeque<&str>) -> Result<Command, ()> {
 }
 
 pub fn parse_go(words: &mut VecDeque<&str>) -> Result<Command, String> {
-    let mut depth = None;
-    let mut move_time = None;
-    let mut white_time = None;
-    let mut black_time = None;
-    let mut white_increment = None;
-    let mut black_increment = None;
-    let mut ponder = false;
+    if words.front() == Some(&"montecarlo") {
+        words.pop_front();
+        return parse_monte_carlo(words);
+    }
+
+    let mut options = GoOptions::default();
 
     loop {
         let word = words.pop_front();
@@ -71,51 +88,81 @@ pub fn parse_go(words: &mut VecDeque<&str>) -> Result<Command, String> {
         let word = word.unwrap();
         match word {
             "ponder" => {
-                ponder = true;
+                options.ponder = true;
+            }
+            "infinite" => {
+                options.infinite = true;
             }
             "depth" => {
                 let value = words.pop_front().ok_or("No value found")?;
-                depth = Some(value.parse::<u8>().map_err(|_| "Invalid depth value")?);
+                options.depth = Some(value.parse::<u8>().map_err(|_| "Invalid depth value")?);
             }
             "movetime" => {
                 let value = words.pop_front().ok_or("No value found")?;
-                move_time = Some(Duration::from_millis(
+                options.move_time = Some(Duration::from_millis(
                     value.parse::<u64>().map_err(|_| "Invalid movetime value")?,
                 ));
             }
+            "nodes" => {
+                let value = words.pop_front().ok_or("No value found")?;
+                options.nodes = Some(value.parse::<usize>().map_err(|_| "Invalid nodes value")?);
+            }
+            "mate" => {
+                let value = words.pop_front().ok_or("No value found")?;
+                options.mate = Some(value.parse::<u8>().map_err(|_| "Invalid mate value")?);
+            }
             "wtime" => {
                 let value = words.pop_front().ok_or("No value found")?;
-                white_time = Some(Duration::from_millis(value.parse::<u64>().unwrap_or(0)));
+                options.white_time = Some(Duration::from_millis(value.parse::<u64>().unwrap_or(0)));
             }
             "btime" => {
                 let value = words.pop_front().ok_or("No value found")?;
-                black_time = Some(Duration::from_millis(value.parse::<u64>().unwrap_or(0)));
+                options.black_time = Some(Duration::from_millis(value.parse::<u64>().unwrap_or(0)));
             }
             "winc" => {
                 let value = words.pop_front().ok_or("No value found")?;
-                white_increment = Some(Duration::from_millis(
+                options.white_increment = Some(Duration::from_millis(
                     value.parse::<u64>().map_err(|_| "Invalid winc value")?,
                 ));
             }
             "binc" => {
                 let value = words.pop_front().ok_or("No value found")?;
-                black_increment = Some(Duration::from_millis(
+                options.black_increment = Some(Duration::from_millis(
                     value.parse::<u64>().map_err(|_| "Invalid binc value")?,
                 ));
             }
+            "movestogo" => {
+                let value = words.pop_front().ok_or("No value found")?;
+                options.moves_to_go =
+                    Some(value.parse::<u16>().map_err(|_| "Invalid movestogo value")?);
+            }
+            "dump" => {
+                let plies = words.pop_front().ok_or("No value found")?;
+                let plies = plies.parse::<u8>().map_err(|_| "Invalid dump plies value")?;
+                let path = words.pop_front().ok_or("No dump file found")?;
+                options.tree_dump = Some((plies, path.to_string()));
+            }
             _ => {}
         }
     }
 
-    Ok(Command::Go {
-        depth,
-        move_time,
-        white_time,
-        black_time,
-        white_increment,
-        black_increment,
-        ponder,
-    })
+    Ok(Command::Go(options))
+}
+
+fn parse_monte_carlo(words: &mut VecDeque<&str>) -> Result<Command, String> {
+    let mut playouts = DEFAULT_PLAYOUTS;
+    let mut depth = DEFAULT_PLAYOUT_DEPTH;
+
+    while let Some(word) = words.pop_front() {
+        let value = words.pop_front().ok_or("No value found")?;
+        match word {
+            "playouts" => playouts = value.parse().map_err(|_| "Invalid playouts value")?,
+            "depth" => depth = value.parse().map_err(|_| "Invalid depth value")?,
+            _ => return Err(format!("Unknown montecarlo option {}", word)),
+        }
+    }
+
+    Ok(Command::MonteCarlo { playouts, depth })
 }
 
 pub fn parse_perft(words: &mut VecDeque<&str>) -> Result<Command, ()> {
@@ -123,6 +170,20 @@ pub fn parse_perft(words: &mut VecDeque<&str>) -> Result<Command, ()> {
     Ok(Command::Perft(depth))
 }
 
+pub fn parse_list(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    match words.pop_front() {
+        None => Ok(Command::ListMoves { depth: None }),
+        Some("scores") => {
+            let depth = match words.pop_front() {
+                Some(depth) => depth.parse::<u8>().map_err(|_| ())?,
+                None => LIST_DEPTH,
+            };
+            Ok(Command::ListMoves { depth: Some(depth.max(1)) })
+        }
+        Some(_) => Err(()),
+    }
+}
+
 pub fn parse_move(words: &mut VecDeque<&str>) -> Result<Command, ()> {
     let mov_str = words.pop_front().ok_or(())?.to_string();
     Ok(Command::DoMove { mov_str })
@@ -142,13 +203,92 @@ pub fn parse_set_option(words: &mut VecDeque<&str>) -> Result<Command, ()> {
         return Err(());
     }
 
-    let name = words.pop_front().ok_or(())?.to_string();
-
-    if words.pop_front().ok_or(())? != "value" {
+    // Some names, such as "Move Overhead", span several words too.
+    let mut name_words = Vec::new();
+    while let Some(word) = words.pop_front() {
+        if word == "value" {
+            break;
+        }
+        name_words.push(word);
+    }
+    let name = name_words.join(" ");
+    if name.is_empty() {
         return Err(());
     }
 
-    let value = words.pop_front().ok_or(())?.to_string();
+    // Some values, such as the opponent description, span several words.
+    let value = words.drain(..).collect::<Vec<_>>().join(" ");
+    if value.is_empty() {
+        return Err(());
+    }
 
     Ok(Command::SetOption { name, value })
 }
+
+pub fn parse_elo(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    let mut next_count = || words.pop_front().ok_or(())?.parse::<u32>().map_err(|_| ());
+    let wins = next_count()?;
+    let draws = next_count()?;
+    let losses = next_count()?;
+    Ok(Command::Elo { wins, draws, losses })
+}
+
+pub fn parse_pgn_filter(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    let input = words.pop_front().ok_or(())?.to_string();
+    let output = words.pop_front().ok_or(())?.to_string();
+    let mut filter = PgnFilter::default();
+    let mut fens = false;
+
+    while let Some(word) = words.pop_front() {
+        let mut value = || words.pop_front().ok_or(());
+        match word {
+            "fens" => fens = true,
+            "minelo" => filter.min_elo = Some(value()?.parse().map_err(|_| ())?),
+            "result" => filter.result = Some(value()?.to_string()),
+            "timecontrol" => filter.time_control = Some(value()?.to_string()),
+            "minply" => filter.min_plies = Some(value()?.parse().map_err(|_| ())?),
+            "maxply" => filter.max_plies = Some(value()?.parse().map_err(|_| ())?),
+            _ => return Err(()),
+        }
+    }
+
+    Ok(Command::PgnFilter { input, output, filter, fens })
+}
+
+pub fn parse_dedupe(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    let input = words.pop_front().ok_or(())?.to_string();
+    let output = words.pop_front().ok_or(())?.to_string();
+    let plies = match words.pop_front() {
+        Some("plies") => words.pop_front().ok_or(())?.parse().map_err(|_| ())?,
+        Some(_) => return Err(()),
+        None => DEDUPE_PLIES,
+    };
+    Ok(Command::Dedupe { input, output, plies })
+}
+
+pub fn parse_record(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    match words.pop_front().ok_or(())? {
+        "off" => Ok(Command::Record(None)),
+        path => Ok(Command::Record(Some(path.to_string()))),
+    }
+}
+
+pub fn parse_replay(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    let path = words.pop_front().ok_or(())?.to_string();
+    let timed = match words.pop_front() {
+        None => true,
+        Some("notiming") => false,
+        Some(_) => return Err(()),
+    };
+    Ok(Command::Replay { path, timed })
+}
+
+pub fn parse_explore(words: &mut VecDeque<&str>) -> Result<Command, ()> {
+    let action = match words.pop_front() {
+        None => ExploreAction::Show,
+        Some("load") => ExploreAction::Load(words.pop_front().ok_or(())?.to_string()),
+        Some("back") => ExploreAction::Back,
+        Some(mov_str) => ExploreAction::Move(mov_str.to_string()),
+    };
+    Ok(Command::Explore(action))
+}
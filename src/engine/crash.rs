@@ -0,0 +1,101 @@
+use super::build_info;
+use camel::{
+    moves::{gen::MoveStage, Move},
+    position::{fen::ToFen, Position},
+    search::table::SearchTable,
+};
+use std::{
+    any::Any,
+    fs,
+    panic::{self, PanicHookInfo},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+/// What is needed to answer the GUI if the running search panics.
+pub struct SearchContext {
+    pub position: Position,
+    pub position_command: String,
+    pub table: Arc<SearchTable>,
+    pub stop: Arc<AtomicBool>,
+    pub dump_path: Option<String>,
+}
+
+static SEARCH_CONTEXT: Mutex<Option<SearchContext>> = Mutex::new(None);
+
+fn search_context() -> MutexGuard<'static, Option<SearchContext>> {
+    SEARCH_CONTEXT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub fn enter_search(context: SearchContext) {
+    *search_context() = Some(context);
+}
+
+pub fn leave_search() {
+    search_context().take();
+}
+
+impl SearchContext {
+    /// The best move found so far, if it made it to the table, or else any legal move.
+    fn fallback_move(&self) -> Option<Move> {
+        let moves = self.position.moves(MoveStage::All);
+        self.table
+            .get_hash_move(&self.position)
+            .filter(|mov| moves.contains(mov))
+            .or(moves.first().copied())
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+fn write_dump(path: &str, context: &SearchContext, info: &PanicHookInfo) -> bool {
+    let location = info.location().map_or("unknown location".to_string(), |l| l.to_string());
+    let dump = format!(
+        "Camel {}\n{}\nPanic: {} at {}\nFEN: {}\nCommand: {}\n",
+        build_info::version(),
+        build_info::details(),
+        panic_message(info.payload()),
+        location,
+        context.position.to_fen(),
+        context.position_command
+    );
+    fs::write(path, dump).is_ok()
+}
+
+/// Makes a panic during a search stop it and still report a legal move,
+/// before the default hook runs. Panics outside of a search are left alone.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(context) = search_context().take() {
+            output!(
+                "info string error: search panicked with '{}'",
+                panic_message(info.payload()).replace('\n', " ")
+            );
+            context.stop.store(true, Ordering::Release);
+
+            if let Some(path) = &context.dump_path {
+                if write_dump(path, &context, info) {
+                    output!("info string crash dump written to {}", path);
+                } else {
+                    output!("info string could not write crash dump to {}", path);
+                }
+            }
+
+            match context.fallback_move() {
+                Some(mov) => output!("bestmove {}", mov),
+                None => output!("bestmove 0000"),
+            }
+        }
+
+        default_hook(info);
+    }));
+}
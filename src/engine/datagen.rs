@@ -0,0 +1,62 @@
+use camel::datagen::{generate, DatagenConfig};
+use std::{fs::File, io::BufWriter, time::Instant};
+
+const DEFAULT_GAMES: usize = 1000;
+const DEFAULT_NODES: usize = 5000;
+const DEFAULT_RANDOM_PLIES: usize = 8;
+
+fn parse_config(args: &[String]) -> Result<(DatagenConfig, String), String> {
+    let mut config = DatagenConfig {
+        games: DEFAULT_GAMES,
+        nodes: DEFAULT_NODES,
+        random_plies: DEFAULT_RANDOM_PLIES,
+        threads: 1,
+        seed: 0,
+    };
+    let mut out = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {}", flag))?;
+        let number = || value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value));
+        match flag.as_str() {
+            "--games" => config.games = number()?,
+            "--nodes" => config.nodes = number()?,
+            "--plies" => config.random_plies = number()?,
+            "--threads" => config.threads = number()?,
+            "--seed" => config.seed = number()? as u64,
+            "--out" => out = Some(value.clone()),
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+
+    Ok((config, out.ok_or("Missing --out <file>")?))
+}
+
+/// Runs `camel datagen --out <file> [--games N] [--nodes N] [--plies N] [--threads N] [--seed N]`.
+pub fn run_datagen(args: &[String]) {
+    let (config, out) = match parse_config(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            output!("{}", message);
+            return;
+        }
+    };
+
+    let Ok(file) = File::create(&out) else {
+        output!("Could not create {}", out);
+        return;
+    };
+
+    let start = Instant::now();
+    match generate(&config, &mut BufWriter::new(file)) {
+        Ok(entries) => output!(
+            "Wrote {} positions from {} games to {} in {:.1}s",
+            entries,
+            config.games,
+            out,
+            start.elapsed().as_secs_f64()
+        ),
+        Err(_) => output!("Could not write to {}", out),
+    }
+}
@@ -0,0 +1,51 @@
+const CONFIDENCE_95: f64 = 1.959964;
+
+pub struct EloEstimate {
+    pub elo: f64,
+    pub error: f64,
+    pub los: f64,
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+fn erf(x: f64) -> f64 {
+    // Abramowitz and Stegun approximation, accurate enough for reporting.
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+pub fn estimate_elo(wins: u32, draws: u32, losses: u32) -> Option<EloEstimate> {
+    let games = (wins + draws + losses) as f64;
+    if games == 0.0 {
+        return None;
+    }
+
+    let (wins, draws, losses) = (wins as f64, draws as f64, losses as f64);
+    let score = (wins + draws / 2.0) / games;
+
+    let deviation =
+        ((wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2))
+            / games)
+            .sqrt()
+            / games.sqrt();
+    let lower = elo_from_score((score - CONFIDENCE_95 * deviation).max(0.0));
+    let upper = elo_from_score((score + CONFIDENCE_95 * deviation).min(1.0));
+
+    let decisive = wins + losses;
+    let los = if decisive == 0.0 {
+        0.5
+    } else {
+        0.5 * (1.0 + erf((wins - losses) / (2.0 * decisive).sqrt()))
+    };
+
+    // The interval is unbounded when it touches a perfect score.
+    let error =
+        if lower.is_finite() && upper.is_finite() { (upper - lower) / 2.0 } else { f64::INFINITY };
+
+    Some(EloEstimate { elo: elo_from_score(score), error, los })
+}
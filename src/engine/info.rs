@@ -0,0 +1,140 @@
+use camel::{
+    evaluation::Score,
+    search::{
+        constraint::SearchConstraint,
+        nodes_per_second,
+        observer::{SearchInfo, SearchObserver},
+        table::SearchTable,
+        SearchResult,
+    },
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Prints the search progress as UCI info lines, followed by the best move.
+pub struct UciPrinter {
+    /// Whether the chances of a win, a draw and a loss are printed along with the score.
+    show_wdl: bool,
+    /// Whether the best move is out, after which nothing else is printed.
+    finished: AtomicBool,
+}
+
+impl UciPrinter {
+    pub fn new(show_wdl: bool) -> Self {
+        Self { show_wdl, finished: AtomicBool::new(false) }
+    }
+
+    /// Stops the progress reports. The output is held meanwhile, so that a report being
+    /// printed is not cut, nor printed after the best move.
+    pub fn finish(&self) {
+        let _stdout = std::io::stdout().lock();
+        self.finished.store(true, Ordering::Release);
+    }
+
+    /// Prints how a running search is doing every second, until it is finished, so that GUIs
+    /// show signs of life in the middle of long iterations.
+    pub fn print_progress(
+        &self,
+        constraint: &SearchConstraint,
+        table: &SearchTable,
+        start: Instant,
+    ) {
+        let mut last_report = Instant::now();
+        while !self.finished.load(Ordering::Acquire) {
+            thread::sleep(PROGRESS_POLL_INTERVAL);
+            if last_report.elapsed() < PROGRESS_INTERVAL {
+                continue;
+            }
+            last_report = Instant::now();
+
+            // The search may have finished while sleeping.
+            let _stdout = std::io::stdout().lock();
+            if self.finished.load(Ordering::Acquire) {
+                return;
+            }
+
+            let time = start.elapsed();
+            let nodes = constraint.searched_nodes.load(Ordering::Relaxed);
+            output!(
+                "info time {} nodes {} nps {} hashfull {}",
+                time.as_millis(),
+                nodes,
+                nodes_per_second(nodes, time),
+                table.hashfull_millis()
+            );
+            if let Some((mov, number)) = *constraint.root_move.lock().unwrap() {
+                output!("info currmove {} currmovenumber {}", mov, number);
+            }
+        }
+    }
+}
+
+impl SearchObserver for UciPrinter {
+    fn on_iteration(&self, info: &SearchInfo) {
+        let mut line = format!("info depth {} seldepth {} ", info.depth, info.seldepth);
+
+        if let Some(multi_pv) = info.multi_pv {
+            line += &format!("multipv {} ", multi_pv);
+        }
+
+        line += &match info.score {
+            Score::Value(score) => format!("score cp {} ", score),
+            Score::Mate(_, _) => format!("score mate {} ", info.mate_in().unwrap()),
+        };
+
+        if self.show_wdl {
+            let (win, draw, loss) = info.wdl;
+            line += &format!("wdl {} {} {} ", win, draw, loss);
+        }
+
+        output!(
+            "{}time {} nodes {} nps {} hashfull {} pv {}",
+            line,
+            info.time.as_millis().max(1),
+            info.nodes,
+            info.nps(),
+            info.hashfull,
+            info.pv.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ")
+        );
+
+        // Like the UCI refutation token: the move, then the line that answers it.
+        if let (Some(first), false) = (info.pv.first(), info.refutation.is_empty()) {
+            output!(
+                "info string refutation {} {}",
+                first,
+                info.refutation.iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ")
+            );
+        }
+    }
+
+    fn on_best_move(&self, result: &SearchResult) {
+        self.finish();
+
+        let Some(best_move) = result.best_move else {
+            return;
+        };
+
+        let ebf = match result.branching_factor {
+            Some(branching_factor) => format!(" string ebf {:.2}", branching_factor),
+            None => String::new(),
+        };
+        output!(
+            "info time {} nodes {} nps {}{}",
+            result.time.as_millis().max(1),
+            result.nodes,
+            result.nps(),
+            ebf
+        );
+
+        match result.ponder_move {
+            Some(ponder_move) => output!("bestmove {} ponder {}", best_move, ponder_move),
+            None => output!("bestmove {}", best_move),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use super::record::record_input;
+use std::{
+    io::BufRead,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+/// Reads the standard input on its own thread, so that `stop` and `quit` take effect at once,
+/// even while the main thread is busy with another command. `isready` is also answered here
+/// while a search runs. Every other line is passed on in order, so that the main thread sees
+/// the full conversation. The channel is closed when the input ends or after `quit`, which
+/// the main thread gets to finish the session properly.
+pub fn spawn_input_reader(stop: Arc<AtomicBool>, pondering: Arc<AtomicBool>) -> Receiver<String> {
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            record_input(&line);
+
+            let command = line.trim();
+            let quit = matches!(command, "quit" | "q");
+            if quit || command == "stop" {
+                pondering.store(false, Ordering::Release);
+                stop.store(true, Ordering::Release);
+            }
+
+            // The main thread may be busy, so a running search is vouched for here.
+            if command == "isready" && !stop.load(Ordering::Acquire) {
+                output!("readyok");
+                continue;
+            }
+
+            if sender.send(line).is_err() || quit {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
@@ -0,0 +1,24 @@
+use camel::search::table::{DEFAULT_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB};
+
+// Share of the available memory taken by the table in auto mode.
+const AUTO_TABLE_MEMORY_DIVISOR: usize = 4;
+
+/// Memory the system can still give out, in megabytes, if it can be told.
+pub fn available_memory_mb() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kilobytes = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+    Some(kilobytes / 1024)
+}
+
+/// Table size for the given available memory, as a power of two so that sizes stay familiar.
+/// It never goes below the default, which is also used when the memory is unknown.
+pub fn auto_table_size_mb(available_memory_mb: Option<usize>) -> usize {
+    let Some(available_memory_mb) = available_memory_mb else {
+        return DEFAULT_TABLE_SIZE_MB;
+    };
+
+    let share = available_memory_mb / AUTO_TABLE_MEMORY_DIVISOR;
+    let size = if share == 0 { 0 } else { 1 << share.ilog2() };
+    size.clamp(DEFAULT_TABLE_SIZE_MB, MAX_TABLE_SIZE_MB)
+}
@@ -1,66 +1,134 @@
 use self::commands::{execute_command, parse_command};
+pub use self::{datagen::run_datagen, shrink::run_shrink, spsa::run_spsa, tune::run_tune};
 use camel::{
+    evaluation::{params::EvalParams, ValueScore},
     position::{
         fen::{FromFen, START_FEN},
+        opening::OpeningTree,
+        pgn::PgnFilter,
+        render::PieceSet,
         Position,
     },
     search::{
         history::HistoryEntry,
+        strength::MAX_SKILL_LEVEL,
         table::{SearchTable, DEFAULT_TABLE_SIZE_MB},
     },
 };
 use std::{
     sync::{
-        atomic::{AtomicBool, AtomicU16},
-        Arc,
+        atomic::{AtomicBool, AtomicU16, AtomicU64},
+        Arc, Mutex,
     },
-    time::Duration,
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
+/// Prints a line of output, like `println!`, also writing it to the recording if one is on.
+macro_rules! output {
+    () => {
+        $crate::engine::record::print_line("")
+    };
+    ($($arg:tt)*) => {
+        $crate::engine::record::print_line(&format!($($arg)*))
+    };
+}
+
+mod build_info;
 mod commands;
+mod crash;
+mod datagen;
+mod elo;
+mod info;
+mod input;
+mod memory;
+mod record;
+mod shrink;
+mod spsa;
 mod time;
+mod tune;
 
 pub const DEFAULT_NUMBER_THREADS: u16 = 1;
-pub const MAX_THREADS: u16 = 8;
+pub const MAX_THREADS: u16 = 1024;
+pub const DEFAULT_VARIETY: ValueScore = 0;
+pub const MAX_VARIETY: ValueScore = 100;
+pub const DEFAULT_VARIETY_MOVES: u16 = 8;
+pub const MAX_VARIETY_MOVES: u16 = 40;
+pub const DEFAULT_PONDER_CREDIT: u16 = 50;
+pub const DEFAULT_CONTEMPT: ValueScore = 0;
+pub const MAX_CONTEMPT: ValueScore = 100;
+pub const DEFAULT_ENGINE_RATING: u16 = 2600;
+pub const MAX_RATING: u16 = 4000;
+pub const DEFAULT_ELO: u16 = 1500;
+pub const MAX_MULTI_PV: u8 = 16;
+pub const DEFAULT_MOVE_OVERHEAD_MILLIS: u64 = 10;
+pub const DEFAULT_CLOCK_SAFETY_MILLIS: u64 = 50;
+pub const MAX_TIME_MARGIN_MILLIS: u64 = 5000;
+pub const EXPLORE_PLIES: usize = 40;
+pub const EXPLORE_DEPTH: u8 = 8;
+pub const LIST_DEPTH: u8 = 6;
+pub const DEDUPE_PLIES: usize = 16;
+
+/// The limits and clock state given to a search by the `go` command.
+#[derive(Default)]
+pub struct GoOptions {
+    pub depth: Option<u8>,
+    pub move_time: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    /// Moves until the clock is refilled, in cyclic time controls.
+    pub moves_to_go: Option<u16>,
+    pub ponder: bool,
+    pub nodes: Option<usize>,
+    /// Moves within which a mate is looked for.
+    pub mate: Option<u8>,
+    /// Search until told to stop, whatever the other limits.
+    pub infinite: bool,
+    pub tree_dump: Option<(u8, String)>,
+}
 
 pub enum Command {
     // Standard UCI commands
-    Position {
-        position: Position,
-        game_history: Vec<Position>,
-    },
-    Go {
-        depth: Option<u8>,
-        move_time: Option<Duration>,
-        white_time: Option<Duration>,
-        black_time: Option<Duration>,
-        white_increment: Option<Duration>,
-        black_increment: Option<Duration>,
-        ponder: bool,
-    },
+    Position { position: Position, game_history: Vec<Position> },
+    Go(GoOptions),
+    MonteCarlo { playouts: usize, depth: u8 },
     Stop,
     PonderHit,
     Uci,
     Debug(bool),
     IsReady,
     UCINewGame,
-    SetOption {
-        name: String,
-        value: String,
-    },
+    SetOption { name: String, value: String },
 
     // Custom commands
     Smp,
+    Scaling,
     Perft(u8),
-    DoMove {
-        mov_str: String,
-    },
+    DoMove { mov_str: String },
     Display,
-    ListMoves,
+    Evaluate { json: bool },
+    ListMoves { depth: Option<u8> },
     Help,
     Clear,
     Quit,
-    Tune,
+    Tune(Vec<String>),
+    Shrink(Vec<String>),
+    EvalTest(Option<String>),
+    Elo { wins: u32, draws: u32, losses: u32 },
+    Record(Option<String>),
+    PgnFilter { input: String, output: String, filter: PgnFilter, fens: bool },
+    Dedupe { input: String, output: String, plies: usize },
+    Replay { path: String, timed: bool },
+    Explore(ExploreAction),
+}
+
+pub enum ExploreAction {
+    Load(String),
+    Show,
+    Move(String),
+    Back,
 }
 
 pub struct Engine {
@@ -70,6 +138,48 @@ pub struct Engine {
     pub stop: Arc<AtomicBool>,
     pub pondering: Arc<AtomicBool>,
     pub number_threads: Arc<AtomicU16>,
+    pub variety: ValueScore,
+    pub variety_moves: u16,
+    pub ponder_credit: u16,
+    pub search_start: Instant,
+    pub time_credit_millis: Arc<AtomicU64>,
+    pub search_thread: Option<JoinHandle<()>>,
+    pub contempt: ValueScore,
+    pub engine_rating: u16,
+    pub opponent_rating: Option<u16>,
+    pub must_win: bool,
+    pub multi_pv: u8,
+    pub seed: Option<u64>,
+    pub move_overhead_millis: u64,
+    /// Overhead added for the rest of the game after the clock ran too low.
+    pub extra_overhead_millis: Arc<AtomicU64>,
+    /// Delay measured between the GUI and the engine, also added to the move overhead.
+    pub latency: Duration,
+    /// What our clock should read at the next timed search, if the last one was timed.
+    pub expected_clock: Arc<Mutex<Option<Duration>>>,
+    pub clock_safety_millis: u64,
+    /// The last position command, kept for crash dumps.
+    pub position_command: String,
+    pub crash_dump_path: Option<String>,
+    pub eval_params: Arc<EvalParams>,
+    /// Whether evaluations are printed split by side, term and phase.
+    pub eval_breakdown: bool,
+    /// Memory available when the engine started, to size the table in auto mode.
+    pub available_memory_mb: Option<usize>,
+    /// Whether the table is backed by huge pages, where supported.
+    pub huge_pages: bool,
+    pub opening_tree: Option<OpeningTree>,
+    /// Positions left by moves played in the explorer, to go back to.
+    pub explored_positions: Vec<Position>,
+    /// How boards are drawn, for terminals that lack Unicode or mix up the colors.
+    pub piece_set: PieceSet,
+    pub verbose: bool,
+    /// Whether the chances of a win, a draw and a loss are reported with the score.
+    pub show_wdl: bool,
+    /// Whether the engine plays at the rating in `elo`, rather than at its skill level.
+    pub limit_strength: bool,
+    pub elo: u16,
+    pub skill_level: u8,
 }
 
 pub fn uci_loop() {
@@ -80,13 +190,48 @@ pub fn uci_loop() {
         table: Arc::new(SearchTable::new(DEFAULT_TABLE_SIZE_MB)),
         pondering: Arc::new(AtomicBool::new(false)),
         number_threads: Arc::new(AtomicU16::new(DEFAULT_NUMBER_THREADS)),
+        variety: DEFAULT_VARIETY,
+        variety_moves: DEFAULT_VARIETY_MOVES,
+        ponder_credit: DEFAULT_PONDER_CREDIT,
+        search_start: Instant::now(),
+        time_credit_millis: Arc::new(AtomicU64::new(0)),
+        search_thread: None,
+        contempt: DEFAULT_CONTEMPT,
+        engine_rating: DEFAULT_ENGINE_RATING,
+        opponent_rating: None,
+        must_win: false,
+        multi_pv: 1,
+        seed: None,
+        move_overhead_millis: DEFAULT_MOVE_OVERHEAD_MILLIS,
+        extra_overhead_millis: Arc::new(AtomicU64::new(0)),
+        latency: Duration::ZERO,
+        expected_clock: Arc::new(Mutex::new(None)),
+        clock_safety_millis: DEFAULT_CLOCK_SAFETY_MILLIS,
+        position_command: "position startpos".to_string(),
+        crash_dump_path: None,
+        eval_params: Arc::default(),
+        eval_breakdown: false,
+        available_memory_mb: memory::available_memory_mb(),
+        huge_pages: false,
+        opening_tree: None,
+        explored_positions: Vec::new(),
+        piece_set: PieceSet::default(),
+        verbose: false,
+        show_wdl: false,
+        limit_strength: false,
+        elo: DEFAULT_ELO,
+        skill_level: MAX_SKILL_LEVEL,
     };
 
-    println!("Camel {} by Bruno Mendes", env!("CARGO_PKG_VERSION"));
+    output!("Camel {} by Bruno Mendes", build_info::version());
+    output!("{}", build_info::details());
+
+    crash::install_panic_hook();
+
+    let input_lines = input::spawn_input_reader(engine.stop.clone(), engine.pondering.clone());
 
-    loop {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
+    // Once the input is over or quit, a running search is stopped and still gives its answer.
+    while let Ok(input) = input_lines.recv() {
         let input = input.trim();
 
         if input.is_empty() {
@@ -94,9 +239,22 @@ pub fn uci_loop() {
         }
 
         if let Ok(command) = parse_command(input) {
+            if matches!(command, Command::Position { .. }) {
+                engine.position_command = input.to_string();
+            }
+
+            if matches!(command, Command::Quit) {
+                break;
+            }
+
             execute_command(command, &mut engine);
         } else {
-            println!("Invalid command. Type 'help' to know more.");
+            output!("Invalid command. Type 'help' to know more.");
         }
     }
+
+    execute_command(Command::Stop, &mut engine);
+    if let Some(search_thread) = engine.search_thread.take() {
+        let _ = search_thread.join();
+    }
 }
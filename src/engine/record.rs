@@ -0,0 +1,74 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+const INPUT_MARK: &str = "<";
+const OUTPUT_MARK: &str = ">";
+
+/// The recording of the session, if one is on. It is shared by the thread reading the input
+/// and by all threads printing output, so that the whole dialogue is kept in order.
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+struct Recorder {
+    file: File,
+    start: Instant,
+}
+
+impl Recorder {
+    fn record(&mut self, mark: &str, line: &str) {
+        // Losing a line is preferable to disrupting the session being recorded.
+        let _ = writeln!(self.file, "{} {} {}", self.start.elapsed().as_millis(), mark, line);
+    }
+}
+
+/// Starts recording to a new file, replacing any recording already on.
+pub fn start_recording(path: &str) -> io::Result<()> {
+    let recorder = Recorder { file: File::create(path)?, start: Instant::now() };
+    *RECORDER.lock().unwrap_or_else(PoisonError::into_inner) = Some(recorder);
+    Ok(())
+}
+
+pub fn stop_recording() {
+    *RECORDER.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+fn record(mark: &str, line: &str) {
+    if let Some(recorder) = RECORDER.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+        recorder.record(mark, line);
+    }
+}
+
+/// Records a line of input as it was received, before it is understood.
+pub fn record_input(line: &str) {
+    record(INPUT_MARK, line);
+}
+
+/// Prints a line of output, recording it as well. Use it through `output!`.
+pub fn print_line(line: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    record(OUTPUT_MARK, line);
+}
+
+/// The input lines of a recording, with the time each one was received at.
+pub fn read_recording(path: &str) -> io::Result<Vec<(Duration, String)>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut parts = line.splitn(3, ' ');
+        if let (Some(millis), Some(INPUT_MARK), Some(input)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            if let Ok(millis) = millis.parse::<u64>() {
+                entries.push((Duration::from_millis(millis), input.to_string()));
+            }
+        }
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,241 @@
+use camel::{
+    moves::{gen::MoveStage, Move},
+    position::{
+        board::Piece,
+        fen::{FromFen, ToFen, START_FEN},
+        CastlingRights, Position,
+    },
+};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+// A candidate that neither fails nor finishes in this time is taken as not failing.
+const CANDIDATE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Copy, Clone)]
+enum Action {
+    Perft(u8),
+    Search(u8),
+}
+
+impl Action {
+    fn command(&self) -> String {
+        match self {
+            Action::Perft(depth) => format!("perft {}", depth),
+            Action::Search(depth) => format!("go depth {}", depth),
+        }
+    }
+
+    /// Start of the line printed once the action has finished.
+    fn finished_prefix(&self) -> &'static str {
+        match self {
+            Action::Perft(_) => "-> Nodes",
+            Action::Search(_) => "bestmove",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Reproducer {
+    start: Position,
+    moves: Vec<Move>,
+}
+
+impl Reproducer {
+    fn parse(words: &[String]) -> Result<Self, String> {
+        let mut words = words.iter().map(String::as_str);
+        let fen = match words.next() {
+            Some("startpos") => START_FEN.to_string(),
+            Some("fen") => {
+                words.by_ref().take_while(|word| *word != "moves").collect::<Vec<_>>().join(" ")
+            }
+            _ => return Err("Expected startpos or fen <fen>".to_string()),
+        };
+        let start = Position::from_fen(&fen).ok_or(format!("Invalid FEN {}", fen))?;
+
+        let move_words = words.skip_while(|word| *word == "moves").collect::<Vec<_>>();
+        let mut position = start;
+        let mut moves = Vec::new();
+        for word in move_words {
+            let mov = legal_move(&position, word).ok_or(format!("Illegal move {}", word))?;
+            position = position.make_move(mov);
+            moves.push(mov);
+        }
+
+        Ok(Self { start, moves })
+    }
+
+    fn position_command(&self) -> String {
+        let mut command = format!("position fen {}", self.start.to_fen());
+        if !self.moves.is_empty() {
+            command += " moves";
+            self.moves.iter().for_each(|mov| command += &format!(" {}", mov));
+        }
+        command
+    }
+
+    /// The same moves from another start, if they are all still legal there.
+    fn replayed_from(&self, start: Position) -> Option<Self> {
+        let mut position = start;
+        for mov in &self.moves {
+            let mov = legal_move(&position, &mov.to_string())?;
+            position = position.make_move(mov);
+        }
+        Some(Self { start, moves: self.moves.clone() })
+    }
+
+    /// Smaller variations, tried in order: fewer moves first, then fewer pieces.
+    fn candidates(&self) -> Vec<Self> {
+        let mut candidates = Vec::new();
+
+        if let Some((first, rest)) = self.moves.split_first() {
+            candidates.push(Self { start: self.start.make_move(*first), moves: rest.to_vec() });
+            candidates.push(Self {
+                start: self.start,
+                moves: self.moves[..self.moves.len() - 1].to_vec(),
+            });
+        }
+
+        if !self.start.castling_rights.is_empty() || self.start.en_passant_square.is_some() {
+            let mut simplified = self.start;
+            simplified.castling_rights = CastlingRights::empty();
+            simplified.en_passant_square = None;
+            candidates.extend(self.replayed_from(simplified));
+        }
+
+        for square in self.start.board.occupancy_bb_all() {
+            let Some((piece, _)) = self.start.board.piece_color_at(square) else {
+                continue;
+            };
+            if piece == Piece::King {
+                continue;
+            }
+
+            let mut start = self.start;
+            start.board.clear_square(square);
+            // Castling rights may refer to the removed rook.
+            if piece == Piece::Rook {
+                start.castling_rights = CastlingRights::empty();
+            }
+            if is_valid(&start) {
+                candidates.extend(self.replayed_from(start));
+            }
+        }
+
+        candidates
+    }
+}
+
+fn legal_move(position: &Position, word: &str) -> Option<Move> {
+    position.moves(MoveStage::All).into_iter().find(|mov| mov.to_string() == word)
+}
+
+/// Whether the side that just moved is not left in check.
+fn is_valid(position: &Position) -> bool {
+    let mut opponent_to_move = *position;
+    opponent_to_move.side_to_move = position.side_to_move.opposite();
+    opponent_to_move.en_passant_square = None;
+    !opponent_to_move.is_check()
+}
+
+fn forward_lines(stream: impl Read + Send + 'static, sender: Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Runs the action in a new engine process, since a panic may abort it.
+fn fails(reproducer: &Reproducer, action: Action) -> bool {
+    let Ok(executable) = std::env::current_exe() else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return false;
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    forward_lines(child.stdout.take().unwrap(), sender.clone());
+    forward_lines(child.stderr.take().unwrap(), sender);
+
+    let mut stdin = child.stdin.take().unwrap();
+    let _ = writeln!(stdin, "{}\n{}", reproducer.position_command(), action.command());
+
+    let failed = loop {
+        match receiver.recv_timeout(CANDIDATE_TIMEOUT) {
+            Ok(line) if line.contains("panicked") => break true,
+            Ok(line) if line.starts_with(action.finished_prefix()) => break false,
+            Ok(_) => continue,
+            // The process is gone without finishing.
+            Err(RecvTimeoutError::Disconnected) => break true,
+            Err(RecvTimeoutError::Timeout) => break false,
+        }
+    };
+
+    stop(child);
+    failed
+}
+
+fn stop(mut child: Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn parse_action(args: &[String]) -> Result<Action, String> {
+    let depth = args
+        .get(1)
+        .and_then(|depth| depth.parse().ok())
+        .ok_or("Expected a depth after the action".to_string())?;
+    match args.first().map(String::as_str) {
+        Some("perft") => Ok(Action::Perft(depth)),
+        Some("search") => Ok(Action::Search(depth)),
+        _ => Err("Expected perft or search".to_string()),
+    }
+}
+
+/// Runs `shrink perft|search <depth> startpos|fen <fen> [moves ...]`, removing moves and pieces
+/// while the action still panics, and prints the smallest failing position found.
+pub fn run_shrink(args: &[String]) {
+    let parsed = parse_action(args)
+        .and_then(|action| Ok((action, Reproducer::parse(args.get(2..).unwrap_or_default())?)));
+    let (action, mut reproducer) = match parsed {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            output!("{}", message);
+            return;
+        }
+    };
+
+    if !fails(&reproducer, action) {
+        output!("The failure does not reproduce.");
+        return;
+    }
+
+    let mut shrunk = true;
+    while shrunk {
+        shrunk = false;
+        for candidate in reproducer.candidates() {
+            if fails(&candidate, action) {
+                output!("Still failing: {}", candidate.position_command());
+                reproducer = candidate;
+                shrunk = true;
+                break;
+            }
+        }
+    }
+
+    output!("Minimal reproducer: {}", reproducer.position_command());
+}
@@ -0,0 +1,43 @@
+use camel::tuner::spsa::{spsa_tune, SpsaConfig};
+
+fn parse_config(args: &[String]) -> Result<SpsaConfig, String> {
+    let mut config = SpsaConfig::default();
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {}", flag))?;
+        let number = || value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value));
+        match flag.as_str() {
+            "--iterations" => config.iterations = number()?,
+            "--pairs" => config.pairs = number()?,
+            "--nodes" => config.nodes = number()?,
+            "--plies" => config.random_plies = number()?,
+            "--threads" => config.threads = number()?,
+            "--seed" => config.seed = number()? as u64,
+            "--rate" => {
+                config.learning_rate =
+                    value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value))?
+            }
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+
+    Ok(config)
+}
+
+/// Runs `camel spsa [--iterations N] [--pairs N] [--nodes N] [--plies N] [--threads N]
+/// [--seed N] [--rate X]`.
+pub fn run_spsa(args: &[String]) {
+    let config = match parse_config(args) {
+        Ok(config) => config,
+        Err(message) => {
+            output!("{}", message);
+            return;
+        }
+    };
+
+    match spsa_tune(&config) {
+        Some(params) => output!("Tuned search parameters: {:?}", params),
+        None => output!("Could not start {} threads", config.threads),
+    }
+}
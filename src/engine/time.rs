@@ -1,7 +1,13 @@
-use camel::position::{Color, Position};
+use camel::position::Position;
 use std::time::Duration;
 
 const TYPICAL_GAME_MOVES: u16 = 50;
+/// The search may go this many times over the time it aims for, when the best move is unsettled.
+const HARD_TIME_FACTOR: u32 = 3;
+/// Share of the clock a single move may never go beyond.
+const MAX_CLOCK_SHARE: u32 = 4;
+/// Weight of the previous latency estimate against the newest measure, out of 4.
+const LATENCY_SMOOTHING: u32 = 3;
 
 fn get_duration_based_on_moves(position: &Position, time: Duration) -> Duration {
     let expected_remaining_moves =
@@ -14,25 +20,29 @@ fn get_duration_based_on_moves(position: &Position, time: Duration) -> Duration
     regular_time.mul_f32(parabole_factor.max(0.8))
 }
 
+/// Share of the clock when it is refilled after the given number of moves.
+/// Half a move worth of time is kept in reserve.
+fn get_duration_based_on_moves_to_go(time: Duration, moves_to_go: u16) -> Duration {
+    time.div_f32(moves_to_go.max(1) as f32 + 0.5)
+}
+
 pub fn get_duration(
     position: &Position,
-    white_time: Duration,
-    black_time: Duration,
-    white_increment: Option<Duration>,
-    black_increment: Option<Duration>,
+    our_duration: Duration,
+    our_increment: Option<Duration>,
+    moves_to_go: Option<u16>,
     ponder: bool,
+    overhead: Duration,
 ) -> Duration {
-    let our_duration = match position.side_to_move {
-        Color::White => white_time,
-        Color::Black => black_time,
-    };
-    let our_increment = match position.side_to_move {
-        Color::White => white_increment,
-        Color::Black => black_increment,
+    // The overhead is lost on every move, so it is taken out of the clock before sharing it.
+    let our_duration = our_duration.saturating_sub(overhead);
+    // In cyclic time controls the clock is spread evenly over the moves until it is refilled,
+    // whereas in sudden death more time is spent in the middlegame.
+    let mut standard_move_time = match moves_to_go {
+        Some(moves_to_go) => get_duration_based_on_moves_to_go(our_duration, moves_to_go),
+        None => get_duration_based_on_moves(position, our_duration),
     };
 
-    let mut standard_move_time = get_duration_based_on_moves(position, our_duration);
-
     if ponder {
         standard_move_time += standard_move_time / 4;
     }
@@ -44,9 +54,28 @@ pub fn get_duration(
     if let Some(our_increment) = our_increment {
         let new_move_time = standard_move_time + our_increment.mul_f32(0.9);
         if new_move_time < our_duration {
-            return new_move_time;
+            return new_move_time.saturating_sub(overhead);
         }
     }
 
-    standard_move_time
+    standard_move_time.saturating_sub(overhead)
+}
+
+/// Time after which the search is aborted, given the time it aims for.
+pub fn get_hard_duration(
+    soft_time: Duration,
+    our_duration: Duration,
+    overhead: Duration,
+) -> Duration {
+    (soft_time * HARD_TIME_FACTOR)
+        .min(our_duration.saturating_sub(overhead) / MAX_CLOCK_SHARE)
+        .max(soft_time)
+}
+
+/// Updates the latency estimate with the clock the GUI reports against the one we expected,
+/// which is what the last clock was left with after our own measure of the time we took.
+/// Any time the GUI charged beyond that was lost on the way between us.
+pub fn update_latency(latency: Duration, expected_clock: Duration, clock: Duration) -> Duration {
+    let lag = expected_clock.saturating_sub(clock);
+    (latency * LATENCY_SMOOTHING + lag) / (LATENCY_SMOOTHING + 1)
 }
@@ -0,0 +1,44 @@
+use camel::tuner::{texel_tune, TuneConfig};
+
+fn parse_config(args: &[String]) -> Result<(TuneConfig, Option<String>), String> {
+    let mut config = TuneConfig::default();
+    let mut out = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {}", flag))?;
+        let number = || value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value));
+        match flag.as_str() {
+            "--epd" => config.path = value.clone(),
+            "--positions" => config.positions = Some(number()?),
+            "--threads" => config.threads = number()?,
+            "--epochs" => config.epochs = number()?,
+            "--out" => out = Some(value.clone()),
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+    }
+
+    Ok((config, out))
+}
+
+/// Runs `tune [--epd <file>] [--positions N] [--threads N] [--epochs N] [--out <file>]`.
+pub fn run_tune(args: &[String]) {
+    let (config, out) = match parse_config(args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            output!("{}", message);
+            return;
+        }
+    };
+
+    let Some(parameters) = texel_tune(&config) else {
+        output!("Could not read labeled positions from {}", config.path);
+        return;
+    };
+
+    if let Some(out) = out {
+        if std::fs::write(&out, format!("{:?}\n", parameters)).is_err() {
+            output!("Could not write to {}", out);
+        }
+    }
+}
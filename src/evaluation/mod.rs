@@ -1,8 +1,14 @@
-use crate::position::{board::Piece, Color};
+use self::params::{EvalParams, DEFAULT_EVAL_PARAMS};
+use crate::{
+    position::{board::Piece, Color},
+    search::Depth,
+};
 
 pub mod moves;
+pub mod params;
 pub mod position;
 pub mod psqt;
+pub mod wdl;
 
 pub type ValueScore = i16;
 
@@ -12,11 +18,8 @@ pub type ValueScore = i16;
 const MATE_SCORE_THRESHOLD: ValueScore = 200;
 pub const MATE_SCORE: ValueScore = ValueScore::MIN + 200;
 
-pub static mut PAWN_VALUE: ValueScore = 94;
-pub static mut KNIGHT_VALUE: ValueScore = 347;
-pub static mut BISHOP_VALUE: ValueScore = 348;
-pub static mut ROOK_VALUE: ValueScore = 553;
-pub static mut QUEEN_VALUE: ValueScore = 1136;
+/// Indexed by piece.
+pub const PIECE_VALUES: [ValueScore; 6] = [1136, 553, 348, 347, 94, 6000];
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Score {
@@ -24,28 +27,99 @@ pub enum Score {
     Value(ValueScore),
 }
 
+// Search values are relative to the side to move. Mates are counted in plies from the root,
+// so that shorter mates are preferred; the helpers below are the only place to convert them.
 impl Score {
     pub fn is_mate(score: ValueScore) -> bool {
         !((MATE_SCORE + MATE_SCORE_THRESHOLD)..=(MATE_SCORE.abs() - MATE_SCORE_THRESHOLD))
             .contains(&score)
     }
+
+    /// Value of giving mate at the given ply.
+    pub const fn mate_in(ply: Depth) -> ValueScore {
+        -MATE_SCORE - ply as ValueScore
+    }
+
+    /// Value of being mated at the given ply.
+    pub const fn mated_in(ply: Depth) -> ValueScore {
+        MATE_SCORE + ply as ValueScore
+    }
+
+    /// Makes a mate value count plies from the node at the given ply instead of from the root,
+    /// so that it can be stored independently of the path to the node.
+    pub fn to_node_relative(score: ValueScore, ply: Depth) -> ValueScore {
+        match score {
+            score if !Self::is_mate(score) => score,
+            score if score > 0 => score + ply as ValueScore,
+            score => score - ply as ValueScore,
+        }
+    }
+
+    /// Reverses `to_node_relative`.
+    pub fn to_root_relative(score: ValueScore, ply: Depth) -> ValueScore {
+        match score {
+            score if !Self::is_mate(score) => score,
+            score if score > 0 => score - ply as ValueScore,
+            score => score + ply as ValueScore,
+        }
+    }
+
+    /// Converts a root search value to a score, with mates in full moves.
+    pub fn from_search(score: ValueScore, side_to_move: Color) -> Self {
+        if !Self::is_mate(score) {
+            return Score::Value(score);
+        }
+
+        let plies_to_mate = (MATE_SCORE.abs() - score.abs()) as u8;
+        let winner = if score > 0 { side_to_move } else { side_to_move.opposite() };
+        Score::Mate(winner, (plies_to_mate + 1) / 2)
+    }
 }
 
 pub trait Evaluable {
-    fn value(&self) -> ValueScore;
+    fn value_with(&self, params: &EvalParams) -> ValueScore;
+
+    fn value(&self) -> ValueScore {
+        self.value_with(&DEFAULT_EVAL_PARAMS)
+    }
 }
 
 impl Evaluable for Piece {
-    fn value(&self) -> ValueScore {
-        unsafe {
-            match self {
-                Piece::Pawn => PAWN_VALUE,
-                Piece::Knight => KNIGHT_VALUE,
-                Piece::Bishop => BISHOP_VALUE,
-                Piece::Rook => ROOK_VALUE,
-                Piece::Queen => QUEEN_VALUE,
-                Piece::King => 6000,
-            }
-        }
+    fn value_with(&self, params: &EvalParams) -> ValueScore {
+        params.piece_values[*self as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Score;
+    use crate::position::Color;
+
+    #[test]
+    fn mate_values() {
+        assert!(Score::is_mate(Score::mate_in(3)));
+        assert!(Score::is_mate(Score::mated_in(4)));
+        assert!(Score::mate_in(1) > Score::mate_in(3));
+        assert!(Score::mated_in(1) < Score::mated_in(3));
+        assert!(!Score::is_mate(0));
+
+        assert_eq!(
+            Score::from_search(Score::mate_in(3), Color::White),
+            Score::Mate(Color::White, 2)
+        );
+        assert_eq!(
+            Score::from_search(Score::mated_in(4), Color::White),
+            Score::Mate(Color::Black, 2)
+        );
+        assert_eq!(Score::from_search(120, Color::Black), Score::Value(120));
+    }
+
+    #[test]
+    fn mate_values_relative_to_node() {
+        // Mated 5 plies from the root is mated 2 plies after a node at ply 3.
+        assert_eq!(Score::to_node_relative(Score::mated_in(5), 3), Score::mated_in(2));
+        assert_eq!(Score::to_node_relative(Score::mate_in(5), 3), Score::mate_in(2));
+        assert_eq!(Score::to_root_relative(Score::mate_in(2), 3), Score::mate_in(5));
+        assert_eq!(Score::to_root_relative(Score::to_node_relative(-35, 7), 7), -35);
     }
 }
@@ -1,4 +1,4 @@
-use super::{psqt::psqt_value, Evaluable, ValueScore};
+use super::{params::DEFAULT_EVAL_PARAMS, psqt::psqt_value, Evaluable, ValueScore};
 use crate::{
     moves::Move,
     position::{board::Piece, Position},
@@ -24,8 +24,8 @@ pub fn evaluate_move(position: &Position, mov: Move) -> ValueScore {
         score += promotion_piece.value();
     }
 
-    score += psqt_value(moving_piece, mov.to(), position.side_to_move, 0);
-    score -= psqt_value(moving_piece, mov.from(), position.side_to_move, 0);
+    score += psqt_value(moving_piece, mov.to(), position.side_to_move, 0, &DEFAULT_EVAL_PARAMS);
+    score -= psqt_value(moving_piece, mov.from(), position.side_to_move, 0, &DEFAULT_EVAL_PARAMS);
 
     score
 }
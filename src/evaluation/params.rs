@@ -0,0 +1,82 @@
+use super::{
+    position::{
+        bishops::{BISHOP_PAIR_BONUS, BISHOP_XRAY_BONUS},
+        king::{OPEN_FILE_ATTACK_BONUS, PAWN_STORM_BONUS, SHELTER_PENALTY},
+        pawns::{
+            DOUBLED_PAWNS_PENALTY, PASSED_PAWN_BONUS, PAWN_ISLAND_PENALTY, PHALANX_PAWN_BONUS,
+            SUPPORTED_PAWN_BONUS,
+        },
+        queens::BATTERY_BONUS,
+        rooks::{DOUBLED_ROOKS_BONUS, OPEN_FILE_BONUS, SEMI_OPEN_FILE_BONUS},
+        trapped::{TRAPPED_BISHOP_PENALTY, TRAPPED_KNIGHT_PENALTY, TRAPPED_ROOK_PENALTY},
+        MIDGAME_RATIOS,
+    },
+    psqt::{
+        PieceSquareTable, ENDGAME_PSQT, KING_FILE_BUCKETS, KING_FILE_PIECES, KING_FILE_PSQT,
+        MIDGAME_PSQT,
+    },
+    ValueScore, PIECE_VALUES,
+};
+
+/// The tunable terms of the evaluation. Each search reads its own set through its constraint,
+/// so that different sets can be evaluated at the same time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    /// Indexed by piece.
+    pub piece_values: [ValueScore; 6],
+    /// Weight of each piece, indexed by piece, in how far the game is from the endgame.
+    pub midgame_ratios: [ValueScore; 6],
+    pub bishop_pair_bonus: ValueScore,
+    pub bishop_xray_bonus: ValueScore,
+    pub shelter_penalty: ValueScore,
+    pub pawn_storm_bonus: [ValueScore; 8],
+    pub open_file_attack_bonus: ValueScore,
+    pub doubled_pawns_penalty: ValueScore,
+    pub pawn_island_penalty: ValueScore,
+    pub passed_pawn_bonus: [ValueScore; 8],
+    pub phalanx_pawn_bonus: [ValueScore; 8],
+    pub supported_pawn_bonus: [ValueScore; 8],
+    pub battery_bonus: ValueScore,
+    pub semi_open_file_bonus: ValueScore,
+    pub open_file_bonus: ValueScore,
+    pub doubled_rooks_bonus: ValueScore,
+    pub trapped_bishop_penalty: ValueScore,
+    pub trapped_knight_penalty: ValueScore,
+    pub trapped_rook_penalty: ValueScore,
+    pub king_file_psqt: [[[ValueScore; 8]; KING_FILE_PIECES]; KING_FILE_BUCKETS],
+    /// Indexed by piece.
+    pub midgame_psqt: [PieceSquareTable; 6],
+    /// Indexed by piece.
+    pub endgame_psqt: [PieceSquareTable; 6],
+}
+
+pub const DEFAULT_EVAL_PARAMS: EvalParams = EvalParams {
+    piece_values: PIECE_VALUES,
+    midgame_ratios: MIDGAME_RATIOS,
+    bishop_pair_bonus: BISHOP_PAIR_BONUS,
+    bishop_xray_bonus: BISHOP_XRAY_BONUS,
+    shelter_penalty: SHELTER_PENALTY,
+    pawn_storm_bonus: PAWN_STORM_BONUS,
+    open_file_attack_bonus: OPEN_FILE_ATTACK_BONUS,
+    doubled_pawns_penalty: DOUBLED_PAWNS_PENALTY,
+    pawn_island_penalty: PAWN_ISLAND_PENALTY,
+    passed_pawn_bonus: PASSED_PAWN_BONUS,
+    phalanx_pawn_bonus: PHALANX_PAWN_BONUS,
+    supported_pawn_bonus: SUPPORTED_PAWN_BONUS,
+    battery_bonus: BATTERY_BONUS,
+    semi_open_file_bonus: SEMI_OPEN_FILE_BONUS,
+    open_file_bonus: OPEN_FILE_BONUS,
+    doubled_rooks_bonus: DOUBLED_ROOKS_BONUS,
+    trapped_bishop_penalty: TRAPPED_BISHOP_PENALTY,
+    trapped_knight_penalty: TRAPPED_KNIGHT_PENALTY,
+    trapped_rook_penalty: TRAPPED_ROOK_PENALTY,
+    king_file_psqt: KING_FILE_PSQT,
+    midgame_psqt: MIDGAME_PSQT,
+    endgame_psqt: ENDGAME_PSQT,
+};
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        DEFAULT_EVAL_PARAMS
+    }
+}
@@ -1,28 +1,42 @@
 use crate::{
-    evaluation::ValueScore,
+    evaluation::{params::EvalParams, ValueScore},
+    moves::gen::xray_attacks,
     position::{board::Piece, Color, Position},
 };
 
-pub static mut BISHOP_PAIR_BONUS: ValueScore = 49;
+pub const BISHOP_PAIR_BONUS: ValueScore = 49;
+pub const BISHOP_XRAY_BONUS: ValueScore = 10;
 
-pub fn evaluate_bishops(position: &Position) -> ValueScore {
+fn xrayed_royals(position: &Position, color: Color) -> u8 {
+    let occupancy = position.board.occupancy_bb_all();
+    let their_royals = position.board.pieces_bb_color(Piece::Queen, color.opposite())
+        | position.board.pieces_bb_color(Piece::King, color.opposite());
+
+    position.board.pieces_bb_color(Piece::Bishop, color).fold(0, |acc, square| {
+        let xray = xray_attacks(Piece::Bishop, square, occupancy, occupancy, color);
+        acc + (xray & their_royals).count_ones() as u8
+    })
+}
+
+pub fn evaluate_bishops(position: &Position, color: Color, params: &EvalParams) -> ValueScore {
     let mut score = 0;
 
-    for color in Color::list() {
-        let our_bishops = position.board.pieces_bb_color(Piece::Bishop, *color);
-        if our_bishops.count_ones() > 1 {
-            score += unsafe { BISHOP_PAIR_BONUS * color.sign() };
-        }
+    let our_bishops = position.board.pieces_bb_color(Piece::Bishop, color);
+    if our_bishops.count_ones() > 1 {
+        score += params.bishop_pair_bonus;
     }
 
+    // A bishop eyeing the queen or king behind a single piece threatens pins and skewers.
+    score += xrayed_royals(position, color) as ValueScore * params.bishop_xray_bonus;
+
     score
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        evaluation::position::bishops::BISHOP_PAIR_BONUS,
-        position::{fen::FromFen, Position},
+        evaluation::{params::DEFAULT_EVAL_PARAMS, position::bishops::BISHOP_PAIR_BONUS},
+        position::{fen::FromFen, Color, Position},
     };
 
     #[test]
@@ -31,7 +45,18 @@ mod tests {
             "1qr1kr1b/p1p1ppp1/4nn2/1p6/2p5/1P2NQ2/P2PPPPP/B1R1KR1B b KQkq - 0 7",
         )
         .unwrap();
-        let bishops_score = super::evaluate_bishops(&position);
-        assert_eq!(bishops_score, unsafe { BISHOP_PAIR_BONUS });
+        let bishops_score = super::evaluate_bishops(&position, Color::White, &DEFAULT_EVAL_PARAMS)
+            - super::evaluate_bishops(&position, Color::Black, &DEFAULT_EVAL_PARAMS);
+        assert_eq!(bishops_score, BISHOP_PAIR_BONUS);
+    }
+
+    #[test]
+    fn bishop_xray_pin() {
+        let position = Position::from_fen(
+            "rn1qk2r/ppp2ppp/3p1n2/4p3/2B1P1b1/3P1N2/PPP2PPP/RN1QK2R w KQkq - 0 6",
+        )
+        .unwrap();
+        assert_eq!(super::xrayed_royals(&position, Color::Black), 1);
+        assert_eq!(super::xrayed_royals(&position, Color::White), 0);
     }
 }
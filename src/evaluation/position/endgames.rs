@@ -0,0 +1,247 @@
+use crate::{
+    evaluation::{Evaluable, ValueScore},
+    position::{
+        board::{Board, Piece},
+        square::Square,
+        Color, Position,
+    },
+};
+
+/// Scale factor that leaves the evaluation untouched.
+pub const SCALE_NORMAL: ValueScore = 64;
+
+const MAX_ENDGAME_PIECES: u32 = 5;
+const EDGE_BONUS: ValueScore = 20;
+const CORNER_BONUS: ValueScore = 20;
+const KING_PROXIMITY_BONUS: ValueScore = 10;
+const DRAWISH_SCALE: ValueScore = 16;
+
+const SIGNATURE_PIECES: [Piece; 5] =
+    [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight, Piece::Pawn];
+
+/// How an endgame is evaluated, always from the perspective of its strong side.
+#[derive(Copy, Clone)]
+pub enum EndgameRule {
+    /// Replaces the generic evaluation.
+    Evaluate(fn(&Position, Color) -> ValueScore),
+    /// Scales the generic evaluation, when it favours the strong side, to a `SCALE_NORMAL` fraction.
+    Scale(fn(&Position, Color) -> ValueScore),
+}
+
+const fn signature_shift(color: Color, piece_index: usize) -> usize {
+    (color as usize * SIGNATURE_PIECES.len() + piece_index) * 4
+}
+
+/// Material signature of an endgame written as in "KRPKR", with White as the first king.
+const fn signature(code: &str) -> u64 {
+    let code = code.as_bytes();
+    let mut signature = 0;
+    let mut color = Color::White;
+    let mut i = 1;
+
+    while i < code.len() {
+        let piece_index = match code[i] {
+            b'K' => {
+                color = Color::Black;
+                i += 1;
+                continue;
+            }
+            b'Q' => 0,
+            b'R' => 1,
+            b'B' => 2,
+            b'N' => 3,
+            b'P' => 4,
+            _ => panic!("Invalid endgame code"),
+        };
+        signature += 1 << signature_shift(color, piece_index);
+        i += 1;
+    }
+
+    signature
+}
+
+/// Counts of each piece kind but the king, for both colors.
+pub fn material_signature(board: &Board) -> u64 {
+    Color::list().iter().fold(0, |acc, color| {
+        SIGNATURE_PIECES.iter().enumerate().fold(acc, |acc, (index, piece)| {
+            let count = board.pieces_bb_color(*piece, *color).count_ones().min(15) as u64;
+            acc | count << signature_shift(*color, index)
+        })
+    })
+}
+
+const fn swap_colors(signature: u64) -> u64 {
+    let black_shift = signature_shift(Color::Black, 0);
+    (signature >> black_shift) | (signature & ((1 << black_shift) - 1)) << black_shift
+}
+
+const ENDGAMES: [(u64, EndgameRule); 7] = [
+    (signature("KQK"), EndgameRule::Evaluate(evaluate_mating)),
+    (signature("KRK"), EndgameRule::Evaluate(evaluate_mating)),
+    (signature("KBNK"), EndgameRule::Evaluate(evaluate_bishop_knight_mate)),
+    (signature("KBPK"), EndgameRule::Scale(scale_wrong_bishop)),
+    (signature("KRPKR"), EndgameRule::Scale(scale_king_in_front)),
+    (signature("KRKB"), EndgameRule::Scale(scale_drawish)),
+    (signature("KRKN"), EndgameRule::Scale(scale_drawish)),
+];
+
+/// Finds the specialized rule for the material on the board, with its strong side.
+pub fn probe_endgame(position: &Position) -> Option<(EndgameRule, Color)> {
+    if position.board.occupancy_bb_all().count_ones() > MAX_ENDGAME_PIECES {
+        return None;
+    }
+
+    let signature = material_signature(&position.board);
+    ENDGAMES.iter().find_map(|(key, rule)| {
+        if *key == signature {
+            Some((*rule, Color::White))
+        } else if swap_colors(*key) == signature {
+            Some((*rule, Color::Black))
+        } else {
+            None
+        }
+    })
+}
+
+fn king_square(position: &Position, color: Color) -> Square {
+    position.board.pieces_bb_color(Piece::King, color).into_iter().next().unwrap()
+}
+
+fn king_distance(from: Square, to: Square) -> u8 {
+    (from.file() as i8 - to.file() as i8)
+        .unsigned_abs()
+        .max((from.rank() as i8 - to.rank() as i8).unsigned_abs())
+}
+
+fn edge_closeness(square: Square) -> ValueScore {
+    let file_edge = (3 - square.file() as i8).max(square.file() as i8 - 4);
+    let rank_edge = (3 - square.rank() as i8).max(square.rank() as i8 - 4);
+    (file_edge + rank_edge) as ValueScore
+}
+
+fn strong_material(position: &Position, strong: Color) -> ValueScore {
+    SIGNATURE_PIECES.iter().fold(0, |acc, piece| {
+        acc + position.board.pieces_bb_color(*piece, strong).count_ones() as ValueScore
+            * piece.value()
+    })
+}
+
+fn evaluate_mating(position: &Position, strong: Color) -> ValueScore {
+    // Drive the lone king to the edge, with our king close by to take away its squares.
+    let weak_king = king_square(position, strong.opposite());
+    let strong_king = king_square(position, strong);
+
+    strong_material(position, strong)
+        + EDGE_BONUS * edge_closeness(weak_king)
+        + KING_PROXIMITY_BONUS * (14 - weak_king.manhattan_distance(strong_king)) as ValueScore
+}
+
+fn evaluate_bishop_knight_mate(position: &Position, strong: Color) -> ValueScore {
+    // Mate is only possible in a corner of the color of the bishop.
+    let weak_king = king_square(position, strong.opposite());
+    let strong_king = king_square(position, strong);
+    let bishop = position.board.pieces_bb_color(Piece::Bishop, strong).into_iter().next().unwrap();
+    let corner_distance = [Square::A1, Square::H1, Square::A8, Square::H8]
+        .into_iter()
+        .filter(|corner| corner.color() == bishop.color())
+        .map(|corner| weak_king.manhattan_distance(corner))
+        .min()
+        .unwrap();
+
+    strong_material(position, strong)
+        + CORNER_BONUS * (14 - corner_distance) as ValueScore
+        + KING_PROXIMITY_BONUS * (14 - weak_king.manhattan_distance(strong_king)) as ValueScore
+}
+
+fn scale_wrong_bishop(position: &Position, strong: Color) -> ValueScore {
+    // A rook pawn cannot promote if the bishop does not control the promotion square
+    // and the defending king reaches it.
+    let pawn = position.board.pieces_bb_color(Piece::Pawn, strong).into_iter().next().unwrap();
+    let bishop = position.board.pieces_bb_color(Piece::Bishop, strong).into_iter().next().unwrap();
+    let promotion_rank = if strong == Color::White { 7 } else { 0 };
+    let promotion_square = Square::from(promotion_rank * 8 + pawn.file()).unwrap();
+    let weak_king = king_square(position, strong.opposite());
+
+    if (pawn.file() == 0 || pawn.file() == 7)
+        && promotion_square.color() != bishop.color()
+        && king_distance(weak_king, promotion_square) <= 1
+    {
+        0
+    } else {
+        SCALE_NORMAL
+    }
+}
+
+fn scale_king_in_front(position: &Position, strong: Color) -> ValueScore {
+    // The defending king blocking the pawn usually holds the draw.
+    let pawn = position.board.pieces_bb_color(Piece::Pawn, strong).into_iter().next().unwrap();
+    let weak_king = king_square(position, strong.opposite());
+    let ahead = match strong {
+        Color::White => weak_king.rank() > pawn.rank(),
+        Color::Black => weak_king.rank() < pawn.rank(),
+    };
+
+    if weak_king.file() == pawn.file() && ahead {
+        DRAWISH_SCALE
+    } else {
+        SCALE_NORMAL
+    }
+}
+
+fn scale_drawish(_: &Position, _: Color) -> ValueScore {
+    DRAWISH_SCALE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{material_signature, probe_endgame, signature, EndgameRule, SCALE_NORMAL};
+    use crate::{
+        evaluation::Evaluable,
+        position::{fen::FromFen, Color, Position},
+    };
+
+    #[test]
+    fn signature_matches_board() {
+        let position = Position::from_fen("8/8/4k3/4r3/8/3RP3/4K3/8 w - - 0 1").unwrap();
+        assert_eq!(material_signature(&position.board), signature("KRPKR"));
+        assert!(matches!(probe_endgame(&position), Some((_, Color::White))));
+
+        let position = Position::from_fen("8/8/4k3/4r3/8/3RP3/4K3/8 b - - 0 1").unwrap();
+        assert!(matches!(probe_endgame(&position), Some((_, Color::White))));
+
+        let position = Position::from_fen("8/8/4k3/4r3/8/8/4K3/8 w - - 0 1").unwrap();
+        assert!(matches!(probe_endgame(&position), Some((EndgameRule::Evaluate(_), Color::Black))));
+
+        let position = Position::from_fen("8/8/4k3/4r3/3P4/3RP3/4K3/8 w - - 0 1").unwrap();
+        assert!(probe_endgame(&position).is_none());
+    }
+
+    #[test]
+    fn mating_drives_king_to_edge() {
+        let center = Position::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let edge = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(edge.value() > center.value());
+        assert!(center.value() > 0);
+    }
+
+    #[test]
+    fn wrong_bishop_rook_pawn_draws() {
+        let position = Position::from_fen("7k/8/8/7P/8/8/8/3BK3 w - - 0 1").unwrap();
+        assert_eq!(position.value(), 0);
+
+        let position = Position::from_fen("7k/8/8/7P/8/8/8/2B1K3 w - - 0 1").unwrap();
+        assert!(position.value() > 0);
+    }
+
+    #[test]
+    fn king_in_front_of_rook_pawn_scales() {
+        let blocked = Position::from_fen("8/4k3/8/8/4P3/8/r3K3/4R3 w - - 0 1").unwrap();
+        let Some((EndgameRule::Scale(scale), strong)) = probe_endgame(&blocked) else {
+            panic!("KRPKR should be scaled");
+        };
+        assert!(scale(&blocked, strong) < SCALE_NORMAL);
+
+        let free = Position::from_fen("8/2k5/8/8/4P3/8/r3K3/4R3 w - - 0 1").unwrap();
+        assert_eq!(scale(&free, strong), SCALE_NORMAL);
+    }
+}
@@ -1,15 +1,28 @@
 use crate::{
-    evaluation::ValueScore,
+    evaluation::{params::EvalParams, ValueScore},
     position::{bitboard::Bitboard, board::Piece, square::Square, Color, Position},
 };
+use std::ops::RangeInclusive;
 
-pub static mut SHELTER_PENALTY: ValueScore = -20;
+pub const SHELTER_PENALTY: ValueScore = -20;
+pub const PAWN_STORM_BONUS: [ValueScore; 8] = [0, 0, 0, 6, 14, 22, 10, 0];
+pub const OPEN_FILE_ATTACK_BONUS: ValueScore = 15;
 
-fn king_pawn_shelter(position: &Position, king_color: Color, king_square: Square) -> ValueScore {
-    let mut shelter = 0;
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Wing {
+    Queenside,
+    Kingside,
+}
 
-    let our_pawns = position.board.pieces_bb_color(Piece::Pawn, king_color);
+fn king_wing(king_square: Square) -> Option<Wing> {
+    match king_square.file() {
+        0..=2 => Some(Wing::Queenside),
+        5..=7 => Some(Wing::Kingside),
+        _ => None,
+    }
+}
 
+fn king_files(king_square: Square) -> RangeInclusive<u8> {
     let file_min = match king_square.file() {
         0 => 0,
         _ => king_square.file() - 1,
@@ -18,27 +31,37 @@ fn king_pawn_shelter(position: &Position, king_color: Color, king_square: Square
         7 => 7,
         _ => king_square.file() + 1,
     };
+    file_min..=file_max
+}
 
-    for file in file_min..=file_max {
+fn king_pawn_shelter(
+    position: &Position,
+    king_color: Color,
+    king_square: Square,
+    params: &EvalParams,
+) -> ValueScore {
+    let mut shelter = 0;
+
+    let our_pawns = position.board.pieces_bb_color(Piece::Pawn, king_color);
+
+    for file in king_files(king_square) {
         let our_pawns_on_file = our_pawns & Bitboard::file_mask(file);
         let most_advanced_pawn = match king_color {
             Color::White => our_pawns_on_file.into_iter().next(),
             Color::Black => our_pawns_on_file.into_iter().next_back(),
         };
-        unsafe {
-            if let Some(pawn_square) = most_advanced_pawn {
-                let rank_diff = (pawn_square.rank() as i8 - king_square.rank() as i8).abs();
-                let shelter_penalty = match rank_diff {
-                    0 => 0,
-                    1 => 0,
-                    2 => SHELTER_PENALTY / 2,
-                    3 => SHELTER_PENALTY,
-                    _ => SHELTER_PENALTY * 2,
-                };
-                shelter += shelter_penalty;
-            } else {
-                shelter += SHELTER_PENALTY;
-            }
+        if let Some(pawn_square) = most_advanced_pawn {
+            let rank_diff = (pawn_square.rank() as i8 - king_square.rank() as i8).abs();
+            let shelter_penalty = match rank_diff {
+                0 => 0,
+                1 => 0,
+                2 => params.shelter_penalty / 2,
+                3 => params.shelter_penalty,
+                _ => params.shelter_penalty * 2,
+            };
+            shelter += shelter_penalty;
+        } else {
+            shelter += params.shelter_penalty;
         }
     }
 
@@ -63,32 +86,74 @@ fn king_tropism(position: &Position, king_color: Color, king_square: Square) ->
     -tropism
 }
 
-pub fn evaluate_king_safety(position: &Position, midgame_ratio: u8) -> ValueScore {
-    let white_king_square =
-        position.board.pieces_bb_color(Piece::King, Color::White).into_iter().next();
-    let black_king_square =
-        position.board.pieces_bb_color(Piece::King, Color::Black).into_iter().next();
+fn opposite_wings_attack(
+    position: &Position,
+    attacker_color: Color,
+    their_king_square: Square,
+    params: &EvalParams,
+) -> ValueScore {
+    let mut attack = 0;
 
-    if white_king_square.is_none() || black_king_square.is_none() {
-        return 0;
+    let our_pawns = position.board.pieces_bb_color(Piece::Pawn, attacker_color);
+    let our_heavy_pieces = position.board.pieces_bb_color(Piece::Rook, attacker_color)
+        | position.board.pieces_bb_color(Piece::Queen, attacker_color);
+
+    // Pawns thrown forward on this wing do not weaken our own king, since it lives on the
+    // other side of the board, so they are rewarded instead of being treated as weaknesses.
+    for file in king_files(their_king_square) {
+        let our_pawns_on_file = our_pawns & Bitboard::file_mask(file);
+        let most_advanced_pawn = match attacker_color {
+            Color::White => our_pawns_on_file.into_iter().next_back(),
+            Color::Black => our_pawns_on_file.into_iter().next(),
+        };
+        if let Some(pawn_square) = most_advanced_pawn {
+            let relative_rank = match attacker_color {
+                Color::White => pawn_square.rank(),
+                Color::Black => 7 - pawn_square.rank(),
+            };
+            attack += params.pawn_storm_bonus[relative_rank as usize];
+        } else if (our_heavy_pieces & Bitboard::file_mask(file)).is_not_empty() {
+            // A file without our pawns only matters if a rook or queen already stands on it.
+            attack += params.open_file_attack_bonus;
+        }
     }
 
+    attack
+}
+
+pub fn evaluate_king_safety(
+    position: &Position,
+    color: Color,
+    midgame_ratio: u8,
+    params: &EvalParams,
+) -> ValueScore {
+    let our_king_square = position.board.pieces_bb_color(Piece::King, color).into_iter().next();
+    let their_king_square =
+        position.board.pieces_bb_color(Piece::King, color.opposite()).into_iter().next();
+
+    let (Some(our_king_square), Some(their_king_square)) = (our_king_square, their_king_square)
+    else {
+        return 0;
+    };
+
     let mut score = 0;
 
-    score += king_tropism(position, Color::White, white_king_square.unwrap())
-        * midgame_ratio as ValueScore
-        / 255;
-    score -= king_tropism(position, Color::Black, black_king_square.unwrap())
-        * midgame_ratio as ValueScore
-        / 255;
+    score += king_tropism(position, color, our_king_square) * midgame_ratio as ValueScore / 255;
 
-    score += king_pawn_shelter(position, Color::White, white_king_square.unwrap())
-        * midgame_ratio as ValueScore
-        / 255;
-    score -= king_pawn_shelter(position, Color::Black, black_king_square.unwrap())
+    score += king_pawn_shelter(position, color, our_king_square, params)
         * midgame_ratio as ValueScore
         / 255;
 
+    // Kings on opposite wings turn the middlegame into a race: whoever opens lines
+    // against the enemy king first usually wins, so attacking terms weigh more.
+    let our_wing = king_wing(our_king_square);
+    let their_wing = king_wing(their_king_square);
+    if our_wing.is_some() && their_wing.is_some() && our_wing != their_wing {
+        score += opposite_wings_attack(position, color, their_king_square, params)
+            * midgame_ratio as ValueScore
+            / 255;
+    }
+
     score
 }
 
@@ -96,10 +161,11 @@ pub fn evaluate_king_safety(position: &Position, midgame_ratio: u8) -> ValueScor
 mod tests {
     use super::king_tropism;
     use crate::{
-        evaluation::ValueScore,
+        evaluation::{params::DEFAULT_EVAL_PARAMS, ValueScore},
         position::{
             board::Piece,
             fen::{FromFen, START_FEN},
+            square::Square,
             Color, Position,
         },
     };
@@ -120,8 +186,13 @@ mod tests {
         let black_king_square =
             position.board.pieces_bb_color(Piece::King, Color::Black).into_iter().next().unwrap();
 
-        super::king_pawn_shelter(position, Color::White, white_king_square)
-            - super::king_pawn_shelter(position, Color::Black, black_king_square)
+        super::king_pawn_shelter(position, Color::White, white_king_square, &DEFAULT_EVAL_PARAMS)
+            - super::king_pawn_shelter(
+                position,
+                Color::Black,
+                black_king_square,
+                &DEFAULT_EVAL_PARAMS,
+            )
     }
 
     #[test]
@@ -170,4 +241,38 @@ mod tests {
 
         assert!((-10..=-2).contains(&position_shelter(&position)));
     }
+
+    #[test]
+    fn opposite_wings_storm() {
+        let position =
+            Position::from_fen("r1bq1rk1/pp2ppb1/2np1np1/6PP/3NP3/2N1BP2/PPPQ4/2KR1B1R b - - 0 12")
+                .unwrap();
+
+        let white_attack =
+            super::opposite_wings_attack(&position, Color::White, Square::G8, &DEFAULT_EVAL_PARAMS);
+        let black_attack =
+            super::opposite_wings_attack(&position, Color::Black, Square::C1, &DEFAULT_EVAL_PARAMS);
+
+        assert!(white_attack > black_attack);
+    }
+
+    #[test]
+    fn open_file_needs_a_heavy_piece_on_it() {
+        let rook_aside = Position::from_fen("6k1/5p1p/8/8/8/8/PPP4P/2KR4 w - - 0 1").unwrap();
+        let rook_on_file = Position::from_fen("6k1/5p1p/8/8/8/8/PPP4P/2K3R1 w - - 0 1").unwrap();
+
+        let attack = |position: &Position| {
+            super::opposite_wings_attack(position, Color::White, Square::G8, &DEFAULT_EVAL_PARAMS)
+        };
+
+        assert_eq!(attack(&rook_aside), 0);
+        assert_eq!(attack(&rook_on_file), DEFAULT_EVAL_PARAMS.open_file_attack_bonus);
+    }
+
+    #[test]
+    fn same_wing_no_race() {
+        assert_eq!(super::king_wing(Square::G1), super::king_wing(Square::H8));
+        assert_ne!(super::king_wing(Square::C1), super::king_wing(Square::G8));
+        assert_eq!(super::king_wing(Square::E1), None);
+    }
 }
@@ -1,40 +1,40 @@
 use self::{
-    bishops::evaluate_bishops, king::evaluate_king_safety, pawns::evaluate_pawn_structure,
+    bishops::evaluate_bishops,
+    endgames::{probe_endgame, EndgameRule, SCALE_NORMAL},
+    king::evaluate_king_safety,
+    pawns::evaluate_pawn_structure,
+    queens::evaluate_queens,
     rooks::evaluate_rooks,
+    trapped::evaluate_trapped_pieces,
+};
+use super::{
+    params::EvalParams,
+    psqt::{king_file_psqt_value, psqt_value},
+    Evaluable, ValueScore,
 };
-use super::{psqt::psqt_value, Evaluable, ValueScore};
 use crate::{
     moves::gen::piece_attacks,
     position::{board::Piece, Color, Position},
 };
 
 pub mod bishops;
+pub mod endgames;
 pub mod king;
 pub mod pawns;
+pub mod queens;
 pub mod rooks;
+pub mod trapped;
 
 pub const MAX_POSITIONAL_GAIN: ValueScore = 200;
 
-pub static mut PAWN_MIDGAME_RATIO: ValueScore = 1;
-pub static mut KNIGHT_MIDGAME_RATIO: ValueScore = 5;
-pub static mut BISHOP_MIDGAME_RATIO: ValueScore = 9;
-pub static mut ROOK_MIDGAME_RATIO: ValueScore = 20;
-pub static mut QUEEN_MIDGAME_RATIO: ValueScore = 38;
+/// Indexed by piece.
+pub const MIDGAME_RATIOS: [ValueScore; 6] = [38, 20, 9, 5, 1, 0];
 
-fn midgame_ratio(position: &Position) -> u8 {
+pub(crate) fn midgame_ratio(position: &Position, params: &EvalParams) -> u8 {
     Piece::list().iter().fold(0, |acc, piece| {
         acc.saturating_add(
             position.board.pieces_bb(*piece).count_ones() as u8
-                * unsafe {
-                    match *piece {
-                        Piece::Pawn => PAWN_MIDGAME_RATIO,
-                        Piece::Knight => KNIGHT_MIDGAME_RATIO,
-                        Piece::Bishop => BISHOP_MIDGAME_RATIO,
-                        Piece::Rook => ROOK_MIDGAME_RATIO,
-                        Piece::Queen => QUEEN_MIDGAME_RATIO,
-                        Piece::King => 0,
-                    }
-                } as u8,
+                * params.midgame_ratios[*piece as usize] as u8,
         )
     })
 }
@@ -49,7 +49,7 @@ fn mobility_bonus(piece: Piece) -> ValueScore {
     }
 }
 
-fn insufficient_material(position: &Position) -> bool {
+pub(crate) fn insufficient_material(position: &Position) -> bool {
     let pieces_count = position.board.occupancy_bb_all().count_ones();
 
     if pieces_count > 4 {
@@ -69,51 +69,150 @@ fn insufficient_material(position: &Position) -> bool {
     false
 }
 
-impl Evaluable for Position {
-    fn value(&self) -> ValueScore {
-        if insufficient_material(self) {
-            return 0;
-        }
+/// Evaluation of one side, from its own perspective, split by term.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct SideEvaluation {
+    pub material: ValueScore,
+    /// Piece-square tables and mobility.
+    pub placement: ValueScore,
+    pub pawns: ValueScore,
+    pub king_safety: ValueScore,
+    pub rooks: ValueScore,
+    pub bishops: ValueScore,
+    pub queens: ValueScore,
+    pub trapped: ValueScore,
+}
 
-        let midgame_ratio = midgame_ratio(self);
-        let endgame_ratio = 255 - midgame_ratio;
-        let occupancy = self.board.occupancy_bb_all();
+impl SideEvaluation {
+    pub fn terms(&self) -> [(&'static str, ValueScore); 8] {
+        [
+            ("material", self.material),
+            ("placement", self.placement),
+            ("pawns", self.pawns),
+            ("king safety", self.king_safety),
+            ("rooks", self.rooks),
+            ("bishops", self.bishops),
+            ("queens", self.queens),
+            ("trapped", self.trapped),
+        ]
+    }
 
-        let base_score = Piece::list().iter().fold(0, |acc, piece| {
-            let piece_value = piece.value();
-            let piece_mobility_bonus = mobility_bonus(*piece);
-            let pieces_bb = self.board.pieces_bb(*piece);
+    pub fn total(&self) -> ValueScore {
+        self.material
+            + self.placement
+            + self.pawns
+            + self.king_safety
+            + self.rooks
+            + self.bishops
+            + self.queens
+            + self.trapped
+    }
+}
+
+fn evaluate_side(
+    position: &Position,
+    color: Color,
+    midgame_ratio: u8,
+    params: &EvalParams,
+) -> SideEvaluation {
+    let endgame_ratio = 255 - midgame_ratio;
+    let occupancy = position.board.occupancy_bb_all();
+    let king_square = position.board.pieces_bb_color(Piece::King, color).into_iter().next();
 
-            acc + Color::list().iter().fold(0, |acc, color| {
-                let bb = pieces_bb & self.board.occupancy_bb(*color);
+    let (material, placement) =
+        Piece::list().iter().fold((0, 0), |(material, placement), piece| {
+            let bb = position.board.pieces_bb_color(*piece, color);
+            let piece_mobility_bonus = mobility_bonus(*piece);
 
-                let material_score = bb.count_ones() as ValueScore * piece_value;
-                let positional_score = bb.into_iter().fold(0, |acc, square| {
-                    acc + psqt_value(*piece, square, *color, endgame_ratio)
-                        + piece_mobility_bonus
-                            * piece_attacks(*piece, square, occupancy, *color).count_ones()
-                                as ValueScore
-                });
+            let piece_placement = bb.into_iter().fold(0, |acc, square| {
+                acc + psqt_value(*piece, square, color, endgame_ratio, params)
+                    + king_square.map_or(0, |king_square| {
+                        king_file_psqt_value(*piece, square, king_square, endgame_ratio, params)
+                    })
+                    + piece_mobility_bonus
+                        * piece_attacks(*piece, square, occupancy, color).count_ones() as ValueScore
+            });
 
-                acc + (positional_score + material_score) * color.sign()
-            })
+            (
+                material + bb.count_ones() as ValueScore * piece.value_with(params),
+                placement + piece_placement,
+            )
         });
 
-        let pawns_score = evaluate_pawn_structure(self);
-        let king_score = evaluate_king_safety(self, midgame_ratio);
-        let rooks_score = evaluate_rooks(self);
-        let bishops_score = evaluate_bishops(self);
+    SideEvaluation {
+        material,
+        placement,
+        pawns: evaluate_pawn_structure(position, color, params),
+        king_safety: evaluate_king_safety(position, color, midgame_ratio, params),
+        rooks: evaluate_rooks(position, color, params),
+        bishops: evaluate_bishops(position, color, params),
+        queens: evaluate_queens(position, color, params),
+        trapped: evaluate_trapped_pieces(position, color, params),
+    }
+}
+
+/// The evaluation split by side, term and phase, to show what it is made of.
+#[derive(Debug, Copy, Clone)]
+pub struct EvaluationBreakdown {
+    pub midgame_ratio: u8,
+    /// Indexed by color, as if the game was fully in the middlegame.
+    pub midgame: [SideEvaluation; 2],
+    /// Indexed by color, as if the game was fully in the endgame.
+    pub endgame: [SideEvaluation; 2],
+    /// Indexed by color, at the phase of the position.
+    pub blended: [SideEvaluation; 2],
+    /// The evaluation from the perspective of White, which endgame rules may set apart
+    /// from the difference of the blended totals.
+    pub value: ValueScore,
+}
+
+pub fn evaluation_breakdown(position: &Position, params: &EvalParams) -> EvaluationBreakdown {
+    let sides = |midgame_ratio| {
+        [Color::White, Color::Black]
+            .map(|color| evaluate_side(position, color, midgame_ratio, params))
+    };
+    let midgame_ratio = midgame_ratio(position, params);
 
-        base_score + pawns_score + king_score + rooks_score + bishops_score
+    EvaluationBreakdown {
+        midgame_ratio,
+        midgame: sides(255),
+        endgame: sides(0),
+        blended: sides(midgame_ratio),
+        value: position.value_with(params),
+    }
+}
+
+impl Evaluable for Position {
+    fn value_with(&self, params: &EvalParams) -> ValueScore {
+        if insufficient_material(self) {
+            return 0;
+        }
+
+        let endgame = probe_endgame(self);
+        if let Some((EndgameRule::Evaluate(evaluate), strong)) = endgame {
+            return evaluate(self, strong) * strong.sign();
+        }
+
+        let midgame_ratio = midgame_ratio(self, params);
+        let score = evaluate_side(self, Color::White, midgame_ratio, params).total()
+            - evaluate_side(self, Color::Black, midgame_ratio, params).total();
+
+        match endgame {
+            Some((EndgameRule::Scale(scale), strong)) if score * strong.sign() > 0 => {
+                (score as i32 * scale(self, strong) as i32 / SCALE_NORMAL as i32) as ValueScore
+            }
+            _ => score,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::evaluation_breakdown;
     use crate::{
-        evaluation::Evaluable,
+        evaluation::{params::DEFAULT_EVAL_PARAMS, Evaluable},
         position::{
-            fen::{FromFen, START_FEN},
+            fen::{FromFen, KIWIPETE_WHITE_FEN, START_FEN},
             Position,
         },
     };
@@ -142,4 +241,18 @@ mod tests {
         let king_at_corner_evaluation = king_at_corner_position.value();
         assert!(king_at_center_evaluation > king_at_corner_evaluation);
     }
+
+    #[test]
+    fn breakdown_adds_up_to_evaluation() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let breakdown = evaluation_breakdown(&position, &DEFAULT_EVAL_PARAMS);
+        assert_eq!(breakdown.blended[0].total() - breakdown.blended[1].total(), position.value());
+        assert_eq!(breakdown.value, position.value());
+
+        let position = Position::from_fen(START_FEN).unwrap();
+        let breakdown = evaluation_breakdown(&position, &DEFAULT_EVAL_PARAMS);
+        assert_eq!(breakdown.midgame[0], breakdown.midgame[1]);
+        assert_eq!(breakdown.endgame[0], breakdown.endgame[1]);
+        assert_eq!(breakdown.midgame[0].material, breakdown.endgame[0].material);
+    }
 }
@@ -1,12 +1,17 @@
 use crate::{
-    evaluation::ValueScore,
+    evaluation::{params::EvalParams, ValueScore},
     moves::gen::MoveDirection,
     position::{bitboard::Bitboard, board::Piece, Color, Position},
 };
 
-pub static mut DOUBLED_PAWNS_PENALTY: ValueScore = -10;
-pub static mut PAWN_ISLAND_PENALTY: ValueScore = -10;
-pub static mut PASSED_PAWN_BONUS: [ValueScore; 8] = [0, 8, 9, 14, 41, 98, 158, 0];
+pub const DOUBLED_PAWNS_PENALTY: ValueScore = -10;
+pub const PAWN_ISLAND_PENALTY: ValueScore = -10;
+pub const PASSED_PAWN_BONUS: [ValueScore; 8] = [0, 8, 9, 14, 41, 98, 158, 0];
+pub const PHALANX_PAWN_BONUS: [ValueScore; 8] = [0, 2, 4, 6, 12, 25, 40, 0];
+pub const SUPPORTED_PAWN_BONUS: [ValueScore; 8] = [0, 0, 5, 6, 10, 20, 35, 0];
+
+const WEST_EDGE_FILE: Bitboard = Bitboard::file_mask(0);
+const EAST_EDGE_FILE: Bitboard = Bitboard::file_mask(7);
 
 fn doubled_pawns(bb: Bitboard) -> u8 {
     (0..8).fold(0, |acc, file| {
@@ -37,8 +42,24 @@ fn pawn_islands(bb: Bitboard) -> u8 {
     islands
 }
 
+fn phalanx_pawns(bb: Bitboard) -> Bitboard {
+    let west_neighbours = (bb & !WEST_EDGE_FILE).shift(MoveDirection::WEST);
+    let east_neighbours = (bb & !EAST_EDGE_FILE).shift(MoveDirection::EAST);
+    bb & (west_neighbours | east_neighbours)
+}
+
+fn supported_pawns(us_direction: i8, bb: Bitboard) -> Bitboard {
+    let west_defended = (bb & !WEST_EDGE_FILE).shift(us_direction + MoveDirection::WEST);
+    let east_defended = (bb & !EAST_EDGE_FILE).shift(us_direction + MoveDirection::EAST);
+    bb & (west_defended | east_defended)
+}
+
 type RelativeRank = u8;
 
+fn relative_ranks(us_direction: i8, bb: Bitboard) -> impl Iterator<Item = RelativeRank> {
+    bb.map(move |square| if us_direction > 0 { square.rank() } else { 7 - square.rank() })
+}
+
 fn passed_pawns(us_direction: i8, us_bb: Bitboard, them_bb: Bitboard) -> [RelativeRank; 8] {
     let mut passed_pawns_ranks = [0; 8];
 
@@ -76,28 +97,29 @@ fn passed_pawns(us_direction: i8, us_bb: Bitboard, them_bb: Bitboard) -> [Relati
     passed_pawns_ranks
 }
 
-pub fn evaluate_pawn_structure(position: &Position) -> ValueScore {
+pub fn evaluate_pawn_structure(
+    position: &Position,
+    color: Color,
+    params: &EvalParams,
+) -> ValueScore {
     let mut score = 0;
 
-    let white_pawns = position.board.pieces_bb_color(Piece::Pawn, Color::White);
-    let black_pawns = position.board.pieces_bb_color(Piece::Pawn, Color::Black);
+    let our_pawns = position.board.pieces_bb_color(Piece::Pawn, color);
+    let their_pawns = position.board.pieces_bb_color(Piece::Pawn, color.opposite());
+    let direction = MoveDirection::pawn_direction(color);
 
-    unsafe {
-        score += doubled_pawns(white_pawns) as ValueScore * DOUBLED_PAWNS_PENALTY;
-        score -= doubled_pawns(black_pawns) as ValueScore * DOUBLED_PAWNS_PENALTY;
+    score += doubled_pawns(our_pawns) as ValueScore * params.doubled_pawns_penalty;
+    score += pawn_islands(our_pawns) as ValueScore * params.pawn_island_penalty;
 
-        score += pawn_islands(white_pawns) as ValueScore * PAWN_ISLAND_PENALTY;
-        score -= pawn_islands(black_pawns) as ValueScore * PAWN_ISLAND_PENALTY;
+    score += passed_pawns(direction, our_pawns, their_pawns)
+        .iter()
+        .fold(0, |acc, rank| acc + params.passed_pawn_bonus[*rank as usize]);
 
-        score +=
-            passed_pawns(MoveDirection::pawn_direction(Color::White), white_pawns, black_pawns)
-                .iter()
-                .fold(0, |acc, rank| acc + PASSED_PAWN_BONUS[*rank as usize]);
-        score -=
-            passed_pawns(MoveDirection::pawn_direction(Color::Black), black_pawns, white_pawns)
-                .iter()
-                .fold(0, |acc, rank| acc + PASSED_PAWN_BONUS[*rank as usize]);
-    }
+    score += relative_ranks(direction, phalanx_pawns(our_pawns))
+        .fold(0, |acc, rank| acc + params.phalanx_pawn_bonus[rank as usize]);
+
+    score += relative_ranks(direction, supported_pawns(direction, our_pawns))
+        .fold(0, |acc, rank| acc + params.supported_pawn_bonus[rank as usize]);
 
     score
 }
@@ -107,7 +129,9 @@ mod tests {
     use crate::{
         evaluation::position::pawns::passed_pawns,
         moves::gen::MoveDirection,
-        position::{board::Piece, fen::FromFen, Color, Position},
+        position::{
+            bitboard::Bitboard, board::Piece, fen::FromFen, square::Square, Color, Position,
+        },
     };
 
     #[test]
@@ -199,4 +223,36 @@ mod tests {
             [0, 4, 0, 0, 3, 4, 0, 0]
         );
     }
+
+    #[test]
+    fn phalanx_pawns_1() {
+        let position = Position::from_fen("8/8/8/3PP3/8/P1P5/5PP1/8 w - - 0 1").unwrap();
+        let white_pawns = position.board.pieces_bb_color(Piece::Pawn, Color::White);
+
+        assert_eq!(
+            super::phalanx_pawns(white_pawns),
+            Bitboard::new(
+                1 << Square::D5 as u64
+                    | 1 << Square::E5 as u64
+                    | 1 << Square::F2 as u64
+                    | 1 << Square::G2 as u64
+            )
+        );
+    }
+
+    #[test]
+    fn supported_pawns_1() {
+        let position = Position::from_fen("8/5p2/4p3/3p4/8/2P5/1P6/8 w - - 0 1").unwrap();
+        let white_pawns = position.board.pieces_bb_color(Piece::Pawn, Color::White);
+        let black_pawns = position.board.pieces_bb_color(Piece::Pawn, Color::Black);
+
+        assert_eq!(
+            super::supported_pawns(MoveDirection::pawn_direction(Color::White), white_pawns),
+            Bitboard::new(1 << Square::C3 as u64)
+        );
+        assert_eq!(
+            super::supported_pawns(MoveDirection::pawn_direction(Color::Black), black_pawns),
+            Bitboard::new(1 << Square::E6 as u64 | 1 << Square::D5 as u64)
+        );
+    }
 }
@@ -0,0 +1,71 @@
+use crate::{
+    evaluation::{params::EvalParams, ValueScore},
+    moves::gen::{piece_attacks, xray_attacks},
+    position::{bitboard::Bitboard, board::Piece, Color, Position},
+};
+
+pub const BATTERY_BONUS: ValueScore = 12;
+
+fn king_zone(position: &Position, color: Color) -> Bitboard {
+    position.board.pieces_bb_color(Piece::King, color).into_iter().next().map_or(
+        Bitboard::new(0),
+        |king_square| {
+            piece_attacks(Piece::King, king_square, Bitboard::new(0), color)
+                | Bitboard::new(1 << king_square as u64)
+        },
+    )
+}
+
+fn batteries(position: &Position, color: Color) -> u8 {
+    let occupancy = position.board.occupancy_bb_all();
+    let their_king_zone = king_zone(position, color.opposite());
+    let our_queens = position.board.pieces_bb_color(Piece::Queen, color);
+
+    [(Piece::Rook, Piece::Rook), (Piece::Bishop, Piece::Bishop)].iter().fold(
+        0,
+        |acc, (partner, line_piece)| {
+            let our_partners = position.board.pieces_bb_color(*partner, color);
+
+            // The back piece must reach the enemy king zone through the front piece.
+            let partner_behind = our_partners.fold(0, |acc, square| {
+                let xray = xray_attacks(*line_piece, square, occupancy, our_queens, color);
+                acc + (xray & their_king_zone).is_not_empty() as u8
+            });
+            let queen_behind = our_queens.fold(0, |acc, square| {
+                let xray = xray_attacks(*line_piece, square, occupancy, our_partners, color);
+                acc + (xray & their_king_zone).is_not_empty() as u8
+            });
+
+            acc + partner_behind + queen_behind
+        },
+    )
+}
+
+pub fn evaluate_queens(position: &Position, color: Color, params: &EvalParams) -> ValueScore {
+    batteries(position, color) as ValueScore * params.battery_bonus
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::{fen::FromFen, Color, Position};
+
+    #[test]
+    fn queen_bishop_battery() {
+        let position =
+            Position::from_fen("r1bq1rk1/pppn1ppp/4pn2/3p4/2PP4/2NBPQ2/PP3PPP/R1B1K1NR w KQ - 0 7")
+                .unwrap();
+        assert_eq!(super::batteries(&position, Color::White), 0);
+
+        let position =
+            Position::from_fen("r1bq1rk1/pppn1ppp/4pn2/3p4/2PP4/2NBP3/PPQ2PPP/R1B1K1NR w KQ - 0 7")
+                .unwrap();
+        assert_eq!(super::batteries(&position, Color::White), 1);
+    }
+
+    #[test]
+    fn queen_rook_battery() {
+        let position = Position::from_fen("6k1/5pp1/7p/8/8/6Q1/6R1/6K1 w - - 0 1").unwrap();
+        assert_eq!(super::batteries(&position, Color::White), 1);
+        assert_eq!(super::batteries(&position, Color::Black), 0);
+    }
+}
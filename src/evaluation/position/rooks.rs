@@ -1,43 +1,55 @@
 use crate::{
-    evaluation::ValueScore,
+    evaluation::{params::EvalParams, ValueScore},
+    moves::gen::piece_attacks,
     position::{bitboard::Bitboard, board::Piece, Color, Position},
 };
 
-pub static mut SEMI_OPEN_FILE_BONUS: ValueScore = 19;
-pub static mut OPEN_FILE_BONUS: ValueScore = 21;
+pub const SEMI_OPEN_FILE_BONUS: ValueScore = 19;
+pub const OPEN_FILE_BONUS: ValueScore = 21;
+pub const DOUBLED_ROOKS_BONUS: ValueScore = 15;
 
-pub fn evaluate_rooks(position: &Position) -> ValueScore {
+fn doubled_rooks(position: &Position, color: Color) -> bool {
+    let rooks = position.board.pieces_bb_color(Piece::Rook, color);
+    let occupancy = position.board.occupancy_bb_all();
+
+    rooks.into_iter().next().is_some_and(|rook| {
+        let file_attacks =
+            piece_attacks(Piece::Rook, rook, occupancy, color) & Bitboard::file_mask(rook.file());
+        (file_attacks & rooks).is_not_empty()
+    })
+}
+
+pub fn evaluate_rooks(position: &Position, color: Color, params: &EvalParams) -> ValueScore {
     let mut score = 0;
+    let rooks = position.board.pieces_bb_color(Piece::Rook, color);
 
-    unsafe {
-        for color in Color::list() {
-            let rooks = position.board.pieces_bb_color(Piece::Rook, *color);
-
-            if let Some(rook) = rooks.into_iter().next() {
-                let our_pawns = position.board.pieces_bb_color(Piece::Pawn, *color);
-                let their_pawns = position.board.pieces_bb_color(Piece::Pawn, color.opposite());
-                let our_file = Bitboard::file_mask(rook.file());
-
-                // Semi-open files
-                if (our_file & our_pawns).is_empty() {
-                    if (our_file & their_pawns).is_empty() {
-                        score += OPEN_FILE_BONUS * color.sign();
-                    } else {
-                        score += SEMI_OPEN_FILE_BONUS * color.sign();
-                    }
-                }
+    if let Some(rook) = rooks.into_iter().next() {
+        let our_pawns = position.board.pieces_bb_color(Piece::Pawn, color);
+        let their_pawns = position.board.pieces_bb_color(Piece::Pawn, color.opposite());
+        let our_file = Bitboard::file_mask(rook.file());
+
+        // Semi-open files
+        if (our_file & our_pawns).is_empty() {
+            if (our_file & their_pawns).is_empty() {
+                score += params.open_file_bonus;
+            } else {
+                score += params.semi_open_file_bonus;
             }
         }
     }
 
+    if doubled_rooks(position, color) {
+        score += params.doubled_rooks_bonus;
+    }
+
     score
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        evaluation::position::rooks::SEMI_OPEN_FILE_BONUS,
-        position::{fen::FromFen, Position},
+        evaluation::{params::DEFAULT_EVAL_PARAMS, position::rooks::SEMI_OPEN_FILE_BONUS},
+        position::{fen::FromFen, Color, Position},
     };
 
     #[test]
@@ -45,7 +57,21 @@ mod tests {
         let position =
             Position::from_fen("2kr2nr/pbppb3/1pn1pq1p/6p1/2P5/4BNNP/PPQ1BPP1/3R1RK1 b - - 1 19")
                 .unwrap();
-        let rooks_score = super::evaluate_rooks(&position);
-        assert_eq!(rooks_score, unsafe { SEMI_OPEN_FILE_BONUS });
+        let rooks_score = super::evaluate_rooks(&position, Color::White, &DEFAULT_EVAL_PARAMS)
+            - super::evaluate_rooks(&position, Color::Black, &DEFAULT_EVAL_PARAMS);
+        assert_eq!(rooks_score, SEMI_OPEN_FILE_BONUS);
+    }
+
+    #[test]
+    fn rooks_doubled() {
+        let position = Position::from_fen("3r2k1/5ppp/8/8/8/8/3R1PPP/3R2K1 w - - 0 1").unwrap();
+        assert!(super::doubled_rooks(&position, Color::White));
+        assert!(!super::doubled_rooks(&position, Color::Black));
+
+        let position = Position::from_fen("3r2k1/5ppp/8/8/8/3N4/3R1PPP/3R2K1 w - - 0 1").unwrap();
+        assert!(super::doubled_rooks(&position, Color::White));
+
+        let position = Position::from_fen("3r2k1/5ppp/8/8/3R4/3N4/5PPP/3R2K1 w - - 0 1").unwrap();
+        assert!(!super::doubled_rooks(&position, Color::White));
     }
 }
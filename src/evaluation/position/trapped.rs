@@ -0,0 +1,155 @@
+use crate::{
+    evaluation::{params::EvalParams, ValueScore},
+    position::{board::Piece, square::Square, CastlingRights, Color, Position},
+};
+
+pub const TRAPPED_BISHOP_PENALTY: ValueScore = 90;
+pub const TRAPPED_KNIGHT_PENALTY: ValueScore = 60;
+pub const TRAPPED_ROOK_PENALTY: ValueScore = 45;
+
+/// Bishop square and the enemy pawn that shuts it in, from white's perspective.
+const TRAPPED_BISHOP_PATTERNS: [(Square, Square); 4] = [
+    (Square::A7, Square::B6),
+    (Square::H7, Square::G6),
+    (Square::B8, Square::C7),
+    (Square::G8, Square::F7),
+];
+
+/// Knight square and the enemy pawns covering its escape squares, from white's perspective.
+const TRAPPED_KNIGHT_PATTERNS: [(Square, [Square; 2]); 4] = [
+    (Square::A8, [Square::A7, Square::C7]),
+    (Square::H8, [Square::H7, Square::F7]),
+    (Square::A7, [Square::A6, Square::B7]),
+    (Square::H7, [Square::H6, Square::G7]),
+];
+
+fn relative_square(square: Square, color: Color) -> Square {
+    match color {
+        Color::White => square,
+        Color::Black => square.flip(),
+    }
+}
+
+fn has_piece(position: &Position, square: Square, piece: Piece, color: Color, us: Color) -> bool {
+    position.board.piece_color_at(relative_square(square, us)) == Some((piece, color))
+}
+
+fn trapped_bishops(position: &Position, color: Color) -> u8 {
+    TRAPPED_BISHOP_PATTERNS.iter().fold(0, |acc, (bishop, pawn)| {
+        acc + (has_piece(position, *bishop, Piece::Bishop, color, color)
+            && has_piece(position, *pawn, Piece::Pawn, color.opposite(), color)) as u8
+    })
+}
+
+fn trapped_knights(position: &Position, color: Color) -> u8 {
+    TRAPPED_KNIGHT_PATTERNS.iter().fold(0, |acc, (knight, pawns)| {
+        acc + (has_piece(position, *knight, Piece::Knight, color, color)
+            && pawns
+                .iter()
+                .all(|pawn| has_piece(position, *pawn, Piece::Pawn, color.opposite(), color)))
+            as u8
+    })
+}
+
+fn locked_rook(position: &Position, color: Color) -> bool {
+    let Some(king) = position.board.pieces_bb_color(Piece::King, color).into_iter().next() else {
+        return false;
+    };
+    let king = relative_square(king, color);
+    if king.rank() != 0 {
+        return false;
+    }
+
+    let (corner_files, castle_right) = match (king.file(), color) {
+        (1..=3, Color::White) => (0..king.file(), CastlingRights::WHITE_QUEENSIDE),
+        (1..=3, Color::Black) => (0..king.file(), CastlingRights::BLACK_QUEENSIDE),
+        (5..=6, Color::White) => (king.file() + 1..8, CastlingRights::WHITE_KINGSIDE),
+        (5..=6, Color::Black) => (king.file() + 1..8, CastlingRights::BLACK_KINGSIDE),
+        _ => return false,
+    };
+
+    // Once the king has walked towards the corner, a rook behind it can only get out slowly.
+    !position.castling_rights.contains(castle_right)
+        && corner_files.into_iter().any(|file| {
+            [file, file + 8].iter().any(|square| {
+                has_piece(position, Square::from(*square).unwrap(), Piece::Rook, color, color)
+                    && (*square < 8 || file == 0 || file == 7)
+            })
+        })
+}
+
+pub fn evaluate_trapped_pieces(
+    position: &Position,
+    color: Color,
+    params: &EvalParams,
+) -> ValueScore {
+    let mut score = 0;
+
+    score -= trapped_bishops(position, color) as ValueScore * params.trapped_bishop_penalty;
+    score -= trapped_knights(position, color) as ValueScore * params.trapped_knight_penalty;
+
+    if locked_rook(position, color) {
+        score -= params.trapped_rook_penalty;
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::position::{fen::FromFen, Color, Position};
+
+    #[test]
+    fn bishop_trapped_by_pawns() {
+        let position = Position::from_fen(
+            "r2qkb1r/Bpp2ppp/1pn1bn2/3pp3/4P3/2N5/PPPP1PPP/R2QKBNR b KQkq - 0 6",
+        )
+        .unwrap();
+        assert_eq!(super::trapped_bishops(&position, Color::White), 1);
+        assert_eq!(super::trapped_bishops(&position, Color::Black), 0);
+
+        let position =
+            Position::from_fen("rn1qkbnr/pppb1ppp/4p3/3p4/3P4/1P2P3/PbP2PPP/RN1QKBNR w KQkq - 0 5")
+                .unwrap();
+        assert_eq!(super::trapped_bishops(&position, Color::Black), 0);
+
+        let position =
+            Position::from_fen("rn1qkbnr/ppp2ppp/4p3/3p4/3P4/4P1P1/PPP2P1b/RNBQKBNR w KQkq - 0 5")
+                .unwrap();
+        assert_eq!(super::trapped_bishops(&position, Color::Black), 1);
+    }
+
+    #[test]
+    fn knight_cornered() {
+        let position =
+            Position::from_fen("N3kbnr/p1p2ppp/8/3pp3/8/8/PPPP1PPP/R1BQKB1R b KQk - 0 6").unwrap();
+        assert_eq!(super::trapped_knights(&position, Color::White), 1);
+
+        let position =
+            Position::from_fen("N3kbnr/p4ppp/8/3pp3/8/8/PPPP1PPP/R1BQKB1R b KQk - 0 6").unwrap();
+        assert_eq!(super::trapped_knights(&position, Color::White), 0);
+    }
+
+    #[test]
+    fn rook_locked_by_king() {
+        let position = Position::from_fen(
+            "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1KR1 b kq - 0 6",
+        )
+        .unwrap();
+        assert!(super::locked_rook(&position, Color::White));
+        assert!(!super::locked_rook(&position, Color::Black));
+
+        let position = Position::from_fen(
+            "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 w - - 0 6",
+        )
+        .unwrap();
+        assert!(!super::locked_rook(&position, Color::White));
+        assert!(!super::locked_rook(&position, Color::Black));
+
+        let position =
+            Position::from_fen("rnbq1k1r/pppp1ppp/5n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQ - 0 6")
+                .unwrap();
+        assert!(super::locked_rook(&position, Color::Black));
+        assert!(!super::locked_rook(&position, Color::White));
+    }
+}
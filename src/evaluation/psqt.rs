@@ -1,7 +1,27 @@
-use super::ValueScore;
+use super::{params::EvalParams, ValueScore};
 use crate::position::{board::Piece, square::Square, Color};
 
-type PieceSquareTable = [ValueScore; 64];
+pub type PieceSquareTable = [ValueScore; 64];
+
+pub const KING_FILE_BUCKETS: usize = 2;
+pub const KING_FILE_PIECES: usize = 3;
+
+/// Midgame file adjustments for pawns, knights and bishops, by friendly king bucket.
+/// Bucket 0 is a king on a wing, bucket 1 a king in the center. Files are relative to
+/// the king: a queenside king sees the board mirrored, so that both wings share values.
+#[rustfmt::skip]
+pub const KING_FILE_PSQT: [[[ValueScore; 8]; KING_FILE_PIECES]; KING_FILE_BUCKETS] = [
+    [
+        [ 0,  0,  0,  0,  0,  5, 10, 10],
+        [-5,  0,  0,  5,  5, 10,  5,  0],
+        [ 0,  0,  0,  0,  5,  5,  5,  0],
+    ],
+    [
+        [ 5,  5,  0, -5, -5,  0,  5,  5],
+        [ 0,  0,  5,  5,  5,  5,  0,  0],
+        [ 0,  0,  5,  0,  0,  5,  0,  0],
+    ],
+];
 
 // Values adapted from https://www.chessprogramming.org/Simplified_Evaluation_Function
 // The board is reversed (white is at the bottom) to allow for easier tuning.
@@ -107,33 +127,99 @@ const ENDGAME_PAWN_PSQT: PieceSquareTable = [
       0,  0,  0,  0,  0,  0,  0,  0,
 ];
 
-pub fn psqt_value(piece: Piece, square: Square, color: Color, endgame_ratio: u8) -> ValueScore {
-    let midgame_psqt = match piece {
-        Piece::Pawn => &MIDGAME_PAWN_PSQT,
-        Piece::Knight => &MIDGAME_KNIGHT_PSQT,
-        Piece::Bishop => &MIDGAME_BISHOP_PSQT,
-        Piece::Rook => &MIDGAME_ROOK_PSQT,
-        Piece::Queen => &MIDGAME_QUEEN_PSQT,
-        Piece::King => &MIDGAME_KING_PSQT,
-    };
+/// Midgame tables, indexed by piece.
+pub const MIDGAME_PSQT: [PieceSquareTable; 6] = [
+    MIDGAME_QUEEN_PSQT,
+    MIDGAME_ROOK_PSQT,
+    MIDGAME_BISHOP_PSQT,
+    MIDGAME_KNIGHT_PSQT,
+    MIDGAME_PAWN_PSQT,
+    MIDGAME_KING_PSQT,
+];
 
-    let endgame_psqt = match piece {
-        Piece::Pawn => &ENDGAME_PAWN_PSQT,
-        Piece::Knight => &ENDGAME_KNIGHT_PSQT,
-        Piece::Bishop => &ENDGAME_BISHOP_PSQT,
-        Piece::Rook => &ENDGAME_ROOK_PSQT,
-        Piece::Queen => &ENDGAME_QUEEN_PSQT,
-        Piece::King => &ENDGAME_KING_PSQT,
-    };
+/// Endgame tables, indexed by piece.
+pub const ENDGAME_PSQT: [PieceSquareTable; 6] = [
+    ENDGAME_QUEEN_PSQT,
+    ENDGAME_ROOK_PSQT,
+    ENDGAME_BISHOP_PSQT,
+    ENDGAME_KNIGHT_PSQT,
+    ENDGAME_PAWN_PSQT,
+    ENDGAME_KING_PSQT,
+];
 
-    let square = match color {
+/// Index of the square in the tables, which are written from the perspective of White.
+pub fn psqt_index(square: Square, color: Color) -> usize {
+    match color {
         Color::White => square.flip() as usize,
         Color::Black => square as usize,
-    };
+    }
+}
 
-    let midgame_value = midgame_psqt[square];
-    let endgame_value = endgame_psqt[square];
+pub fn psqt_value(
+    piece: Piece,
+    square: Square,
+    color: Color,
+    endgame_ratio: u8,
+    params: &EvalParams,
+) -> ValueScore {
+    let square = psqt_index(square, color);
+    let midgame_value = params.midgame_psqt[piece as usize][square];
+    let endgame_value = params.endgame_psqt[piece as usize][square];
 
     let endgame_ratio = endgame_ratio as ValueScore;
     (midgame_value * (255 - endgame_ratio) + endgame_value * endgame_ratio) / 255
 }
+
+pub fn king_file_psqt_value(
+    piece: Piece,
+    square: Square,
+    king_square: Square,
+    endgame_ratio: u8,
+    params: &EvalParams,
+) -> ValueScore {
+    let piece_index = match piece {
+        Piece::Pawn => 0,
+        Piece::Knight => 1,
+        Piece::Bishop => 2,
+        _ => return 0,
+    };
+
+    let (bucket, file) = match king_square.file() {
+        0..=2 => (0, 7 - square.file()),
+        3..=4 => (1, square.file()),
+        _ => (0, square.file()),
+    };
+
+    let midgame_value = params.king_file_psqt[bucket][piece_index][file as usize];
+    midgame_value * (255 - endgame_ratio as ValueScore) / 255
+}
+
+#[cfg(test)]
+mod tests {
+    use super::king_file_psqt_value;
+    use crate::{
+        evaluation::params::DEFAULT_EVAL_PARAMS,
+        position::{board::Piece, square::Square},
+    };
+
+    #[test]
+    fn king_file_psqt_mirrors_wings() {
+        let kingside =
+            king_file_psqt_value(Piece::Pawn, Square::G2, Square::G1, 0, &DEFAULT_EVAL_PARAMS);
+        let queenside =
+            king_file_psqt_value(Piece::Pawn, Square::B2, Square::C1, 0, &DEFAULT_EVAL_PARAMS);
+        assert_eq!(kingside, queenside);
+        assert_ne!(
+            kingside,
+            king_file_psqt_value(Piece::Pawn, Square::G2, Square::E1, 0, &DEFAULT_EVAL_PARAMS)
+        );
+        assert_eq!(
+            king_file_psqt_value(Piece::Pawn, Square::G2, Square::G1, 255, &DEFAULT_EVAL_PARAMS),
+            0
+        );
+        assert_eq!(
+            king_file_psqt_value(Piece::Rook, Square::G2, Square::G1, 0, &DEFAULT_EVAL_PARAMS),
+            0
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use super::{params::DEFAULT_EVAL_PARAMS, position::midgame_ratio, Score};
+use crate::position::Position;
+
+/// Coefficients of the logarithm of the margin an advantage must clear for a win to be as likely
+/// as not, and of the logarithm of the scale of the logistic curves, both in centipawns.
+/// Each is a constant, a term for the share of the material that is gone and a term for the
+/// progress of the game. They were fitted by maximum likelihood on the scored positions of the
+/// 4000 self-play games in `books/gauntlet-v1.4.0.pgn`, against the results of those games,
+/// with `./fit_wdl.py books/gauntlet-v1.4.0.pgn`.
+const WDL_MARGIN_COEFFICIENTS: [f64; 3] = [3.809, -0.256, 1.716];
+const WDL_SCALE_COEFFICIENTS: [f64; 3] = [4.771, -0.856, 2.316];
+/// Moves after which the game counts as fully progressed.
+const WDL_MAX_FULLMOVES: f64 = 100.0;
+
+fn logistic(value: f64, margin: f64, scale: f64) -> f64 {
+    1.0 / (1.0 + ((margin - value) / scale).exp())
+}
+
+fn fitted(coefficients: [f64; 3], material_gone: f64, progress: f64) -> f64 {
+    (coefficients[0] + coefficients[1] * material_gone + coefficients[2] * progress).exp()
+}
+
+/// Chances of a win, a draw and a loss for the side to move, in thousandths, given the score
+/// of the position. A win needs the advantage to clear a margin and a loss mirrors it, so that
+/// draws take what is left in between. Both the margin and the spread of the curves grow as
+/// the game goes on, since advantages late in the game are harder to convert. At the same move
+/// number, they shrink with the material: in the games behind the fit, early trades were more
+/// often decisive.
+pub fn wdl(score: Score, position: &Position) -> (u16, u16, u16) {
+    let value = match score {
+        Score::Mate(color, _) if color == position.side_to_move => return (1000, 0, 0),
+        Score::Mate(_, _) => return (0, 0, 1000),
+        Score::Value(value) => value,
+    };
+
+    let material_gone = 1.0 - midgame_ratio(position, &DEFAULT_EVAL_PARAMS) as f64 / 255.0;
+    let progress = (position.fullmove_number as f64).min(WDL_MAX_FULLMOVES) / WDL_MAX_FULLMOVES;
+    let margin = fitted(WDL_MARGIN_COEFFICIENTS, material_gone, progress);
+    let scale = fitted(WDL_SCALE_COEFFICIENTS, material_gone, progress);
+
+    let win = (logistic(value as f64, margin, scale) * 1000.0).round() as u16;
+    let loss = (logistic(-(value as f64), margin, scale) * 1000.0).round() as u16;
+    (win, 1000 - win - loss, loss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wdl;
+    use crate::{
+        evaluation::Score,
+        position::{
+            fen::{FromFen, START_FEN},
+            Color, Position,
+        },
+    };
+
+    #[test]
+    fn wdl_adds_up_and_follows_the_score() {
+        let position = Position::from_fen(START_FEN).unwrap();
+
+        let mut last_balance = -1000;
+        for value in (-1000..=1000).step_by(50) {
+            let (win, draw, loss) = wdl(Score::Value(value), &position);
+            assert_eq!(win + draw + loss, 1000);
+            assert_eq!(wdl(Score::Value(-value), &position), (loss, draw, win));
+
+            let balance = win as i32 - loss as i32;
+            assert!(balance >= last_balance);
+            last_balance = balance;
+        }
+
+        let (win, draw, loss) = wdl(Score::Value(0), &position);
+        assert_eq!(win, loss);
+        assert!(win < 500 && draw > 0);
+    }
+
+    #[test]
+    fn wdl_is_more_decisive_with_less_material_at_the_same_move() {
+        let opening = Position::from_fen(START_FEN).unwrap();
+        let endgame = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1").unwrap();
+        let (opening_win, opening_draw, _) = wdl(Score::Value(100), &opening);
+        let (endgame_win, endgame_draw, _) = wdl(Score::Value(100), &endgame);
+        assert!(endgame_win > opening_win);
+        assert!(endgame_draw < opening_draw);
+    }
+
+    #[test]
+    fn wdl_advantages_convert_less_later_in_the_game() {
+        let early = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 20").unwrap();
+        let late = Position::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 60").unwrap();
+        assert!(wdl(Score::Value(100), &late).0 < wdl(Score::Value(100), &early).0);
+    }
+
+    #[test]
+    fn wdl_of_mates() {
+        let position = Position::from_fen(START_FEN).unwrap();
+        assert_eq!(wdl(Score::Mate(Color::White, 3), &position), (1000, 0, 0));
+        assert_eq!(wdl(Score::Mate(Color::Black, 3), &position), (0, 0, 1000));
+    }
+}
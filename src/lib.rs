@@ -1,6 +1,37 @@
+//! Camel's move generation, evaluation and search, usable outside the UCI binary.
+//!
+//! A [`Position`] is created from a FEN string, yields its legal [`Move`]s and can be
+//! searched with [`search::search`]:
+//!
+//! ```
+//! # #[cfg(feature = "search")] {
+//! use camel::{
+//!     position::fen::{FromFen, START_FEN},
+//!     search::{search, AnalysisLimit},
+//!     Position,
+//! };
+//!
+//! let position = Position::from_fen(START_FEN).unwrap();
+//! let result = search(&position, AnalysisLimit::Depth(4));
+//! assert!(result.best_move.is_some());
+//! # }
+//! ```
+//!
+//! Evaluation and search are behind the `search` feature, and tuning and data generation
+//! behind `tuner`, both enabled by default. Building with `default-features = false` leaves
+//! only positions, move generation and FEN, SAN and PGN, with no further dependencies.
+
 #![allow(clippy::too_many_arguments)]
+#[cfg(feature = "tuner")]
+pub mod datagen;
+#[cfg(feature = "search")]
 pub mod evaluation;
 pub mod moves;
 pub mod position;
+#[cfg(feature = "search")]
 pub mod search;
+#[cfg(feature = "tuner")]
 pub mod tuner;
+
+pub use moves::Move;
+pub use position::Position;
@@ -1,7 +1,14 @@
-use engine::uci_loop;
+use engine::{run_datagen, run_shrink, run_spsa, run_tune, uci_loop};
 
 mod engine;
 
 fn main() {
-    uci_loop();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("datagen") => run_datagen(&args[1..]),
+        Some("tune") => run_tune(&args[1..]),
+        Some("shrink") => run_shrink(&args[1..]),
+        Some("spsa") => run_spsa(&args[1..]),
+        _ => uci_loop(),
+    }
 }
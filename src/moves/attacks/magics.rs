@@ -1,139 +1,62 @@
-use super::sliders::{slider_attacks_from_square, BISHOP_MOVE_DIRECTIONS, ROOK_MOVE_DIRECTIONS};
-use crate::position::{bitboard::Bitboard, board::Piece, square::Square};
-use ctor::ctor;
-use rand::{rngs::StdRng, Rng, SeedableRng};
-use std::thread;
+use crate::position::bitboard::Bitboard;
 
-#[ctor]
-pub static ROOK_MAGICS: [SquareMagic; 64] = find_magics(Piece::Rook);
+// The magic numbers and attack tables are written by the build script.
+include!(concat!(env!("OUT_DIR"), "/magics.rs"));
 
-#[ctor]
-pub static BISHOP_MAGICS: [SquareMagic; 64] = find_magics(Piece::Bishop);
-
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct SquareMagic {
     pub blockers_mask: Bitboard,
     pub shift: u8,
     pub magic_number: Bitboard,
-    pub attacks: Vec<Bitboard>,
+    /// Start of the attacks of this square in the attacks table of the piece.
+    pub offset: usize,
 }
 
-fn bitsets(bitboard: Bitboard) -> Vec<Bitboard> {
-    let bitboard = bitboard.raw();
-    let mut bitsets = Vec::new();
-    let mut current_bb = 0;
-
-    loop {
-        bitsets.push(Bitboard::new(current_bb));
-        current_bb = (current_bb.wrapping_sub(bitboard)) & bitboard;
-        if current_bb == 0 {
-            break;
-        }
-    }
-
-    bitsets
-}
-
-fn sparse_random(seed: u64) -> Bitboard {
-    let mut rng = StdRng::seed_from_u64(seed);
-    let r1 = rng.gen::<u64>();
-    let r2 = rng.gen::<u64>();
-    let r3 = rng.gen::<u64>();
-
-    Bitboard::new(r1 & r2 & r3)
+/// Index of the slider attacks in the attacks table of the piece, given the board occupancy.
+pub fn magic_index(magic: &SquareMagic, occupancy: Bitboard) -> usize {
+    let blockers = occupancy & magic.blockers_mask;
+    let hash = blockers.wrapping_mul(magic.magic_number.raw());
+    magic.offset + (hash >> (64 - magic.shift)) as usize
 }
 
-fn find_magic(square: Square, piece: Piece) -> SquareMagic {
-    let directions = match piece {
-        Piece::Rook => &ROOK_MOVE_DIRECTIONS,
-        Piece::Bishop => &BISHOP_MOVE_DIRECTIONS,
-        _ => panic!("Invalid piece"),
-    };
-
-    let blockers_mask = slider_attacks_from_square::<true>(square, directions, None);
-    let shift = blockers_mask.count_ones() as u8;
-
-    let mut magic = SquareMagic {
-        blockers_mask,
-        shift,
-        magic_number: Bitboard::new(0),
-        attacks: vec![Bitboard::new(0); 1 << shift],
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        moves::attacks::sliders::{
+            slider_attacks_from_square, BISHOP_MOVE_DIRECTIONS, ROOK_MOVE_DIRECTIONS,
+        },
+        position::square::Square,
     };
 
-    let bitsets = bitsets(blockers_mask);
-    let moves = bitsets
-        .iter()
-        .map(|bitset| slider_attacks_from_square::<false>(square, directions, Some(*bitset)))
-        .collect::<Vec<_>>();
-
-    for seed in 0.. {
-        magic.magic_number = sparse_random(seed);
+    fn bitsets(bitboard: Bitboard) -> Vec<Bitboard> {
+        let bitboard = bitboard.raw();
+        let mut bitsets = Vec::new();
+        let mut current_bb = 0;
 
-        let mut found_collision = false;
-        let mut used = vec![false; 1 << shift];
-
-        for (i, bitset) in bitsets.iter().enumerate() {
-            let index = magic_index(&magic, *bitset);
-
-            if used[index] && magic.attacks[index] != moves[i] {
-                found_collision = true;
+        loop {
+            bitsets.push(Bitboard::new(current_bb));
+            current_bb = (current_bb.wrapping_sub(bitboard)) & bitboard;
+            if current_bb == 0 {
                 break;
             }
-
-            used[index] = true;
-            magic.attacks[index] = moves[i];
         }
 
-        if !found_collision {
-            let largest_used_index =
-                used.iter().enumerate().filter(|(_, used)| **used).map(|(i, _)| i).max().unwrap();
-            magic.attacks.resize(largest_used_index + 1, Bitboard::new(0));
-            return magic;
-        }
+        bitsets
     }
 
-    unreachable!()
-}
-
-fn find_magics(piece: Piece) -> [SquareMagic; 64] {
-    (0..64)
-        .map(|square| thread::spawn(move || find_magic(Square::from(square).unwrap(), piece)))
-        .collect::<Vec<_>>()
-        .into_iter()
-        .map(|h| h.join().unwrap())
-        .collect::<Vec<_>>()
-        .try_into()
-        .unwrap()
-}
-
-pub fn magic_index(magic: &SquareMagic, occupancy: Bitboard) -> usize {
-    let blockers = occupancy & magic.blockers_mask;
-    let hash = blockers.wrapping_mul(magic.magic_number.raw());
-    (hash >> (64 - magic.shift)) as usize
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn test_magics(piece: Piece) {
-        let directions = match piece {
-            Piece::Rook => &ROOK_MOVE_DIRECTIONS,
-            Piece::Bishop => &BISHOP_MOVE_DIRECTIONS,
-            _ => panic!("Invalid piece"),
-        };
-
+    fn test_magics(magics: &[SquareMagic; 64], attacks: &[u64], directions: &[i8]) {
         for square in Square::list() {
-            let magic = find_magic(*square, piece);
+            let magic = &magics[*square as usize];
 
             let blockers_mask = slider_attacks_from_square::<true>(*square, directions, None);
-            let bitsets = bitsets(blockers_mask);
+            assert_eq!(magic.blockers_mask, blockers_mask);
 
-            for bitset in bitsets {
-                let index = magic_index(&magic, bitset);
+            for bitset in bitsets(blockers_mask) {
+                let index = magic_index(magic, bitset);
                 assert_eq!(
-                    magic.attacks[index],
-                    slider_attacks_from_square::<false>(*square, directions, Some(bitset),)
+                    Bitboard::new(attacks[index]),
+                    slider_attacks_from_square::<false>(*square, directions, Some(bitset))
                 );
             }
         }
@@ -162,11 +85,11 @@ mod tests {
 
     #[test]
     fn rook_magics() {
-        test_magics(Piece::Rook);
+        test_magics(&ROOK_MAGICS, &ROOK_ATTACKS, &ROOK_MOVE_DIRECTIONS);
     }
 
     #[test]
     fn bishop_magics() {
-        test_magics(Piece::Bishop);
+        test_magics(&BISHOP_MAGICS, &BISHOP_ATTACKS, &BISHOP_MOVE_DIRECTIONS);
     }
 }
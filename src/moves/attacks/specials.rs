@@ -41,14 +41,14 @@ pub fn generate_pawn_moves(stage: MoveStage, position: &Position, moves: &mut Ve
         MoveStage::HashMove => panic!("Hash move should not be generated here"),
         MoveStage::CapturesAndPromotions => single_push_pawns & PAWN_PROMOTION_RANKS,
         MoveStage::NonCaptures => single_push_pawns & !PAWN_PROMOTION_RANKS,
-        MoveStage::All => single_push_pawns,
+        MoveStage::Evasions | MoveStage::All => single_push_pawns,
     };
     for to_square in single_push_pawns_on_stage {
         let from_square = to_square.shift(-direction).unwrap();
         push_pawn_move(occupancy, moves, from_square, to_square);
     }
 
-    if matches!(stage, MoveStage::All | MoveStage::NonCaptures) {
+    if matches!(stage, MoveStage::All | MoveStage::Evasions | MoveStage::NonCaptures) {
         // Double push
         let third_row_bb = match position.side_to_move {
             Color::White => THIRD_ROW_WHITE,
@@ -62,7 +62,7 @@ pub fn generate_pawn_moves(stage: MoveStage, position: &Position, moves: &mut Ve
         }
     }
 
-    if matches!(stage, MoveStage::All | MoveStage::CapturesAndPromotions) {
+    if matches!(stage, MoveStage::All | MoveStage::Evasions | MoveStage::CapturesAndPromotions) {
         // West capture
         let west_pawns = (our_pawns & !PAWN_WEST_EDGE_FILE).shift(direction + MoveDirection::WEST)
             & occupancy_them;
@@ -266,6 +266,11 @@ fn castle_range_ok(color: Color, board: Board, king_square: Square, rook_square:
         return false;
     }
 
+    // In Chess960, the castling rook may stand between an enemy slider and the king's path,
+    // but it leaves its square when castling, so it cannot shield the king.
+    let mut board = board;
+    board.clear_square(rook_square);
+
     let mut attacked_range = Bitboard::rank_range(king_square, final_king_square);
 
     attacked_range.all(|sq| square_attackers::<true>(&board, sq, color.opposite()).is_empty())
@@ -305,7 +310,7 @@ mod tests {
     use super::generate_king_castles;
     use crate::{
         moves::{attacks::specials::generate_pawn_moves, gen::MoveStage, Move, MoveFlag},
-        position::{fen::FromFen, square::Square, Position},
+        position::{board::Piece, fen::FromFen, square::Square, Color, Position},
     };
 
     #[test]
@@ -441,4 +446,42 @@ mod tests {
 
         assert_eq!(moves.len(), 0);
     }
+
+    #[test]
+    fn castle_chess960_adjacent_king_and_rook() {
+        let position = Position::from_fen("7k/8/8/8/8/8/8/5KR1 w G - 0 1").unwrap();
+
+        let mut moves = Vec::new();
+        generate_king_castles(&position, &mut moves);
+
+        assert_eq!(moves, vec![Move::new(Square::F1, Square::G1, MoveFlag::KingsideCastle)]);
+        assert!(moves[0].is_pseudo_legal(&position));
+        assert_eq!(
+            position.make_move(moves[0]).board.piece_color_at(Square::F1),
+            Some((Piece::Rook, Color::White))
+        );
+    }
+
+    #[test]
+    fn castle_chess960_occupied_destination() {
+        let position = Position::from_fen("7k/8/8/8/8/8/8/RK1N4 w A - 0 1").unwrap();
+
+        let mut moves = Vec::new();
+        generate_king_castles(&position, &mut moves);
+
+        assert!(moves.is_empty());
+        assert!(!Move::new(Square::B1, Square::A1, MoveFlag::QueensideCastle)
+            .is_pseudo_legal(&position));
+    }
+
+    #[test]
+    fn castle_chess960_rook_shielding_king_path() {
+        // Once the rook on b1 leaves, the king on c1 would be attacked by the rook on a1.
+        let position = Position::from_fen("7k/8/8/8/8/8/8/rR1K4 w B - 0 1").unwrap();
+
+        let mut moves = Vec::new();
+        generate_king_castles(&position, &mut moves);
+
+        assert!(moves.is_empty());
+    }
 }
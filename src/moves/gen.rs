@@ -1,7 +1,7 @@
 use super::{
     attacks::{
         leapers::{KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS_BLACK, PAWN_ATTACKS_WHITE},
-        magics::{magic_index, BISHOP_MAGICS, ROOK_MAGICS},
+        magics::{magic_index, BISHOP_ATTACKS, BISHOP_MAGICS, ROOK_ATTACKS, ROOK_MAGICS},
         specials::{generate_king_castles, generate_pawn_moves},
     },
     make_move, Move, MoveFlag,
@@ -18,6 +18,8 @@ pub enum MoveStage {
     HashMove,
     CapturesAndPromotions,
     NonCaptures,
+    /// All legal moves of a side in check. Only valid when the side to move is in check.
+    Evasions,
     All,
 }
 
@@ -104,14 +106,10 @@ pub fn piece_attacks(piece: Piece, square: Square, occupancy: Bitboard, color: C
         Piece::Knight => KNIGHT_ATTACKS[square as usize],
         Piece::King => KING_ATTACKS[square as usize],
         Piece::Rook => {
-            let magic = &ROOK_MAGICS[square as usize];
-            let index = magic_index(magic, occupancy);
-            magic.attacks[index]
+            Bitboard::new(ROOK_ATTACKS[magic_index(&ROOK_MAGICS[square as usize], occupancy)])
         }
         Piece::Bishop => {
-            let magic = &BISHOP_MAGICS[square as usize];
-            let index = magic_index(magic, occupancy);
-            magic.attacks[index]
+            Bitboard::new(BISHOP_ATTACKS[magic_index(&BISHOP_MAGICS[square as usize], occupancy)])
         }
         Piece::Queen => {
             piece_attacks(Piece::Rook, square, occupancy, color)
@@ -124,6 +122,18 @@ pub fn piece_attacks(piece: Piece, square: Square, occupancy: Bitboard, color: C
     }
 }
 
+pub fn xray_attacks(
+    piece: Piece,
+    square: Square,
+    occupancy: Bitboard,
+    blockers: Bitboard,
+    color: Color,
+) -> Bitboard {
+    let attacks = piece_attacks(piece, square, occupancy, color);
+    let first_blockers = blockers & attacks;
+    piece_attacks(piece, square, occupancy & !first_blockers, color) & !attacks
+}
+
 pub fn generate_regular_moves(
     stage: MoveStage,
     board: &Board,
@@ -144,7 +154,9 @@ pub fn generate_regular_moves(
             MoveStage::NonCaptures => {
                 piece_attacks(piece, from_square, occupancy, color) & !occupancy
             }
-            MoveStage::All => piece_attacks(piece, from_square, occupancy, color) & !occupancy_us,
+            MoveStage::Evasions | MoveStage::All => {
+                piece_attacks(piece, from_square, occupancy, color) & !occupancy_us
+            }
         };
 
         for to_square in attacks {
@@ -157,12 +169,53 @@ pub fn generate_regular_moves(
     }
 }
 
+/// King safety information of the side to move, which is needed both by the move
+/// generator and the search. It is computed once per node and shared between them.
+#[derive(Debug, Copy, Clone)]
+pub struct CheckInfo {
+    pub checkers: Bitboard,
+    pub king_square: Square,
+    pub king_rays: Bitboard,
+}
+
+impl CheckInfo {
+    pub fn new(position: &Position) -> Self {
+        let side_to_move = position.side_to_move;
+        let board = &position.board;
+        let king_square = board.pieces_bb_color(Piece::King, side_to_move).next().unwrap();
+
+        Self {
+            checkers: king_square_attackers::<false>(board, side_to_move.opposite()),
+            king_square,
+            king_rays: piece_attacks(
+                Piece::Queen,
+                king_square,
+                board.occupancy_bb_all(),
+                side_to_move,
+            ),
+        }
+    }
+
+    pub fn is_check(&self) -> bool {
+        self.checkers.is_not_empty()
+    }
+}
+
 pub fn generate_moves(stage: MoveStage, position: &Position) -> Vec<Move> {
+    generate_moves_with_info(stage, position, &CheckInfo::new(position))
+}
+
+pub fn generate_moves_with_info(
+    stage: MoveStage,
+    position: &Position,
+    check_info: &CheckInfo,
+) -> Vec<Move> {
     let mut moves = Vec::with_capacity(64);
     let side_to_move = position.side_to_move;
     let board = &position.board;
 
-    let checkers = king_square_attackers::<false>(board, side_to_move.opposite());
+    let checkers = check_info.checkers;
+    debug_assert!(stage != MoveStage::Evasions || checkers.is_not_empty());
 
     if checkers.count_ones() > 1 {
         // Double check requires the king to move.
@@ -181,7 +234,9 @@ pub fn generate_moves(stage: MoveStage, position: &Position) -> Vec<Move> {
         }
     }
 
-    let king_square = board.pieces_bb_color(Piece::King, side_to_move).next().unwrap();
+    let king_square = check_info.king_square;
+    let king_rays = check_info.king_rays;
+    let possibly_pinned = king_rays & position.board.occupancy_bb(side_to_move);
 
     moves.retain(|mov| {
         match mov.flag() {
@@ -202,14 +257,6 @@ pub fn generate_moves(stage: MoveStage, position: &Position) -> Vec<Move> {
                 return false;
             }
             _ if mov.from() != king_square => {
-                let king_rays = piece_attacks(
-                    Piece::Queen,
-                    king_square,
-                    board.occupancy_bb_all(),
-                    side_to_move,
-                );
-                let possibly_pinned = king_rays & position.board.occupancy_bb(side_to_move);
-
                 match checkers.count_ones() {
                     1 => {
                         if checkers.is_set(mov.to()) && !possibly_pinned.is_set(mov.from()) {
@@ -250,6 +297,10 @@ pub fn generate_moves(stage: MoveStage, position: &Position) -> Vec<Move> {
 }
 
 pub fn perft<const STAGED: bool, const ROOT: bool>(position: &Position, depth: u8) -> u64 {
+    perft_in_place::<STAGED, ROOT>(&mut position.clone(), depth)
+}
+
+fn perft_in_place<const STAGED: bool, const ROOT: bool>(position: &mut Position, depth: u8) -> u64 {
     if depth == 0 {
         return 1;
     }
@@ -269,8 +320,9 @@ pub fn perft<const STAGED: bool, const ROOT: bool>(position: &Position, depth: u
     let mut nodes = 0;
 
     for mov in moves {
-        let new_position = make_move(position, mov);
-        let count = perft::<STAGED, false>(&new_position, depth - 1);
+        let undo = position.make_move_in_place(mov);
+        let count = perft_in_place::<STAGED, false>(position, depth - 1);
+        position.unmake_move(mov, undo);
         nodes += count;
 
         if ROOT {
@@ -287,6 +339,7 @@ mod tests {
         moves::gen::MoveStage,
         position::{
             bitboard::Bitboard,
+            board::Piece,
             fen::{FromFen, KIWIPETE_WHITE_FEN},
             square::Square,
             Color, Position,
@@ -345,6 +398,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn xray_attacks_through_blockers() {
+        let position = Position::from_fen("4k3/4r3/8/8/8/4Q3/8/4R1K1 w - - 0 1").unwrap();
+        let occupancy = position.board.occupancy_bb_all();
+
+        let xray = super::xray_attacks(
+            Piece::Rook,
+            Square::E1,
+            occupancy,
+            position.board.occupancy_bb(Color::White),
+            Color::White,
+        );
+
+        assert!(xray.is_set(Square::E4));
+        assert!(xray.is_set(Square::E7));
+        assert!(!xray.is_set(Square::E3));
+        assert!(!xray.is_set(Square::E8));
+    }
+
     #[test]
     fn gen_simple_all() {
         let position = Position::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - ").unwrap();
@@ -388,4 +460,17 @@ mod tests {
         let moves = super::generate_moves(MoveStage::CapturesAndPromotions, &position);
         assert_eq!(moves.len(), 0);
     }
+
+    #[test]
+    fn gen_in_check_evasions() {
+        let position =
+            Position::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1")
+                .unwrap();
+
+        let mut evasions = super::generate_moves(MoveStage::Evasions, &position);
+        let mut all_moves = super::generate_moves(MoveStage::All, &position);
+        evasions.sort_by_key(|mov| mov.to_string());
+        all_moves.sort_by_key(|mov| mov.to_string());
+        assert_eq!(evasions, all_moves);
+    }
 }
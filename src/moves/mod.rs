@@ -1,6 +1,6 @@
 use self::{
-    attacks::specials::pawn_attacks,
-    gen::{piece_attacks, square_attackers, MoveDirection},
+    attacks::specials::{generate_king_castles, pawn_attacks},
+    gen::{king_square_attackers, piece_attacks, square_attackers, MoveDirection},
 };
 use crate::position::{
     bitboard::Bitboard, board::Piece, square::Square, CastlingRights, Color, Position,
@@ -10,6 +10,7 @@ use primitive_enum::primitive_enum;
 
 pub mod attacks;
 pub mod gen;
+pub mod san;
 
 primitive_enum!(
     MoveFlag u8;
@@ -74,6 +75,8 @@ impl MoveFlag {
     }
 }
 
+/// A move packed in 16 bits: origin and destination squares, and a flag with its kind.
+/// It displays in the long algebraic notation used by UCI.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Move(u16);
 
@@ -130,8 +133,9 @@ impl Move {
                 return false;
             }
 
-            // Basic check test for king moves.
+            // Basic check test for king moves. Castles are validated below.
             if piece == Piece::King
+                && !self.flag().is_castle()
                 && square_attackers::<true>(
                     &position.board,
                     self.to(),
@@ -142,10 +146,29 @@ impl Move {
                 return false;
             }
 
+            // Pawn moves must match the geometry implied by their flag,
+            // since a move from a colliding hash entry can carry any flag.
+            let forward = MoveDirection::pawn_direction(position.side_to_move);
+            let (start_rank, promotion_rank) = match position.side_to_move {
+                Color::White => (1, 7),
+                Color::Black => (6, 0),
+            };
+            let pawn_push = self.from().shift(forward) == Some(self.to());
+            let pawn_attack = piece == Piece::Pawn
+                && piece_attacks(
+                    Piece::Pawn,
+                    self.from(),
+                    position.board.occupancy_bb_all(),
+                    position.side_to_move,
+                )
+                .is_set(self.to());
+            let promotes = self.to().rank() == promotion_rank;
+
             match self.flag() {
                 MoveFlag::Quiet if piece == Piece::Pawn => {
-                    to_color.is_none() && self.to().file() == self.from().file()
+                    to_color.is_none() && pawn_push && !promotes
                 }
+                MoveFlag::Capture if piece == Piece::Pawn => pawn_attack && !promotes,
                 MoveFlag::Quiet | MoveFlag::Capture => {
                     let attacks = piece_attacks(
                         piece,
@@ -154,49 +177,41 @@ impl Move {
                         position.side_to_move,
                     );
                     attacks.is_set(self.to())
+                        && (self.flag() == MoveFlag::Capture) == to_color.is_some()
                 }
-                MoveFlag::KingsideCastle => {
-                    let castle_right = match position.side_to_move {
-                        Color::White => CastlingRights::WHITE_KINGSIDE,
-                        Color::Black => CastlingRights::BLACK_KINGSIDE,
-                    };
-                    piece == Piece::King
-                        && position.castling_rights.contains(castle_right)
-                        && (!position.is_chess960).then(|| to_piece.is_none()).unwrap_or(true)
-                }
-                MoveFlag::QueensideCastle => {
-                    let castle_right = match position.side_to_move {
-                        Color::White => CastlingRights::WHITE_QUEENSIDE,
-                        Color::Black => CastlingRights::BLACK_QUEENSIDE,
-                    };
-                    piece == Piece::King
-                        && position.castling_rights.contains(castle_right)
-                        && (!position.is_chess960).then(|| to_piece.is_none()).unwrap_or(true)
+                MoveFlag::KingsideCastle | MoveFlag::QueensideCastle => {
+                    // Castling has many subtle conditions, so we defer to the generator.
+                    let mut castles = Vec::with_capacity(2);
+                    if piece == Piece::King && !position.is_check() {
+                        generate_king_castles(position, &mut castles);
+                    }
+                    castles.contains(self)
                 }
                 MoveFlag::BishopPromotion
                 | MoveFlag::KnightPromotion
                 | MoveFlag::RookPromotion
-                | MoveFlag::QueenPromotion => piece == Piece::Pawn && to_color.is_none(),
+                | MoveFlag::QueenPromotion => {
+                    piece == Piece::Pawn && to_color.is_none() && pawn_push && promotes
+                }
                 MoveFlag::BishopPromotionCapture
                 | MoveFlag::KnightPromotionCapture
                 | MoveFlag::RookPromotionCapture
                 | MoveFlag::QueenPromotionCapture => {
-                    piece == Piece::Pawn && to_color == Some(position.side_to_move.opposite())
+                    pawn_attack && to_color == Some(position.side_to_move.opposite()) && promotes
                 }
                 MoveFlag::EnPassantCapture => {
-                    piece == Piece::Pawn
+                    pawn_attack
                         && to_color.is_none()
                         && position.en_passant_square == Some(self.to())
                 }
                 MoveFlag::DoublePawnPush => {
                     piece == Piece::Pawn
+                        && self.from().rank() == start_rank
                         && to_color.is_none()
+                        && self.from().shift(forward * 2) == Some(self.to())
                         && self
-                            .to()
-                            .shift(match position.side_to_move {
-                                Color::White => MoveDirection::SOUTH,
-                                Color::Black => MoveDirection::NORTH,
-                            })
+                            .from()
+                            .shift(forward)
                             .map_or(false, |sq| position.board.color_at(sq).is_none())
                 }
             }
@@ -204,6 +219,51 @@ impl Move {
             false
         }
     }
+
+    pub fn is_legal(&self, position: &Position) -> bool {
+        if !self.is_pseudo_legal(position) {
+            return false;
+        }
+
+        let new_position = make_move(position, *self);
+        king_square_attackers::<true>(&new_position.board, new_position.side_to_move).is_empty()
+    }
+
+    /// Whether the move leaves the opponent in check, directly or by uncovering a slider.
+    /// Cheaper than making the move, except for castles and en passant, which are rare.
+    pub fn gives_check(&self, position: &Position) -> bool {
+        let flag = self.flag();
+        if flag.is_castle() || flag == MoveFlag::EnPassantCapture {
+            return make_move(position, *self).is_check();
+        }
+
+        let board = &position.board;
+        let color = position.side_to_move;
+        let (Some(piece), Some(king_square)) = (
+            board.piece_at(self.from()),
+            board.pieces_bb_color(Piece::King, color.opposite()).next(),
+        ) else {
+            return false;
+        };
+
+        let mut occupancy = board.occupancy_bb_all();
+        occupancy.clear(self.from());
+        occupancy.set(self.to());
+
+        let moved_piece = self.promotion_piece().unwrap_or(piece);
+        if piece_attacks(moved_piece, self.to(), occupancy, color).is_set(king_square) {
+            return true;
+        }
+
+        let mut ours = board.occupancy_bb(color);
+        ours.clear(self.from());
+        let queens = board.pieces_bb(Piece::Queen);
+        let rooks_queens = (board.pieces_bb(Piece::Rook) | queens) & ours;
+        let bishops_queens = (board.pieces_bb(Piece::Bishop) | queens) & ours;
+        (piece_attacks(Piece::Rook, king_square, occupancy, color) & rooks_queens).is_not_empty()
+            || (piece_attacks(Piece::Bishop, king_square, occupancy, color) & bishops_queens)
+                .is_not_empty()
+    }
 }
 
 impl std::fmt::Display for Move {
@@ -226,97 +286,55 @@ impl std::fmt::Display for Move {
 }
 
 pub fn make_move(position: &Position, mov: Move) -> Position {
-    let mut new_board = position.board;
-    let mut new_castling_rights = position.castling_rights;
-    let mut new_en_passant_square = None;
-
-    let piece = new_board.piece_at(mov.from()).unwrap();
-    let mov_flag = mov.flag();
+    let mut new_position = *position;
+    make_move_in_place(&mut new_position, mov);
+    new_position
+}
 
-    new_board.clear_square(mov.from());
+/// State lost when making a move, which is needed to take it back.
+#[derive(Debug, Copy, Clone)]
+pub struct UndoInfo {
+    captured_piece: Option<Piece>,
+    castling_rights: CastlingRights,
+    en_passant_square: Option<Square>,
+    halfmove_clock: u8,
+}
 
-    if mov_flag.is_castle() {
-        match mov_flag {
-            MoveFlag::KingsideCastle => match position.side_to_move {
-                Color::White => {
-                    let right_hand_side_rook =
-                        position.is_chess960.then(|| mov.to()).unwrap_or(Square::H1);
-                    new_board.clear_square(right_hand_side_rook);
-                    new_board.set_square(Square::G1, Piece::King, Color::White);
-                    new_board.set_square(Square::F1, Piece::Rook, Color::White);
-                    new_castling_rights.remove(CastlingRights::WHITE_KINGSIDE);
-                    new_castling_rights.remove(CastlingRights::WHITE_QUEENSIDE);
-                }
-                Color::Black => {
-                    let right_hand_side_rook =
-                        position.is_chess960.then(|| mov.to()).unwrap_or(Square::H8);
-                    new_board.clear_square(right_hand_side_rook);
-                    new_board.set_square(Square::G8, Piece::King, Color::Black);
-                    new_board.set_square(Square::F8, Piece::Rook, Color::Black);
-                    new_castling_rights.remove(CastlingRights::BLACK_KINGSIDE);
-                    new_castling_rights.remove(CastlingRights::BLACK_QUEENSIDE);
-                }
-            },
-            MoveFlag::QueensideCastle => match position.side_to_move {
-                Color::White => {
-                    let left_hand_side_rook =
-                        position.is_chess960.then(|| mov.to()).unwrap_or(Square::A1);
-                    new_board.clear_square(left_hand_side_rook);
-                    new_board.set_square(Square::C1, Piece::King, Color::White);
-                    new_board.set_square(Square::D1, Piece::Rook, Color::White);
-                    new_castling_rights.remove(CastlingRights::WHITE_KINGSIDE);
-                    new_castling_rights.remove(CastlingRights::WHITE_QUEENSIDE);
-                }
-                Color::Black => {
-                    let left_hand_side_rook =
-                        position.is_chess960.then(|| mov.to()).unwrap_or(Square::A8);
-                    new_board.clear_square(left_hand_side_rook);
-                    new_board.set_square(Square::C8, Piece::King, Color::Black);
-                    new_board.set_square(Square::D8, Piece::Rook, Color::Black);
-                    new_castling_rights.remove(CastlingRights::BLACK_KINGSIDE);
-                    new_castling_rights.remove(CastlingRights::BLACK_QUEENSIDE);
-                }
-            },
-            _ => unreachable!(),
+fn castle_squares(mov: Move, position: &Position) -> (Square, Square, Square) {
+    // Rook origin, king destination and rook destination.
+    match (mov.flag(), position.side_to_move) {
+        (MoveFlag::KingsideCastle, Color::White) => {
+            (position.is_chess960.then(|| mov.to()).unwrap_or(Square::H1), Square::G1, Square::F1)
         }
-    } else if let Some(promotion_piece) = mov_flag.promotion_piece() {
-        new_board.set_square(mov.to(), promotion_piece, position.side_to_move);
-    } else if mov_flag == MoveFlag::DoublePawnPush {
-        new_board.set_square(mov.to(), piece, position.side_to_move);
-
-        let candidate_en_passant = match position.side_to_move {
-            Color::White => mov.to().shift(MoveDirection::SOUTH).unwrap(),
-            Color::Black => mov.to().shift(MoveDirection::NORTH).unwrap(),
-        };
-        if pawn_attacks(&position.board, position.side_to_move.opposite())
-            .is_set(candidate_en_passant)
-        {
-            new_en_passant_square = Some(candidate_en_passant);
+        (MoveFlag::KingsideCastle, Color::Black) => {
+            (position.is_chess960.then(|| mov.to()).unwrap_or(Square::H8), Square::G8, Square::F8)
         }
-    } else if mov_flag == MoveFlag::EnPassantCapture {
-        new_board.set_square(mov.to(), Piece::Pawn, position.side_to_move);
-        new_board.clear_square(match position.side_to_move {
-            Color::White => mov.to().shift(MoveDirection::SOUTH).unwrap(),
-            Color::Black => mov.to().shift(MoveDirection::NORTH).unwrap(),
-        });
-    } else {
-        new_board.set_square(mov.to(), piece, position.side_to_move);
+        (MoveFlag::QueensideCastle, Color::White) => {
+            (position.is_chess960.then(|| mov.to()).unwrap_or(Square::A1), Square::C1, Square::D1)
+        }
+        (MoveFlag::QueensideCastle, Color::Black) => {
+            (position.is_chess960.then(|| mov.to()).unwrap_or(Square::A8), Square::C8, Square::D8)
+        }
+        _ => unreachable!(),
+    }
+}
 
-        if !position.castling_rights.is_empty() && matches!(piece, Piece::Rook | Piece::King) {
-            let king_square = match position.side_to_move {
-                Color::White => (position.board.pieces_bb_color(Piece::King, Color::White)).next(),
-                Color::Black => (position.board.pieces_bb_color(Piece::King, Color::Black)).next(),
-            };
-            let king_rank_rooks = match position.side_to_move {
-                Color::White => {
-                    Bitboard::rank_mask(0)
-                        & position.board.pieces_bb_color(Piece::Rook, Color::White)
-                }
-                Color::Black => {
-                    Bitboard::rank_mask(7)
-                        & position.board.pieces_bb_color(Piece::Rook, Color::Black)
-                }
-            };
+fn lost_castling_rights(position: &Position, piece: Piece, from: Square) -> CastlingRights {
+    let (kingside, queenside) = match position.side_to_move {
+        Color::White => (CastlingRights::WHITE_KINGSIDE, CastlingRights::WHITE_QUEENSIDE),
+        Color::Black => (CastlingRights::BLACK_KINGSIDE, CastlingRights::BLACK_QUEENSIDE),
+    };
+
+    match piece {
+        Piece::King => kingside | queenside,
+        Piece::Rook => {
+            let king_square =
+                position.board.pieces_bb_color(Piece::King, position.side_to_move).next();
+            let king_rank_rooks =
+                Bitboard::rank_mask(match position.side_to_move {
+                    Color::White => 0,
+                    Color::Black => 7,
+                }) & position.board.pieces_bb_color(Piece::Rook, position.side_to_move);
             let left_hand_side_rook = king_rank_rooks
                 .into_iter()
                 .next()
@@ -326,48 +344,118 @@ pub fn make_move(position: &Position, mov: Move) -> Position {
                 .next_back()
                 .filter(|sq| sq.file() > king_square.map_or(7, Square::file));
 
-            match (position.side_to_move, piece, mov.from()) {
-                (Color::White, Piece::King, _) => {
-                    new_castling_rights.remove(CastlingRights::WHITE_KINGSIDE);
-                    new_castling_rights.remove(CastlingRights::WHITE_QUEENSIDE);
-                }
-                (Color::Black, Piece::King, _) => {
-                    new_castling_rights.remove(CastlingRights::BLACK_KINGSIDE);
-                    new_castling_rights.remove(CastlingRights::BLACK_QUEENSIDE);
-                }
-                (Color::White, Piece::Rook, square) if Some(square) == left_hand_side_rook => {
-                    new_castling_rights.remove(CastlingRights::WHITE_QUEENSIDE);
-                }
-                (Color::White, Piece::Rook, square) if Some(square) == right_hand_side_rook => {
-                    new_castling_rights.remove(CastlingRights::WHITE_KINGSIDE);
-                }
-                (Color::Black, Piece::Rook, square) if Some(square) == left_hand_side_rook => {
-                    new_castling_rights.remove(CastlingRights::BLACK_QUEENSIDE);
-                }
-                (Color::Black, Piece::Rook, square) if Some(square) == right_hand_side_rook => {
-                    new_castling_rights.remove(CastlingRights::BLACK_KINGSIDE);
-                }
-                _ => (),
+            if Some(from) == left_hand_side_rook {
+                queenside
+            } else if Some(from) == right_hand_side_rook {
+                kingside
+            } else {
+                CastlingRights::empty()
             }
         }
+        _ => CastlingRights::empty(),
     }
+}
 
-    Position {
-        board: new_board,
-        side_to_move: position.side_to_move.opposite(),
-        en_passant_square: new_en_passant_square,
-        castling_rights: new_castling_rights,
-        halfmove_clock: if piece == Piece::Pawn || mov_flag.is_capture() {
-            0
-        } else {
-            position.halfmove_clock.saturating_add(1)
-        },
-        fullmove_number: if position.side_to_move == Color::Black {
-            position.fullmove_number.saturating_add(1)
-        } else {
-            position.fullmove_number
-        },
-        is_chess960: position.is_chess960,
+fn en_passant_victim_square(mov: Move, color: Color) -> Square {
+    match color {
+        Color::White => mov.to().shift(MoveDirection::SOUTH).unwrap(),
+        Color::Black => mov.to().shift(MoveDirection::NORTH).unwrap(),
+    }
+}
+
+/// Makes the move on the position itself, returning what is needed to unmake it.
+pub fn make_move_in_place(position: &mut Position, mov: Move) -> UndoInfo {
+    let side_to_move = position.side_to_move;
+    let piece = position.board.piece_at(mov.from()).unwrap();
+    let mov_flag = mov.flag();
+
+    let undo = UndoInfo {
+        captured_piece: if mov_flag.is_castle() { None } else { position.board.piece_at(mov.to()) },
+        castling_rights: position.castling_rights,
+        en_passant_square: position.en_passant_square,
+        halfmove_clock: position.halfmove_clock,
+    };
+
+    // Rights are lost based on the board before the move.
+    if !position.castling_rights.is_empty() && !mov_flag.is_castle() {
+        position.castling_rights.remove(lost_castling_rights(position, piece, mov.from()));
+    }
+    position.en_passant_square = None;
+    position.board.clear_square(mov.from());
+
+    if mov_flag.is_castle() {
+        let (rook_square, king_destination, rook_destination) = castle_squares(mov, position);
+        position.board.clear_square(rook_square);
+        position.board.set_square(king_destination, Piece::King, side_to_move);
+        position.board.set_square(rook_destination, Piece::Rook, side_to_move);
+        position.castling_rights.remove(lost_castling_rights(position, Piece::King, mov.from()));
+    } else if let Some(promotion_piece) = mov_flag.promotion_piece() {
+        position.board.set_square(mov.to(), promotion_piece, side_to_move);
+    } else if mov_flag == MoveFlag::DoublePawnPush {
+        position.board.set_square(mov.to(), piece, side_to_move);
+
+        let candidate_en_passant = en_passant_victim_square(mov, side_to_move);
+        if pawn_attacks(&position.board, side_to_move.opposite()).is_set(candidate_en_passant) {
+            position.en_passant_square = Some(candidate_en_passant);
+        }
+    } else if mov_flag == MoveFlag::EnPassantCapture {
+        position.board.set_square(mov.to(), Piece::Pawn, side_to_move);
+        position.board.clear_square(en_passant_victim_square(mov, side_to_move));
+    } else {
+        position.board.set_square(mov.to(), piece, side_to_move);
+    }
+
+    position.halfmove_clock = if piece == Piece::Pawn || mov_flag.is_capture() {
+        0
+    } else {
+        position.halfmove_clock.saturating_add(1)
+    };
+    if side_to_move == Color::Black {
+        position.fullmove_number = position.fullmove_number.saturating_add(1);
+    }
+    position.side_to_move = side_to_move.opposite();
+
+    undo
+}
+
+/// Takes back a move made with `make_move_in_place`.
+pub fn unmake_move(position: &mut Position, mov: Move, undo: UndoInfo) {
+    let side_to_move = position.side_to_move.opposite();
+    position.side_to_move = side_to_move;
+    if side_to_move == Color::Black {
+        position.fullmove_number = position.fullmove_number.saturating_sub(1);
+    }
+    position.castling_rights = undo.castling_rights;
+    position.en_passant_square = undo.en_passant_square;
+    position.halfmove_clock = undo.halfmove_clock;
+
+    let mov_flag = mov.flag();
+    if mov_flag.is_castle() {
+        // The king and rook may swap squares in Chess960, so clear both before placing them.
+        let (rook_square, king_destination, rook_destination) = castle_squares(mov, position);
+        position.board.clear_square(king_destination);
+        position.board.clear_square(rook_destination);
+        position.board.set_square(mov.from(), Piece::King, side_to_move);
+        position.board.set_square(rook_square, Piece::Rook, side_to_move);
+        return;
+    }
+
+    let piece = if mov_flag.promotion_piece().is_some() {
+        Piece::Pawn
+    } else {
+        position.board.piece_at(mov.to()).unwrap()
+    };
+    position.board.clear_square(mov.to());
+    position.board.set_square(mov.from(), piece, side_to_move);
+
+    if mov_flag == MoveFlag::EnPassantCapture {
+        position.board.set_square(
+            en_passant_victim_square(mov, side_to_move),
+            Piece::Pawn,
+            side_to_move.opposite(),
+        );
+    } else if let Some(captured_piece) = undo.captured_piece {
+        position.board.set_square(mov.to(), captured_piece, side_to_move.opposite());
     }
 }
 
@@ -475,6 +563,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn gives_check_agrees_with_making_the_move() {
+        for fen in [
+            KIWIPETE_WHITE_FEN,
+            KIWIPETE_BLACK_FEN,
+            "4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1",
+            "3qk3/1P6/8/3Pp3/8/8/6B1/R3K2R w KQ e6 0 1",
+            "r3k2r/8/8/1b6/8/3n4/2p5/5K2 b kq - 0 1",
+        ] {
+            let position = Position::from_fen(fen).unwrap();
+            for mov in position.moves(gen::MoveStage::All) {
+                assert_eq!(
+                    mov.gives_check(&position),
+                    position.make_move(mov).is_check(),
+                    "{} in {}",
+                    mov,
+                    fen
+                );
+            }
+        }
+    }
+
     #[test]
     fn pseudo_legal_chess() {
         let start_position = Position::from_fen(START_FEN).unwrap();
@@ -510,4 +620,68 @@ mod tests {
             assert!(mov.is_pseudo_legal(&position));
         }
     }
+
+    #[test]
+    fn pseudo_legal_inconsistent_flags() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+
+        // Capture flag on an empty square, and quiet flag on an occupied one.
+        assert!(!Move::new(Square::C3, Square::B5, MoveFlag::Capture).is_pseudo_legal(&position));
+        assert!(!Move::new(Square::E5, Square::D7, MoveFlag::Quiet).is_pseudo_legal(&position));
+
+        // Pawn moves with the wrong geometry.
+        assert!(!Move::new(Square::A2, Square::A5, MoveFlag::Quiet).is_pseudo_legal(&position));
+        assert!(
+            !Move::new(Square::D5, Square::D7, MoveFlag::DoublePawnPush).is_pseudo_legal(&position)
+        );
+        assert!(
+            !Move::new(Square::A2, Square::A3, MoveFlag::QueenPromotion).is_pseudo_legal(&position)
+        );
+        assert!(!Move::new(Square::G2, Square::H3, MoveFlag::EnPassantCapture)
+            .is_pseudo_legal(&position));
+
+        // Castling through an attacked square or with the wrong piece.
+        let position = Position::from_fen("r3k2r/8/8/8/8/8/6r1/R3K2R w KQkq - 0 1").unwrap();
+        assert!(
+            !Move::new(Square::E1, Square::G1, MoveFlag::KingsideCastle).is_pseudo_legal(&position)
+        );
+        assert!(!Move::new(Square::A1, Square::C1, MoveFlag::QueensideCastle)
+            .is_pseudo_legal(&position));
+        assert!(
+            Move::new(Square::E1, Square::C1, MoveFlag::QueensideCastle).is_pseudo_legal(&position)
+        );
+    }
+
+    #[test]
+    fn legal_rejects_pinned_piece() {
+        let position = Position::from_fen("4k3/8/8/8/8/4r3/4N3/4K3 w - - 0 1").unwrap();
+        let mov = Move::new(Square::E2, Square::C3, MoveFlag::Quiet);
+        assert!(mov.is_pseudo_legal(&position));
+        assert!(!mov.is_legal(&position));
+    }
+
+    #[test]
+    fn unmake_restores_position() {
+        let fens = [
+            KIWIPETE_WHITE_FEN,
+            KIWIPETE_BLACK_FEN,
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "1k6/3P4/8/8/8/8/8/4K3 w - - 3 40",
+            "1rqkbnrb/pp1ppp1p/1n4p1/B1p5/3PP3/4N3/PPP2PPP/NRQK2RB w GBgb - 0 9",
+        ];
+
+        for fen in fens {
+            let mut position = Position::from_fen(fen).unwrap();
+            let original = position;
+
+            for mov in position.moves(gen::MoveStage::All) {
+                let undo = position.make_move_in_place(mov);
+                assert_eq!(position.to_fen(), make_move(&original, mov).to_fen());
+                position.unmake_move(mov, undo);
+
+                assert_eq!(position.to_fen(), original.to_fen());
+                assert_eq!(position.zobrist_hash(), original.zobrist_hash());
+            }
+        }
+    }
 }
@@ -0,0 +1,166 @@
+use super::{gen::MoveStage, Move, MoveFlag};
+use crate::position::{board::Piece, Position};
+
+fn piece_letter(piece: Piece) -> Option<char> {
+    match piece {
+        Piece::Pawn => None,
+        Piece::Knight => Some('N'),
+        Piece::Bishop => Some('B'),
+        Piece::Rook => Some('R'),
+        Piece::Queen => Some('Q'),
+        Piece::King => Some('K'),
+    }
+}
+
+fn file_char(file: u8) -> char {
+    (b'a' + file) as char
+}
+
+fn rank_char(rank: u8) -> char {
+    (b'1' + rank) as char
+}
+
+/// Strips annotations and notation variants, so that SAN strings can be compared directly.
+fn normalize_san(san: &str) -> String {
+    san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O").replace('=', "")
+}
+
+impl Move {
+    /// Writes the move in standard algebraic notation. The move must be legal in the position.
+    pub fn to_san(&self, position: &Position) -> String {
+        let mut san = String::new();
+
+        match self.flag() {
+            MoveFlag::KingsideCastle => san.push_str("O-O"),
+            MoveFlag::QueensideCastle => san.push_str("O-O-O"),
+            flag => {
+                let piece = position.board.piece_at(self.from()).unwrap();
+
+                if let Some(letter) = piece_letter(piece) {
+                    san.push(letter);
+
+                    // Disambiguate from other pieces of the same kind reaching the same square.
+                    let rivals = position
+                        .moves(MoveStage::All)
+                        .into_iter()
+                        .filter(|mov| {
+                            mov.to() == self.to()
+                                && mov.from() != self.from()
+                                && position.board.piece_at(mov.from()) == Some(piece)
+                        })
+                        .collect::<Vec<_>>();
+                    if !rivals.is_empty() {
+                        if rivals.iter().all(|mov| mov.from().file() != self.from().file()) {
+                            san.push(file_char(self.from().file()));
+                        } else if rivals.iter().all(|mov| mov.from().rank() != self.from().rank()) {
+                            san.push(rank_char(self.from().rank()));
+                        } else {
+                            san.push(file_char(self.from().file()));
+                            san.push(rank_char(self.from().rank()));
+                        }
+                    }
+                } else if flag.is_capture() {
+                    san.push(file_char(self.from().file()));
+                }
+
+                if flag.is_capture() {
+                    san.push('x');
+                }
+
+                san.push_str(&self.to().to_string());
+
+                if let Some(promotion_piece) = flag.promotion_piece() {
+                    san.push('=');
+                    san.push(piece_letter(promotion_piece).unwrap());
+                }
+            }
+        }
+
+        let new_position = position.make_move(*self);
+        if new_position.is_check() {
+            san.push(if new_position.moves(MoveStage::All).is_empty() { '#' } else { '+' });
+        }
+
+        san
+    }
+}
+
+/// Finds the legal move written in standard algebraic notation.
+pub fn parse_san(position: &Position, san: &str) -> Option<Move> {
+    let san = normalize_san(san.trim());
+    position
+        .moves(MoveStage::All)
+        .into_iter()
+        .find(|mov| normalize_san(&mov.to_san(position)) == san)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_san;
+    use crate::{
+        moves::{gen::MoveStage, Move, MoveFlag},
+        position::{
+            fen::{FromFen, ToFen, KIWIPETE_WHITE_FEN, START_FEN},
+            square::Square,
+            Position,
+        },
+    };
+
+    #[test]
+    fn san_basic_moves() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+
+        let san = |from, to, flag| Move::new(from, to, flag).to_san(&position);
+        assert_eq!(san(Square::E1, Square::G1, MoveFlag::KingsideCastle), "O-O");
+        assert_eq!(san(Square::E1, Square::C1, MoveFlag::QueensideCastle), "O-O-O");
+        assert_eq!(san(Square::D5, Square::E6, MoveFlag::Capture), "dxe6");
+        assert_eq!(san(Square::E5, Square::F7, MoveFlag::Capture), "Nxf7");
+        assert_eq!(san(Square::F3, Square::F6, MoveFlag::Capture), "Qxf6");
+        assert_eq!(san(Square::A2, Square::A4, MoveFlag::DoublePawnPush), "a4");
+    }
+
+    #[test]
+    fn san_disambiguation_and_suffixes() {
+        let position = Position::from_fen("7k/8/8/8/8/1K6/8/R3R3 w - - 0 1").unwrap();
+        assert_eq!(Move::new(Square::A1, Square::C1, MoveFlag::Quiet).to_san(&position), "Rac1");
+
+        let position = Position::from_fen("7k/8/8/8/R7/1K6/8/R7 w - - 0 1").unwrap();
+        assert_eq!(Move::new(Square::A1, Square::A2, MoveFlag::Quiet).to_san(&position), "R1a2");
+
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        assert_eq!(Move::new(Square::A1, Square::A8, MoveFlag::Quiet).to_san(&position), "Ra8#");
+
+        let position = Position::from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            Move::new(Square::E7, Square::E8, MoveFlag::QueenPromotion).to_san(&position),
+            "e8=Q+"
+        );
+    }
+
+    #[test]
+    fn san_round_trip() {
+        for fen in [START_FEN, KIWIPETE_WHITE_FEN] {
+            let position = Position::from_fen(fen).unwrap();
+            for mov in position.moves(MoveStage::All) {
+                assert_eq!(parse_san(&position, &mov.to_san(&position)), Some(mov));
+            }
+        }
+    }
+
+    #[test]
+    fn make_moves_san() {
+        let position = Position::from_fen(START_FEN).unwrap();
+        let position = ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "0-0"]
+            .iter()
+            .try_fold(position, |position, san| position.make_move_san(san))
+            .unwrap();
+        assert_eq!(
+            position.to_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+
+        assert!(position.make_move_san("Nxe4!?").is_some());
+        assert!(position.make_move_san("Kf7").is_none());
+        assert!(position.make_move_san("e9").is_none());
+    }
+}
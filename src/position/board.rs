@@ -1,18 +1,28 @@
-use super::{bitboard::Bitboard, CastlingRights, Color, Square};
-use ctor::ctor;
+use super::{
+    bitboard::Bitboard,
+    render::{BoardRenderer, PieceSet},
+    CastlingRights, Color, Square,
+};
 use primitive_enum::primitive_enum;
-use rand::{rngs::StdRng, Rng, SeedableRng};
 
 pub type ZobristHash = u64;
 
 // 2 colors, 6 pieces, 64 squares + 2 colors + 4 castling rights + 64 squares
 const ZOBRIST_NUMBERS_SIZE: usize = 2 * 6 * 64 + 2 + 4 + 64;
 
-#[ctor]
 static ZOBRIST_NUMBERS: [ZobristHash; ZOBRIST_NUMBERS_SIZE] = {
-    let mut rng = StdRng::seed_from_u64(0);
+    // SplitMix64, which is simple enough to run at compile time.
+    let mut state: u64 = 0;
     let mut numbers = [0; ZOBRIST_NUMBERS_SIZE];
-    numbers.iter_mut().take(ZOBRIST_NUMBERS_SIZE).for_each(|n| *n = rng.gen());
+    let mut i = 0;
+    while i < ZOBRIST_NUMBERS_SIZE {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        numbers[i] = z ^ (z >> 31);
+        i += 1;
+    }
     numbers
 };
 
@@ -121,31 +131,7 @@ impl Board {
 
 impl std::fmt::Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut board = String::new();
-        for rank in (0..8).rev() {
-            for file in 0..8 {
-                let square = Square::from(rank * 8 + file).unwrap();
-                let piece = self.piece_color_at(square);
-                board.push(match piece {
-                    Some((Piece::King, Color::White)) => '♔',
-                    Some((Piece::Queen, Color::White)) => '♕',
-                    Some((Piece::Rook, Color::White)) => '♖',
-                    Some((Piece::Bishop, Color::White)) => '♗',
-                    Some((Piece::Knight, Color::White)) => '♘',
-                    Some((Piece::Pawn, Color::White)) => '♙',
-                    Some((Piece::King, Color::Black)) => '♚',
-                    Some((Piece::Queen, Color::Black)) => '♛',
-                    Some((Piece::Rook, Color::Black)) => '♜',
-                    Some((Piece::Bishop, Color::Black)) => '♝',
-                    Some((Piece::Knight, Color::Black)) => '♞',
-                    Some((Piece::Pawn, Color::Black)) => '♟',
-                    None => '-',
-                });
-                board.push(' ');
-            }
-            board.push('\n');
-        }
-        write!(f, "{}", board)
+        write!(f, "{}", PieceSet::default().render(self))
     }
 }
 
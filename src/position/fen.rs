@@ -1,5 +1,6 @@
 use super::{
     board::{Board, Piece},
+    render::fen_char,
     CastlingRights, Color, Position, Square,
 };
 use std::str::FromStr;
@@ -20,6 +21,37 @@ pub trait ToFen {
     fn to_fen(&self) -> String;
 }
 
+/// The EPD form of a position is its FEN without the halfmove clock and fullmove number.
+pub trait ToEpd {
+    fn to_epd(&self) -> String;
+}
+
+/// Reads the move counters that may follow the first four FEN fields.
+/// They can be absent, as in EPD, in which case EPD "hmvc" and "fmvn" operations are honored.
+fn parse_move_counters<'a>(fields: impl Iterator<Item = &'a str>) -> Option<(u8, u16)> {
+    let mut halfmove_clock = 0;
+    let mut fullmove_number = 1;
+
+    let mut fields = fields.peekable();
+    if let Some(field) = fields.next_if(|field| field.parse::<u8>().is_ok()) {
+        halfmove_clock = field.parse().ok()?;
+        if let Some(field) = fields.next_if(|field| field.parse::<u16>().is_ok()) {
+            fullmove_number = field.parse().ok()?;
+        }
+    } else {
+        while let Some(field) = fields.next() {
+            let value = fields.peek().map(|value| value.trim_end_matches(';'));
+            match (field, value) {
+                ("hmvc", Some(value)) => halfmove_clock = value.parse().ok()?,
+                ("fmvn", Some(value)) => fullmove_number = value.parse().ok()?,
+                _ => {}
+            }
+        }
+    }
+
+    Some((halfmove_clock, fullmove_number.max(1)))
+}
+
 fn chess960_compliant(castling_rights: CastlingRights, board: Board) -> bool {
     let white_can_castle_kingside = castling_rights.contains(CastlingRights::WHITE_KINGSIDE);
     let white_can_castle_queenside = castling_rights.contains(CastlingRights::WHITE_QUEENSIDE);
@@ -176,9 +208,7 @@ impl FromFen for Position {
             _ => Square::from_str(en_passant_square_fen).ok(),
         };
 
-        let halfmove_clock: u8 = fen_iter.next().unwrap_or("0").parse().ok()?;
-
-        let fullmove_number: u16 = fen_iter.next().unwrap_or("1").parse().ok()?;
+        let (halfmove_clock, fullmove_number) = parse_move_counters(fen_iter)?;
 
         Some(Position {
             board,
@@ -207,18 +237,7 @@ impl ToFen for Board {
                 let square = rank * 8 + file;
 
                 let piece = match self.piece_color_at(Square::from(square).unwrap()) {
-                    Some((Piece::Pawn, Color::White)) => 'P',
-                    Some((Piece::Pawn, Color::Black)) => 'p',
-                    Some((Piece::Knight, Color::White)) => 'N',
-                    Some((Piece::Knight, Color::Black)) => 'n',
-                    Some((Piece::Bishop, Color::White)) => 'B',
-                    Some((Piece::Bishop, Color::Black)) => 'b',
-                    Some((Piece::Rook, Color::White)) => 'R',
-                    Some((Piece::Rook, Color::Black)) => 'r',
-                    Some((Piece::Queen, Color::White)) => 'Q',
-                    Some((Piece::Queen, Color::Black)) => 'q',
-                    Some((Piece::King, Color::White)) => 'K',
-                    Some((Piece::King, Color::Black)) => 'k',
+                    Some((piece, color)) => fen_char(piece, color),
                     None => ' ',
                 };
 
@@ -244,6 +263,22 @@ impl ToFen for Board {
 
 impl ToFen for Position {
     fn to_fen(&self) -> String {
+        let mut fen = self.to_epd();
+
+        fen.push(' ');
+
+        fen.push_str(&self.halfmove_clock.to_string());
+
+        fen.push(' ');
+
+        fen.push_str(&self.fullmove_number.to_string());
+
+        fen
+    }
+}
+
+impl ToEpd for Position {
+    fn to_epd(&self) -> String {
         let mut fen = String::new();
 
         fen.push_str(&self.board.to_fen());
@@ -280,14 +315,6 @@ impl ToFen for Position {
             &self.en_passant_square.map(|sq| sq.to_string()).unwrap_or_else(|| "-".to_string()),
         );
 
-        fen.push(' ');
-
-        fen.push_str(&self.halfmove_clock.to_string());
-
-        fen.push(' ');
-
-        fen.push_str(&self.fullmove_number.to_string());
-
         fen
     }
 }
@@ -411,4 +438,28 @@ mod tests {
         let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
         assert_eq!(position.to_fen(), KIWIPETE_WHITE_FEN);
     }
+
+    #[test]
+    fn parses_epd_move_counters() {
+        let position = Position::from_fen("8/8/8/8/8/8/8/K6k w - -").unwrap();
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (0, 1));
+
+        let position =
+            Position::from_fen("8/8/8/8/8/8/8/K6k b - - hmvc 12; fmvn 40; c9 \"1/2-1/2\";")
+                .unwrap();
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (12, 40));
+
+        let position = Position::from_fen("8/8/8/8/8/8/8/K6k w - - c9 \"1-0\";").unwrap();
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (0, 1));
+
+        let position = Position::from_fen("8/8/8/8/8/8/8/K6k w - - 7 0").unwrap();
+        assert_eq!((position.halfmove_clock, position.fullmove_number), (7, 1));
+    }
+
+    #[test]
+    fn to_epd_omits_move_counters() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        assert_eq!(position.to_epd(), KIWIPETE_WHITE_FEN.rsplitn(3, ' ').last().unwrap());
+        assert_eq!(Position::from_fen(&position.to_epd()).unwrap().board, position.board);
+    }
 }
@@ -3,8 +3,10 @@ use self::{
     square::Square,
 };
 use crate::moves::{
-    gen::{generate_moves, king_square_attackers, MoveStage},
-    make_move, Move,
+    gen::{generate_moves, generate_moves_with_info, king_square_attackers, CheckInfo, MoveStage},
+    make_move, make_move_in_place,
+    san::parse_san,
+    unmake_move, Move, UndoInfo,
 };
 use bitflags::bitflags;
 use primitive_enum::primitive_enum;
@@ -12,6 +14,9 @@ use primitive_enum::primitive_enum;
 pub mod bitboard;
 pub mod board;
 pub mod fen;
+pub mod opening;
+pub mod pgn;
+pub mod render;
 pub mod square;
 
 primitive_enum!(
@@ -46,6 +51,7 @@ bitflags! {
     }
 }
 
+/// A chess position. Making a move returns a new position, so positions are cheap to copy.
 #[derive(Debug, Copy, Clone)]
 pub struct Position {
     pub board: Board,
@@ -69,16 +75,39 @@ impl Position {
         make_move(self, mov)
     }
 
+    /// Makes the move without copying the position. Pass the returned undo information
+    /// to `unmake_move` to get the position back.
+    pub fn make_move_in_place(&mut self, mov: Move) -> UndoInfo {
+        make_move_in_place(self, mov)
+    }
+
+    pub fn unmake_move(&mut self, mov: Move, undo: UndoInfo) {
+        unmake_move(self, mov, undo)
+    }
+
     pub fn make_move_str(&self, mov_str: &str) -> Option<Self> {
         let moves = self.moves(MoveStage::All);
         let mov = moves.iter().find(|mov| mov.to_string() == mov_str)?;
         Some(self.make_move(*mov))
     }
 
+    pub fn make_move_san(&self, san: &str) -> Option<Self> {
+        let mov = parse_san(self, san)?;
+        Some(self.make_move(mov))
+    }
+
     pub fn moves(&self, stage: MoveStage) -> Vec<Move> {
         generate_moves(stage, self)
     }
 
+    pub fn moves_with_info(&self, stage: MoveStage, check_info: &CheckInfo) -> Vec<Move> {
+        generate_moves_with_info(stage, self, check_info)
+    }
+
+    pub fn check_info(&self) -> CheckInfo {
+        CheckInfo::new(self)
+    }
+
     pub fn is_check(&self) -> bool {
         king_square_attackers::<true>(&self.board, self.side_to_move.opposite()).is_not_empty()
     }
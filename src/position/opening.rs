@@ -0,0 +1,137 @@
+use super::{
+    board::ZobristHash,
+    pgn::{parse_pgn, split_pgn, PgnGame},
+    Color, Position,
+};
+use crate::moves::Move;
+use std::collections::HashMap;
+
+/// Results of the games that played a move.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct MoveStats {
+    pub games: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+}
+
+impl MoveStats {
+    /// Share of the points scored by the given side, from 0 to 1.
+    pub fn score(&self, color: Color) -> f64 {
+        let wins = match color {
+            Color::White => self.white_wins,
+            Color::Black => self.black_wins,
+        };
+        (wins as f64 + self.draws as f64 / 2.0) / self.games.max(1) as f64
+    }
+
+    fn add(&mut self, winner: Option<Option<Color>>) {
+        self.games += 1;
+        match winner {
+            Some(Some(Color::White)) => self.white_wins += 1,
+            Some(Some(Color::Black)) => self.black_wins += 1,
+            Some(None) => self.draws += 1,
+            // Unfinished games count as played, but not towards any result.
+            None => (),
+        }
+    }
+}
+
+/// Moves played from each position of a set of games, with their results.
+/// Positions are told apart by their hash, so transpositions share their moves.
+#[derive(Default)]
+pub struct OpeningTree {
+    moves: HashMap<ZobristHash, Vec<(Move, MoveStats)>>,
+    games: usize,
+}
+
+impl OpeningTree {
+    /// Builds the tree from the first plies of each game of a PGN text.
+    /// Games that cannot be read are skipped.
+    pub fn from_pgn(pgn: &str, max_plies: usize) -> Self {
+        let mut tree = Self::default();
+        for game in split_pgn(pgn).into_iter().filter_map(parse_pgn) {
+            tree.add_game(&game, max_plies);
+        }
+        tree
+    }
+
+    pub fn add_game(&mut self, game: &PgnGame, max_plies: usize) {
+        let winner = match game.header("Result") {
+            Some("1-0") => Some(Some(Color::White)),
+            Some("0-1") => Some(Some(Color::Black)),
+            Some("1/2-1/2") => Some(None),
+            _ => None,
+        };
+
+        for (position, mov) in game.positions.iter().zip(game.moves.iter()).take(max_plies) {
+            let moves = self.moves.entry(position.zobrist_hash()).or_default();
+            match moves.iter_mut().find(|(other, _)| other == mov) {
+                Some((_, stats)) => stats.add(winner),
+                None => {
+                    let mut stats = MoveStats::default();
+                    stats.add(winner);
+                    moves.push((*mov, stats));
+                }
+            }
+        }
+        self.games += 1;
+    }
+
+    /// Moves played from the position, the most played first.
+    pub fn moves(&self, position: &Position) -> Vec<(Move, MoveStats)> {
+        let mut moves = self.moves.get(&position.zobrist_hash()).cloned().unwrap_or_default();
+        moves.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.games));
+        moves
+    }
+
+    pub fn games(&self) -> usize {
+        self.games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpeningTree;
+    use crate::position::{
+        fen::{FromFen, START_FEN},
+        Color, Position,
+    };
+
+    #[test]
+    fn merges_transpositions() {
+        let pgn = r#"[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0
+
+[Result "1/2-1/2"]
+
+1. Nf3 Nc6 2. e4 e5 3. Bc4 1/2-1/2
+
+[Result "0-1"]
+
+1. e4 c5 0-1
+"#;
+
+        let tree = OpeningTree::from_pgn(pgn, 10);
+        assert_eq!(tree.games(), 3);
+
+        let start = Position::from_fen(START_FEN).unwrap();
+        let moves = tree.moves(&start);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].0.to_string(), "e2e4");
+        assert_eq!(moves[0].1.games, 2);
+        assert_eq!(moves[0].1.score(Color::White), 0.5);
+
+        // Both move orders reach this position.
+        let position =
+            Position::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let moves = tree.moves(&position);
+        assert_eq!(moves.len(), 2);
+        assert!(moves.iter().all(|(_, stats)| stats.games == 1));
+
+        let tree = OpeningTree::from_pgn(pgn, 4);
+        assert!(tree.moves(&position).is_empty());
+    }
+}
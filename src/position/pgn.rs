@@ -0,0 +1,269 @@
+use super::{
+    board::ZobristHash,
+    fen::{FromFen, START_FEN},
+    Position,
+};
+use crate::moves::{san::parse_san, Move};
+
+pub struct PgnGame {
+    pub headers: Vec<(String, String)>,
+    pub moves: Vec<Move>,
+    /// Positions of the game, starting with the initial one.
+    pub positions: Vec<Position>,
+}
+
+impl PgnGame {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Hash of the position after the given plies, or of the last one in shorter games.
+    /// Games with the same key transpose into each other.
+    pub fn transposition_key(&self, plies: usize) -> ZobristHash {
+        self.positions[plies.min(self.positions.len() - 1)].zobrist_hash()
+    }
+}
+
+/// Conditions on the games of a PGN file. Unset conditions accept every game.
+#[derive(Default)]
+pub struct PgnFilter {
+    /// Minimum rating of both players. Games without ratings are rejected.
+    pub min_elo: Option<u16>,
+    pub result: Option<String>,
+    pub time_control: Option<String>,
+    pub min_plies: Option<usize>,
+    pub max_plies: Option<usize>,
+}
+
+impl PgnFilter {
+    pub fn accepts(&self, game: &PgnGame) -> bool {
+        let rating = |name| game.header(name).and_then(|value| value.parse::<u16>().ok());
+        let plies = game.moves.len();
+
+        self.min_elo.is_none_or(|min_elo| {
+            rating("WhiteElo").is_some_and(|elo| elo >= min_elo)
+                && rating("BlackElo").is_some_and(|elo| elo >= min_elo)
+        }) && self.result.as_ref().is_none_or(|result| game.header("Result") == Some(result))
+            && self
+                .time_control
+                .as_ref()
+                .is_none_or(|time_control| game.header("TimeControl") == Some(time_control))
+            && self.min_plies.is_none_or(|min_plies| plies >= min_plies)
+            && self.max_plies.is_none_or(|max_plies| plies <= max_plies)
+    }
+}
+
+/// Splits a PGN text into the text of each of its games.
+pub fn split_pgn(pgn: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut in_movetext = false;
+
+    for line in pgn.split_inclusive('\n') {
+        if line.trim_start().starts_with('[') {
+            if in_movetext {
+                games.push(pgn[start..offset].trim());
+                start = offset;
+                in_movetext = false;
+            }
+        } else if !line.trim().is_empty() {
+            in_movetext = true;
+        }
+        offset += line.len();
+    }
+
+    let last_game = pgn[start..].trim();
+    if !last_game.is_empty() {
+        games.push(last_game);
+    }
+
+    games
+}
+
+fn parse_header(line: &str) -> Option<(String, String)> {
+    let line = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = line.split_once(' ')?;
+    Some((name.to_string(), value.trim().trim_matches('"').to_string()))
+}
+
+/// Splits the movetext into move tokens, dropping comments, variations,
+/// numeric annotation glyphs, move numbers and the game result.
+fn movetext_tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut variation_depth = 0;
+    let mut chars = movetext.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                chars.by_ref().find(|c| *c == '}');
+            }
+            ';' => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            c if c.is_whitespace() => {}
+            c if variation_depth == 0 => {
+                current.push(c);
+                continue;
+            }
+            _ => {}
+        }
+
+        if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+        .into_iter()
+        .map(|token| match token.rfind('.') {
+            Some(index) if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                token[index + 1..].to_string()
+            }
+            _ => token,
+        })
+        .filter(|token| {
+            !token.is_empty()
+                && !token.starts_with('$')
+                && !["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str())
+        })
+        .collect()
+}
+
+/// Reads the first game of a PGN text, replaying its mainline from the initial position.
+pub fn parse_pgn(pgn: &str) -> Option<PgnGame> {
+    let mut headers = Vec::new();
+    let mut movetext = String::new();
+
+    for line in pgn.lines() {
+        if line.trim_start().starts_with('[') {
+            if !movetext.trim().is_empty() {
+                // The headers of the next game.
+                break;
+            }
+            headers.push(parse_header(line)?);
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+
+    let start_fen = headers
+        .iter()
+        .find(|(name, _)| name == "FEN")
+        .map_or(START_FEN, |(_, value)| value.as_str());
+    let mut position = Position::from_fen(start_fen)?;
+    let mut moves = Vec::new();
+    let mut positions = vec![position];
+
+    for token in movetext_tokens(&movetext) {
+        let mov = parse_san(&position, &token)?;
+        position = position.make_move(mov);
+        moves.push(mov);
+        positions.push(position);
+    }
+
+    Some(PgnGame { headers, moves, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pgn, split_pgn, PgnFilter};
+    use crate::position::fen::ToFen;
+
+    #[test]
+    fn parses_annotated_game() {
+        let pgn = r#"[Event "Casual game"]
+[White "Camel"]
+[Black "Opponent"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 $1 Nc6 (2... d6 3. d4 (3. Bc4) exd4) 3. Bb5 a6?!
+; the Morphy defence
+4.Ba4 Nf6 5. 0-0 1-0
+
+[Event "Next game"]
+
+1. d4 d5 *
+"#;
+
+        let game = parse_pgn(pgn).unwrap();
+        assert_eq!(game.headers.len(), 4);
+        assert_eq!(game.headers[1], ("White".to_string(), "Camel".to_string()));
+        assert_eq!(game.moves.len(), 9);
+        assert_eq!(game.positions.len(), 10);
+        assert_eq!(
+            game.positions.last().unwrap().to_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+    }
+
+    #[test]
+    fn parses_game_from_fen() {
+        let pgn = r#"[FEN "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1"]
+[SetUp "1"]
+
+1. Ra8# 1-0"#;
+
+        let game = parse_pgn(pgn).unwrap();
+        assert_eq!(game.moves.len(), 1);
+        assert!(game.positions[1].is_check());
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        assert!(parse_pgn("1. e4 e5 2. Ke3 *").is_none());
+    }
+
+    #[test]
+    fn splits_and_filters_games() {
+        let pgn = r#"[White "A"]
+[WhiteElo "2400"]
+[BlackElo "2300"]
+[Result "1-0"]
+
+1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0
+
+[White "B"]
+[Result "1/2-1/2"]
+
+1. d4 d5 1/2-1/2
+"#;
+
+        let games =
+            split_pgn(pgn).into_iter().map(|game| parse_pgn(game).unwrap()).collect::<Vec<_>>();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].header("White"), Some("B"));
+        assert_eq!(games[1].moves.len(), 2);
+
+        let accepted = |filter: PgnFilter| games.iter().filter(|game| filter.accepts(game)).count();
+        assert_eq!(accepted(PgnFilter::default()), 2);
+        assert_eq!(accepted(PgnFilter { min_elo: Some(2300), ..Default::default() }), 1);
+        assert_eq!(accepted(PgnFilter { min_elo: Some(2350), ..Default::default() }), 0);
+        assert_eq!(
+            accepted(PgnFilter { result: Some("1/2-1/2".to_string()), ..Default::default() }),
+            1
+        );
+        assert_eq!(accepted(PgnFilter { min_plies: Some(3), ..Default::default() }), 1);
+        assert_eq!(accepted(PgnFilter { max_plies: Some(2), ..Default::default() }), 1);
+    }
+
+    #[test]
+    fn detects_transpositions() {
+        let first = parse_pgn("1. e4 e5 2. Nf3 Nc6 3. Bb5 *").unwrap();
+        let second = parse_pgn("1. Nf3 Nc6 2. e4 e5 3. Bc4 *").unwrap();
+        let short = parse_pgn("1. e4 e5 2. Nf3 Nc6 *").unwrap();
+
+        assert_ne!(first.transposition_key(2), second.transposition_key(2));
+        assert_eq!(first.transposition_key(4), second.transposition_key(4));
+        assert_ne!(first.transposition_key(5), second.transposition_key(5));
+        assert_eq!(short.transposition_key(10), first.transposition_key(4));
+    }
+}
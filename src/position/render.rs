@@ -0,0 +1,165 @@
+use super::{
+    board::{Board, Piece},
+    square::Square,
+    Color,
+};
+
+/// Turns boards into text, one rank per line from the eighth down.
+pub trait BoardRenderer {
+    fn piece(&self, piece: Piece, color: Color) -> String;
+
+    fn empty_square(&self, square: Square) -> String;
+
+    fn render(&self, board: &Board) -> String {
+        let mut text = String::new();
+        for rank in (0..8).rev() {
+            for file in 0..8 {
+                let square = Square::from(rank * 8 + file).unwrap();
+                match board.piece_color_at(square) {
+                    Some((piece, color)) => text.push_str(&self.piece(piece, color)),
+                    None => text.push_str(&self.empty_square(square)),
+                }
+                text.push(' ');
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// The glyphs pieces are drawn with.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum PieceSet {
+    /// Chess glyphs. The filled ones stand for white, since they read as the lighter pieces
+    /// on the dark backgrounds terminals usually have.
+    #[default]
+    Unicode,
+    /// Plain letters prefixed by the color, for terminals without Unicode or where case is hard
+    /// to tell apart.
+    Ascii,
+    /// The letters used in FEN, uppercase for white.
+    Fen,
+}
+
+impl PieceSet {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "unicode" => Some(PieceSet::Unicode),
+            "ascii" => Some(PieceSet::Ascii),
+            "fen" => Some(PieceSet::Fen),
+            _ => None,
+        }
+    }
+}
+
+/// The FEN letter of the piece, uppercase for white.
+pub fn fen_char(piece: Piece, color: Color) -> char {
+    let c = match piece {
+        Piece::Pawn => 'p',
+        Piece::Knight => 'n',
+        Piece::Bishop => 'b',
+        Piece::Rook => 'r',
+        Piece::Queen => 'q',
+        Piece::King => 'k',
+    };
+    match color {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
+    }
+}
+
+fn unicode_char(piece: Piece, color: Color) -> char {
+    match (piece, color) {
+        (Piece::King, Color::White) => '♚',
+        (Piece::Queen, Color::White) => '♛',
+        (Piece::Rook, Color::White) => '♜',
+        (Piece::Bishop, Color::White) => '♝',
+        (Piece::Knight, Color::White) => '♞',
+        (Piece::Pawn, Color::White) => '♟',
+        (Piece::King, Color::Black) => '♔',
+        (Piece::Queen, Color::Black) => '♕',
+        (Piece::Rook, Color::Black) => '♖',
+        (Piece::Bishop, Color::Black) => '♗',
+        (Piece::Knight, Color::Black) => '♘',
+        (Piece::Pawn, Color::Black) => '♙',
+    }
+}
+
+impl BoardRenderer for PieceSet {
+    fn piece(&self, piece: Piece, color: Color) -> String {
+        match self {
+            PieceSet::Unicode => unicode_char(piece, color).to_string(),
+            PieceSet::Ascii => {
+                let prefix = match color {
+                    Color::White => 'w',
+                    Color::Black => 'b',
+                };
+                format!("{}{}", prefix, fen_char(piece, Color::White))
+            }
+            PieceSet::Fen => fen_char(piece, color).to_string(),
+        }
+    }
+
+    fn empty_square(&self, _: Square) -> String {
+        match self {
+            PieceSet::Ascii => "--".to_string(),
+            PieceSet::Unicode | PieceSet::Fen => "-".to_string(),
+        }
+    }
+}
+
+/// Draws the pieces of a set in the colors of their side, over shaded squares, with ANSI codes.
+#[cfg(feature = "terminal-color")]
+pub struct Colored<R: BoardRenderer>(pub R);
+
+#[cfg(feature = "terminal-color")]
+impl<R: BoardRenderer> BoardRenderer for Colored<R> {
+    fn piece(&self, piece: Piece, color: Color) -> String {
+        let code = match color {
+            Color::White => "1;97",
+            Color::Black => "1;31",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, self.0.piece(piece, color))
+    }
+
+    fn empty_square(&self, square: Square) -> String {
+        let code = match square.color() {
+            Color::White => "37",
+            Color::Black => "90",
+        };
+        format!("\x1b[{}m{}\x1b[0m", code, self.0.empty_square(square))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoardRenderer, PieceSet};
+    use crate::position::{
+        fen::{FromFen, START_FEN},
+        Position,
+    };
+
+    #[test]
+    fn piece_sets() {
+        let position = Position::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let last_ranks = |set: PieceSet| {
+            set.render(&position.board).lines().skip(6).collect::<Vec<_>>().join("\n")
+        };
+
+        assert_eq!(last_ranks(PieceSet::Fen), "- - - - P - - - \n- - - - K - - - ");
+        assert_eq!(last_ranks(PieceSet::Unicode), "- - - - ♟ - - - \n- - - - ♚ - - - ");
+        assert_eq!(
+            last_ranks(PieceSet::Ascii),
+            "-- -- -- -- wP -- -- -- \n-- -- -- -- wK -- -- -- "
+        );
+        assert!(PieceSet::Ascii.render(&position.board).starts_with("-- -- -- -- bK"));
+    }
+
+    #[test]
+    fn fen_set_matches_fen() {
+        let position = Position::from_fen(START_FEN).unwrap();
+        let ranks = PieceSet::Fen.render(&position.board).replace(' ', "");
+        assert!(ranks.starts_with("rnbqkbnr\npppppppp\n--------"));
+        assert!(ranks.ends_with("PPPPPPPP\nRNBQKBNR\n"));
+    }
+}
@@ -1,26 +1,81 @@
-use super::history::HistoryEntry;
+use super::{
+    history::HistoryEntry, params::SearchParams, strength::StrengthLimit, tree::TreeDump, Depth,
+};
+use crate::{
+    evaluation::{params::EvalParams, ValueScore},
+    moves::Move,
+};
+use rand::{rngs::StdRng, SeedableRng};
 use std::{
+    cell::Cell,
     sync::{
-        atomic::{AtomicBool, AtomicU16, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
     time::{Duration, Instant},
 };
 
+const NODE_COUNT_BATCH: usize = 1024;
+/// Share of `max_nodes` after which no new iteration is started.
+const SOFT_NODES_DIVISOR: usize = 2;
+
+thread_local! {
+    static UNCOUNTED_NODES: Cell<usize> = const { Cell::new(0) };
+}
+
 #[derive(Copy, Clone)]
 pub struct TimeConstraint {
     pub initial_instant: Instant,
+    /// The search is aborted once this is over, even in the middle of an iteration.
     pub move_time: Duration,
+    /// No new iteration is started once this is over, after scaling it by how settled the
+    /// best move looks. Without it, the search may take all of the move time.
+    pub soft_time: Option<Duration>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct SearchConstraint {
     pub time_constraint: Option<TimeConstraint>,
     pub global_stop: Arc<AtomicBool>,
     pub threads_stop: Arc<AtomicBool>,
     pub ponder_mode: Arc<AtomicBool>,
+    /// The search only reports its best move once stopped from outside, even if there is
+    /// nothing left to search before that.
+    pub infinite: bool,
     pub number_threads: Arc<AtomicU16>,
     pub game_history: Vec<HistoryEntry>,
+    pub variety_margin: Option<ValueScore>,
+    pub tree_dump: Option<Arc<Mutex<TreeDump>>>,
+    pub time_credit_millis: Arc<AtomicU64>,
+    pub contempt: ValueScore,
+    pub multi_pv: u8,
+    pub excluded_root_moves: Vec<Move>,
+    /// Deepest ply reached by any thread in the current search.
+    pub seldepth: Arc<AtomicU8>,
+    /// Nodes searched by all threads in the current iteration.
+    pub nodes: Arc<AtomicUsize>,
+    /// Share of its nodes, in thousandths, that the main thread spent on its best root move
+    /// in the last iteration.
+    pub best_move_effort: Arc<AtomicU16>,
+    /// Seed of the random choices of the search, so that runs can be reproduced.
+    pub seed: Option<u64>,
+    /// The search is aborted once all threads together searched this many nodes. No new
+    /// iteration is started past a share of them, since it would seldom finish.
+    pub max_nodes: Option<usize>,
+    /// Nodes searched by all threads since the search started, for progress reports and
+    /// `max_nodes`. Threads add theirs in batches, unless `max_nodes` is set.
+    pub searched_nodes: Arc<AtomicUsize>,
+    /// Root move the main thread is searching, with its number in the move order.
+    pub root_move: Arc<Mutex<Option<(Move, usize)>>>,
+    /// Moves within which a mate for the side to move is looked for. The search stops once it
+    /// proves one, and only reports iterations with mate scores.
+    pub mate_limit: Option<u8>,
+    /// Weaker play, with fewer nodes and a randomly picked root move.
+    pub strength: Option<StrengthLimit>,
+    /// Parameters of the static evaluation.
+    pub eval_params: Arc<EvalParams>,
+    /// Parameters of the search itself.
+    pub search_params: SearchParams,
 }
 
 impl SearchConstraint {
@@ -29,28 +84,111 @@ impl SearchConstraint {
             return true;
         }
 
+        if self.max_nodes.is_some_and(|max| self.searched_nodes.load(Ordering::Relaxed) >= max) {
+            return true;
+        }
+
         if self.ponder_mode.load(Ordering::Acquire) {
             return false;
         }
 
         if let Some(time_constraint) = &self.time_constraint {
             let elapsed = time_constraint.initial_instant.elapsed();
-            return elapsed >= time_constraint.move_time;
+            return elapsed >= time_constraint.move_time + self.time_credit();
         }
 
         false
     }
 
+    /// Whether the soft time limit, scaled by the given factor, is over, so that no new
+    /// iteration should be started. It never goes beyond the move time.
+    pub fn soft_time_over(&self, scale: f64) -> bool {
+        if self.ponder_mode.load(Ordering::Acquire) {
+            return false;
+        }
+
+        self.time_constraint.as_ref().is_some_and(|time_constraint| {
+            let soft_time =
+                time_constraint.soft_time.map_or(time_constraint.move_time, |soft_time| {
+                    soft_time.mul_f64(scale).min(time_constraint.move_time)
+                });
+            time_constraint.initial_instant.elapsed() >= soft_time + self.time_credit()
+        })
+    }
+
+    /// Whether enough nodes were searched that a new iteration would likely be aborted
+    /// by `max_nodes` before it finishes.
+    pub fn soft_nodes_over(&self) -> bool {
+        self.max_nodes.is_some_and(|max| {
+            self.searched_nodes.load(Ordering::Relaxed) >= max / SOFT_NODES_DIVISOR
+        })
+    }
+
     pub fn pondering(&self) -> bool {
         self.ponder_mode.load(Ordering::Acquire)
     }
 
     pub fn remaining_time(&self) -> Option<Duration> {
         self.time_constraint.as_ref().map(|time_constraint| {
-            time_constraint.move_time.saturating_sub(time_constraint.initial_instant.elapsed())
+            (time_constraint.move_time + self.time_credit())
+                .saturating_sub(time_constraint.initial_instant.elapsed())
         })
     }
 
+    fn time_credit(&self) -> Duration {
+        Duration::from_millis(self.time_credit_millis.load(Ordering::Relaxed))
+    }
+
+    /// Score of a draw for the side to move at the given ply.
+    /// A positive contempt makes the side to move at the root avoid draws.
+    pub fn draw_score(&self, ply: Depth) -> ValueScore {
+        if ply.is_multiple_of(2) {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// Counts a node in the shared counter. Writing to it for every node is not free, so nodes
+    /// are added in batches, unless they must be exact for the node limit.
+    pub fn note_node(&self) {
+        let batch = if self.max_nodes.is_some() { 1 } else { NODE_COUNT_BATCH };
+        UNCOUNTED_NODES.with(|uncounted| {
+            let nodes = uncounted.get() + 1;
+            if nodes >= batch {
+                self.searched_nodes.fetch_add(nodes, Ordering::Relaxed);
+                uncounted.set(0);
+            } else {
+                uncounted.set(nodes);
+            }
+        });
+    }
+
+    /// Adds the nodes of this thread that are still waiting for a full batch to the shared
+    /// counter, so that they are neither missing from this search nor added to the next one.
+    pub fn flush_nodes(&self) {
+        let nodes = UNCOUNTED_NODES.with(|uncounted| uncounted.replace(0));
+        if nodes > 0 {
+            self.searched_nodes.fetch_add(nodes, Ordering::Relaxed);
+        }
+    }
+
+    pub fn note_ply_reached(&self, ply: Depth) {
+        // Most nodes are not the deepest, so avoid writing to the shared counter for them.
+        if ply > self.seldepth.load(Ordering::Relaxed) {
+            self.seldepth.fetch_max(ply, Ordering::Relaxed);
+        }
+    }
+
+    /// Random generator for one use of randomness in the search, told apart by the stream.
+    /// Without a seed, it is seeded from the operating system.
+    pub fn rng(&self, stream: u64) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ stream),
+            None => StdRng::from_entropy(),
+        }
+    }
+
     pub fn signal_root_finished(&self) {
         self.threads_stop.store(true, Ordering::Release);
     }
@@ -58,8 +196,9 @@ impl SearchConstraint {
 
 #[cfg(test)]
 mod tests {
-    use super::SearchConstraint;
+    use super::{SearchConstraint, NODE_COUNT_BATCH};
     use crate::search::constraint::TimeConstraint;
+    use rand::Rng;
     use std::{
         sync::{
             atomic::{AtomicBool, AtomicU16, Ordering},
@@ -75,12 +214,14 @@ mod tests {
             time_constraint: Some(TimeConstraint {
                 initial_instant: Instant::now(),
                 move_time: Duration::from_millis(100),
+                soft_time: None,
             }),
             global_stop: Arc::new(AtomicBool::new(false)),
             threads_stop: Arc::new(AtomicBool::new(false)),
             ponder_mode: Arc::new(AtomicBool::new(false)),
             number_threads: Arc::new(AtomicU16::new(1)),
             game_history: vec![],
+            ..Default::default()
         };
 
         thread::sleep(Duration::from_millis(90));
@@ -101,12 +242,14 @@ mod tests {
             time_constraint: Some(TimeConstraint {
                 initial_instant: Instant::now(),
                 move_time: Duration::from_millis(100),
+                soft_time: None,
             }),
             global_stop: stop_now.clone(),
             threads_stop: Arc::new(AtomicBool::new(false)),
             ponder_mode: Arc::new(AtomicBool::new(false)),
             number_threads: Arc::new(AtomicU16::new(1)),
             game_history: vec![],
+            ..Default::default()
         };
 
         assert!(!constraint.should_stop_search());
@@ -116,4 +259,87 @@ mod tests {
         assert!(constraint.should_stop_search());
         assert!(constraint.remaining_time().unwrap() > Duration::from_millis(90));
     }
+
+    #[test]
+    fn stop_search_time_credit() {
+        let constraint = SearchConstraint {
+            time_constraint: Some(TimeConstraint {
+                initial_instant: Instant::now(),
+                move_time: Duration::from_millis(50),
+                soft_time: None,
+            }),
+            ..Default::default()
+        };
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(constraint.should_stop_search());
+
+        constraint.time_credit_millis.store(100, Ordering::Relaxed);
+        assert!(!constraint.should_stop_search());
+        assert!(constraint.remaining_time().unwrap() > Duration::from_millis(80));
+    }
+
+    #[test]
+    fn soft_time_scaling() {
+        let constraint = SearchConstraint {
+            time_constraint: Some(TimeConstraint {
+                initial_instant: Instant::now(),
+                move_time: Duration::from_millis(200),
+                soft_time: Some(Duration::from_millis(50)),
+            }),
+            ..Default::default()
+        };
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(constraint.soft_time_over(1.0));
+        assert!(!constraint.soft_time_over(2.0));
+        assert!(!constraint.should_stop_search());
+
+        // The scaled limit is capped by the move time.
+        assert!(!constraint.soft_time_over(10.0));
+        thread::sleep(Duration::from_millis(150));
+        assert!(constraint.soft_time_over(10.0));
+        assert!(constraint.should_stop_search());
+    }
+
+    #[test]
+    fn stop_search_max_nodes() {
+        let constraint = SearchConstraint { max_nodes: Some(2), ..Default::default() };
+        assert!(!constraint.soft_nodes_over());
+        constraint.note_node();
+        assert!(constraint.soft_nodes_over());
+        assert!(!constraint.should_stop_search());
+        constraint.note_node();
+        assert!(constraint.should_stop_search());
+
+        // Without a limit, nodes are only added to the shared counter in batches.
+        let unlimited = SearchConstraint::default();
+        unlimited.note_node();
+        assert_eq!(unlimited.searched_nodes.load(Ordering::Relaxed), 0);
+        (1..NODE_COUNT_BATCH).for_each(|_| unlimited.note_node());
+        assert_eq!(unlimited.searched_nodes.load(Ordering::Relaxed), NODE_COUNT_BATCH);
+
+        // What is left of a batch is added once the search is over.
+        unlimited.note_node();
+        unlimited.flush_nodes();
+        assert_eq!(unlimited.searched_nodes.load(Ordering::Relaxed), NODE_COUNT_BATCH + 1);
+        unlimited.flush_nodes();
+        assert_eq!(unlimited.searched_nodes.load(Ordering::Relaxed), NODE_COUNT_BATCH + 1);
+    }
+
+    #[test]
+    fn rng_seed() {
+        let constraint = SearchConstraint { seed: Some(42), ..Default::default() };
+        let sample = |stream| constraint.rng(stream).gen_range(0..u64::MAX);
+        assert_eq!(sample(1), sample(1));
+        assert_ne!(sample(1), sample(2));
+    }
+
+    #[test]
+    fn draw_score_contempt() {
+        let constraint = SearchConstraint { contempt: 20, ..Default::default() };
+        assert_eq!(constraint.draw_score(0), -20);
+        assert_eq!(constraint.draw_score(3), 20);
+        assert_eq!(SearchConstraint::default().draw_score(1), 0);
+    }
 }
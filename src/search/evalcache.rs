@@ -0,0 +1,97 @@
+use super::constraint::SearchConstraint;
+use crate::{
+    evaluation::{params::EvalParams, Evaluable, ValueScore},
+    position::Position,
+};
+use std::{cell::RefCell, sync::Arc};
+
+const EVAL_CACHE_BITS: u32 = 16;
+const NULL_EVAL_ENTRY: u64 = u64::MAX;
+
+/// Static evaluations of recently seen positions, so that transpositions, which are common
+/// in the quiescence search, are only evaluated once. Each entry packs the hash bits that the
+/// index does not cover with the evaluation, from white's perspective.
+/// It belongs to a single thread, and is only valid for the parameters it was filled with,
+/// which it keeps alive so that they can be told apart from any others.
+struct EvalCache {
+    params: Option<Arc<EvalParams>>,
+    entries: Vec<u64>,
+}
+
+impl EvalCache {
+    fn new() -> Self {
+        Self { params: None, entries: vec![NULL_EVAL_ENTRY; 1 << EVAL_CACHE_BITS] }
+    }
+
+    fn value(&mut self, position: &Position, params: &Arc<EvalParams>) -> ValueScore {
+        if !self.params.as_ref().is_some_and(|cached| Arc::ptr_eq(cached, params)) {
+            self.entries.fill(NULL_EVAL_ENTRY);
+            self.params = Some(params.clone());
+        }
+
+        let hash = position.zobrist_hash();
+        let index = (hash & ((1 << EVAL_CACHE_BITS) - 1)) as usize;
+        let key = hash >> EVAL_CACHE_BITS;
+        let entry = self.entries[index];
+        if entry != NULL_EVAL_ENTRY && entry >> EVAL_CACHE_BITS == key {
+            return entry as u16 as ValueScore;
+        }
+
+        let value = position.value_with(params);
+        self.entries[index] = (key << EVAL_CACHE_BITS) | value as u16 as u64;
+        value
+    }
+}
+
+thread_local! {
+    static EVAL_CACHE: RefCell<EvalCache> = RefCell::new(EvalCache::new());
+}
+
+/// Static evaluation from the side to move's perspective.
+pub fn evaluate(position: &Position, constraint: &SearchConstraint) -> ValueScore {
+    let value =
+        EVAL_CACHE.with(|cache| cache.borrow_mut().value(position, &constraint.eval_params));
+    value * position.side_to_move.sign()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvalCache, NULL_EVAL_ENTRY};
+    use crate::{
+        evaluation::{params::EvalParams, Evaluable},
+        moves::gen::MoveStage,
+        position::{
+            fen::{FromFen, KIWIPETE_BLACK_FEN, KIWIPETE_WHITE_FEN},
+            Position,
+        },
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn cached_values_match_evaluation() {
+        let mut cache = EvalCache::new();
+        let params = Arc::new(EvalParams::default());
+
+        for fen in [KIWIPETE_WHITE_FEN, KIWIPETE_BLACK_FEN] {
+            let position = Position::from_fen(fen).unwrap();
+            for mov in position.moves(MoveStage::All) {
+                let position = position.make_move(mov);
+                let value = position.value_with(&params);
+                assert_eq!(cache.value(&position, &params), value);
+                assert_eq!(cache.value(&position, &params), value);
+            }
+        }
+    }
+
+    #[test]
+    fn other_params_clear_the_cache() {
+        let mut cache = EvalCache::new();
+        let params = Arc::new(EvalParams::default());
+        cache.value(&Position::from_fen(KIWIPETE_WHITE_FEN).unwrap(), &params);
+
+        let other_params = Arc::new(EvalParams::default());
+        cache.value(&Position::from_fen(KIWIPETE_BLACK_FEN).unwrap(), &other_params);
+        assert!(Arc::ptr_eq(cache.params.as_ref().unwrap(), &other_params));
+        assert_eq!(cache.entries.iter().filter(|entry| **entry != NULL_EVAL_ENTRY).count(), 1);
+    }
+}
@@ -1,4 +1,9 @@
-use crate::position::{board::ZobristHash, Position};
+use super::Depth;
+use crate::{
+    evaluation::ValueScore,
+    moves::Move,
+    position::{board::ZobristHash, Position},
+};
 
 #[derive(Debug, Copy, Clone)]
 pub struct HistoryEntry {
@@ -6,21 +11,35 @@ pub struct HistoryEntry {
     pub reversible: bool,
 }
 
-pub struct BranchHistory(pub Vec<HistoryEntry>);
+/// Positions reached from the start of the game up to the current node.
+pub struct BranchHistory {
+    pub entries: Vec<HistoryEntry>,
+    /// Repetition draws scored so far, whose scores depend on the path taken.
+    repetition_draws: usize,
+    /// Static evaluation of the nodes on the path, by ply.
+    static_evaluations: Vec<Option<ValueScore>>,
+    /// Best line found from each node on the path, as a triangular table:
+    /// the line at a ply is the best move there, followed by the line at the next ply.
+    pv_lines: Vec<Vec<Move>>,
+}
 
 impl BranchHistory {
+    pub fn new(entries: Vec<HistoryEntry>) -> Self {
+        Self { entries, repetition_draws: 0, static_evaluations: Vec::new(), pv_lines: Vec::new() }
+    }
+
     pub fn visit_position(&mut self, position: &Position, reversible: bool) {
-        self.0.push(HistoryEntry { hash: position.zobrist_hash(), reversible });
+        self.entries.push(HistoryEntry { hash: position.zobrist_hash(), reversible });
     }
 
     pub fn leave_position(&mut self) {
-        self.0.pop();
+        self.entries.pop();
     }
 
     pub fn repeated(&self, position: &Position) -> u8 {
         let mut count = 0;
         let hash = position.zobrist_hash();
-        for entry in self.0.iter().rev() {
+        for entry in self.entries.iter().rev() {
             if entry.hash == hash {
                 count += 1;
             }
@@ -30,11 +49,59 @@ impl BranchHistory {
         }
         count
     }
+
+    pub fn note_repetition_draw(&mut self) {
+        self.repetition_draws += 1;
+    }
+
+    pub fn repetition_draws(&self) -> usize {
+        self.repetition_draws
+    }
+
+    /// Records the static evaluation of the node at the ply, if it has one, and tells whether
+    /// it improved since the same side was last to move. Without both evaluations, it is
+    /// assumed to have improved.
+    pub fn note_static_evaluation(&mut self, ply: Depth, evaluation: Option<ValueScore>) -> bool {
+        let ply = ply as usize;
+        if self.static_evaluations.len() <= ply {
+            self.static_evaluations.resize(ply + 1, None);
+        }
+        self.static_evaluations[ply] = evaluation;
+
+        let previous = ply.checked_sub(2).and_then(|previous| self.static_evaluations[previous]);
+        match (evaluation, previous) {
+            (Some(evaluation), Some(previous)) => evaluation > previous,
+            _ => true,
+        }
+    }
+
+    /// Forgets the line at the ply, when its node is entered.
+    pub fn clear_pv(&mut self, ply: Depth) {
+        let ply = ply as usize;
+        if self.pv_lines.len() <= ply + 1 {
+            self.pv_lines.resize_with(ply + 2, Vec::new);
+        }
+        self.pv_lines[ply].clear();
+    }
+
+    /// Makes the move, followed by the line found after it, the line at the ply.
+    pub fn update_pv(&mut self, ply: Depth, mov: Move) {
+        let (lines, next_lines) = self.pv_lines.split_at_mut(ply as usize + 1);
+        let line = &mut lines[ply as usize];
+        line.clear();
+        line.push(mov);
+        line.extend_from_slice(&next_lines[0]);
+    }
+
+    pub fn pv(&self, ply: Depth) -> &[Move] {
+        self.pv_lines.get(ply as usize).map_or(&[], |line| line.as_slice())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        moves::Move,
         position::{
             fen::{FromFen, START_FEN},
             Position,
@@ -42,9 +109,42 @@ mod tests {
         search::history::BranchHistory,
     };
 
+    #[test]
+    fn improving_static_evaluation() {
+        let mut history = BranchHistory::new(Vec::new());
+
+        assert!(history.note_static_evaluation(0, Some(10)));
+        assert!(history.note_static_evaluation(1, Some(-20)));
+        assert!(!history.note_static_evaluation(2, Some(5)));
+        assert!(history.note_static_evaluation(3, None));
+        assert!(history.note_static_evaluation(4, Some(8)));
+        assert!(history.note_static_evaluation(5, Some(-30)));
+        assert!(history.note_static_evaluation(2, Some(15)));
+    }
+
+    #[test]
+    fn triangular_pv() {
+        let mut history = BranchHistory::new(Vec::new());
+        let [first, second, third] = [1, 2, 3].map(Move::new_raw);
+
+        history.clear_pv(0);
+        history.clear_pv(1);
+        history.clear_pv(2);
+        history.update_pv(2, third);
+        history.update_pv(1, second);
+        history.update_pv(0, first);
+        assert_eq!(history.pv(0), &[first, second, third]);
+
+        // Entering a sibling forgets the line below it, but not the lines above.
+        history.clear_pv(1);
+        assert!(history.pv(1).is_empty());
+        assert_eq!(history.pv(0), &[first, second, third]);
+        assert!(history.pv(10).is_empty());
+    }
+
     #[test]
     fn repeated_times() {
-        let mut history = BranchHistory(Vec::new());
+        let mut history = BranchHistory::new(Vec::new());
 
         let mut position = Position::from_fen(START_FEN).unwrap();
         history.visit_position(&position, true);
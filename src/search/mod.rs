@@ -1,166 +1,643 @@
-use self::{constraint::SearchConstraint, table::SearchTable};
+use self::{
+    constraint::{SearchConstraint, TimeConstraint},
+    observer::{NoObserver, SearchInfo, SearchObserver},
+    table::SearchTable,
+};
 use crate::{
-    evaluation::{moves::evaluate_move, Score, ValueScore},
+    evaluation::{moves::evaluate_move, wdl::wdl, Evaluable, Score, ValueScore},
     moves::{gen::MoveStage, Move},
-    position::Position,
+    position::{Color, Position},
 };
+use rand::{rngs::StdRng, Rng};
 use std::{
-    sync::{atomic::Ordering, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread::{self},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 pub mod constraint;
+pub mod evalcache;
 pub mod history;
+pub mod montecarlo;
 pub mod movepick;
+pub mod observer;
+pub mod params;
 pub mod pvs;
 pub mod quiesce;
 pub mod see;
+pub mod strength;
 pub mod table;
+pub mod tree;
 
 pub type Depth = u8;
 
 pub const MAX_DEPTH: Depth = 50;
 
-fn print_iter_info(
+const VARIETY_MAX_DEPTH: Depth = 6;
+const VERIFICATION_MARGIN: ValueScore = 15;
+const BLUNDER_MARGIN: ValueScore = 60;
+const BLUNDER_CHECK_MAX_DEPTH: Depth = 6;
+const BATCH_TABLE_SIZE_MB: usize = 8;
+const REFUTATION_PLIES: Depth = 12;
+const HELPER_WINDOW_JITTER: ValueScore = 8;
+const INFINITE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Weight of the recent best move changes in the soft time limit, which decay by half with
+/// each iteration.
+const BEST_MOVE_CHANGE_WEIGHT: f64 = 0.5;
+/// The soft time limit is scaled by this minus the share of nodes spent on the best move.
+const BEST_MOVE_EFFORT_BASE: f64 = 1.5;
+
+/// Factor the soft time limit is scaled by. A best move that keeps changing, or that took few
+/// of the nodes, is not settled yet, so more time is given to it.
+fn soft_time_scale(best_move_changes: f64, best_move_effort: f64) -> f64 {
+    (1.0 + BEST_MOVE_CHANGE_WEIGHT * best_move_changes) * (BEST_MOVE_EFFORT_BASE - best_move_effort)
+}
+
+/// Whether a mate was found that ends the search. When a mate within a limit is looked for,
+/// other mates may still turn into the one looked for with a deeper search.
+fn mate_found(score: Score, side_to_move: Color, mate_limit: Option<u8>) -> bool {
+    match (score, mate_limit) {
+        (Score::Mate(color, moves), Some(limit)) => color == side_to_move && moves <= limit,
+        (Score::Mate(_, _), None) => true,
+        (Score::Value(_), _) => false,
+    }
+}
+
+/// Depth the variation at the given index is searched to in an iteration, with several lines.
+/// Lower lines matter less, so they get a shallower search and a smaller share of the budget,
+/// one ply less each time the number of lines before them doubles.
+fn multi_pv_line_depth(depth: Depth, line: usize) -> Depth {
+    depth.saturating_sub((line + 1).ilog2() as Depth).max(1)
+}
+
+fn search_iteration(
     position: &Position,
+    guess: ValueScore,
     depth: Depth,
-    score: Score,
-    count: usize,
-    elapsed: Duration,
-    table: &SearchTable,
-) {
-    let elapsed_micros = elapsed.as_micros();
-    let nps = (count as f64 / (elapsed_micros.max(1) as f64 / 1000000.0)) as usize;
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+    number_threads: u16,
+) -> Option<(Score, usize, Vec<Move>)> {
+    // Helper threads shuffle root moves differently, even when the search is seeded.
+    let helper_constraints = (1..number_threads)
+        .map(|i| SearchConstraint {
+            seed: constraint.seed.map(|seed| seed.wrapping_add(i as u64)),
+            ..constraint.clone()
+        })
+        .collect::<Vec<_>>();
+    constraint.nodes.store(0, Ordering::Relaxed);
 
-    print!("info depth {} ", depth);
+    thread::scope(|s| {
+        // We must tell threads that it is ok to run.
+        constraint.threads_stop.store(false, Ordering::Release);
 
-    match score {
-        Score::Value(score) => {
-            print!("score cp {} ", score);
+        if number_threads == 1 || depth == 1 {
+            // It is important to at least get a move with depth == 1, so do the simplest thing possible.
+            return pvs::pvs_aspiration::<true>(position, guess, depth, table.clone(), constraint);
         }
-        Score::Mate(color, moves) => {
-            if color == position.side_to_move {
-                print!("score mate {} ", moves);
-            } else {
-                let moves = moves as i16;
-                print!("score mate {} ", -moves);
-            }
+
+        // Start threads.
+        // The main thread will signal others to stop.
+        // Half of the helpers search one ply deeper, and their windows are slightly off,
+        // so that threads do not all walk the same tree in the same order.
+        let handles = std::iter::once(constraint)
+            .chain(helper_constraints.iter())
+            .enumerate()
+            .map(|(i, constraint)| {
+                let table = table.for_thread(i);
+                let pvs_function =
+                    if i == 0 { pvs::pvs_aspiration::<true> } else { pvs::pvs_aspiration::<false> };
+                let (depth, guess) = if i == 0 {
+                    (depth, guess)
+                } else {
+                    (
+                        depth.saturating_add((i % 2) as Depth),
+                        guess.saturating_add(HELPER_WINDOW_JITTER * ((i % 3) as ValueScore - 1)),
+                    )
+                };
+                s.spawn(move || pvs_function(position, guess, depth, table, constraint))
+            })
+            .collect::<Vec<_>>();
+
+        // Wait for the threads to stop and return the score and line of the main thread,
+        // with the nodes of all of them.
+        let results = handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>();
+        results
+            .into_iter()
+            .next()
+            .flatten()
+            .map(|(score, _, pv)| (score, constraint.nodes.load(Ordering::Relaxed), pv))
+    })
+}
+
+/// Completes a line of the search with the moves the table knows after it, up to the depth,
+/// since lines stop short where the search took a score from the table.
+fn extend_pv(
+    position: &Position,
+    mut pv: Vec<Move>,
+    depth: Depth,
+    table: &SearchTable,
+) -> Vec<Move> {
+    if pv.len() >= depth as usize {
+        return pv;
+    }
+
+    let end = pv.iter().fold(*position, |position, mov| position.make_move(*mov));
+    pv.extend(table.get_pv(&end, depth - pv.len() as Depth));
+    pv
+}
+
+fn verify_best_move(
+    position: &Position,
+    best_move: Move,
+    best_score: ValueScore,
+    depth: Depth,
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+) -> Move {
+    let constraint = &follow_up_constraint(constraint);
+    if constraint.pondering()
+        || constraint.should_stop_search()
+        || constraint.remaining_time().is_none_or(|t| t.is_zero())
+    {
+        return best_move;
+    }
+
+    // Look for a runner-up close to the best move, at a lower depth to be fast.
+    let threshold = best_score.saturating_sub(VERIFICATION_MARGIN);
+    let runner_up = movepick::ordered_moves(position).find(|mov| {
+        *mov != best_move
+            && pvs::root_move_reaches(
+                position,
+                *mov,
+                depth.saturating_sub(1),
+                threshold,
+                table.clone(),
+                constraint,
+            )
+    });
+
+    if constraint.should_stop_search() {
+        return best_move;
+    }
+
+    // The score of the best move is exact at this depth, so we only need to check
+    // whether the runner-up beats it, which the last iteration may have missed due to reductions.
+    match runner_up {
+        Some(runner_up)
+            if pvs::root_move_reaches(
+                position,
+                runner_up,
+                depth,
+                best_score.saturating_add(1),
+                table.clone(),
+                constraint,
+            ) && !constraint.should_stop_search() =>
+        {
+            runner_up
         }
+        _ => best_move,
     }
+}
 
-    println!(
-        "time {} nodes {} nps {} hashfull {} pv {}",
-        (elapsed_micros / 1000).max(1),
-        count,
-        nps,
-        table.hashfull_millis(),
-        table.get_pv(position, depth).iter().map(|m| m.to_string()).collect::<Vec<_>>().join(" ")
-    );
+/// Constraint for the short searches that check the best move once the main search is over.
+/// They keep to the clock, the stop flag and the ponder state of the main search, but not to
+/// its signal to the helper threads, which the last iteration leaves raised.
+fn follow_up_constraint(constraint: &SearchConstraint) -> SearchConstraint {
+    SearchConstraint {
+        threads_stop: Arc::new(AtomicBool::new(false)),
+        excluded_root_moves: Vec::new(),
+        tree_dump: None,
+        ..constraint.clone()
+    }
+}
+
+fn check_root_blunder(
+    position: &Position,
+    best_move: Move,
+    previous_move: Move,
+    previous_score: ValueScore,
+    depth: Depth,
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+) -> Move {
+    // The last iteration may have switched to a worse move because it was cut short
+    // or unstable. If a short search still finds the previous move fine, keep it.
+    // Without time left for that search, the new move stands.
+    let constraint = follow_up_constraint(constraint);
+    if constraint.should_stop_search() {
+        return best_move;
+    }
+    let depth = depth.saturating_sub(1).clamp(1, BLUNDER_CHECK_MAX_DEPTH);
+    let threshold = previous_score.saturating_sub(BLUNDER_MARGIN);
+
+    if pvs::root_move_reaches(position, previous_move, depth, threshold, table, &constraint)
+        && !constraint.should_stop_search()
+    {
+        previous_move
+    } else {
+        best_move
+    }
+}
+
+fn pick_varied_move(
+    position: &Position,
+    best_move: Move,
+    best_score: ValueScore,
+    depth: Depth,
+    margin: ValueScore,
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+    mut rng: StdRng,
+) -> Move {
+    // Alternatives are verified with a shallow search, which keeps the extra time small.
+    // It still keeps to what is left of the clock, and to the stop flag.
+    let constraint = follow_up_constraint(constraint);
+    if constraint.should_stop_search() {
+        return best_move;
+    }
+    let depth = depth.min(VARIETY_MAX_DEPTH);
+    let threshold = best_score.saturating_sub(margin);
+
+    let candidates = position
+        .moves(MoveStage::All)
+        .into_iter()
+        .filter(|mov| {
+            *mov == best_move
+                || pvs::root_move_reaches(
+                    position,
+                    *mov,
+                    depth,
+                    threshold,
+                    table.clone(),
+                    &constraint,
+                )
+        })
+        .collect::<Vec<_>>();
+
+    // Moves are not trusted to be close to the best one if their search was cut short.
+    if constraint.should_stop_search() {
+        return best_move;
+    }
+
+    candidates[rng.gen_range(0..candidates.len())]
+}
+
+/// Outcome of a search, as of the last completed iteration.
+#[derive(Default)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Expected reply to the best move, worth pondering on.
+    pub ponder_move: Option<Move>,
+    /// Score from the perspective of the side to move.
+    pub score: Option<Score>,
+    pub depth: Depth,
+    /// Deepest ply reached, including quiescence.
+    pub seldepth: Depth,
+    pub nodes: usize,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+    /// How many times more nodes each iteration took than the previous one.
+    pub branching_factor: Option<f64>,
+}
+
+impl SearchResult {
+    pub fn nps(&self) -> usize {
+        nodes_per_second(self.nodes, self.time)
+    }
+}
+
+pub fn nodes_per_second(nodes: usize, time: Duration) -> usize {
+    (nodes as f64 / (time.as_micros().max(1) as f64 / 1000000.0)) as usize
+}
+
+#[derive(Copy, Clone)]
+pub enum AnalysisLimit {
+    Depth(Depth),
+    Time(Duration),
+    Nodes(usize),
+}
+
+/// Searches a single position on the current thread, with a small private table.
+pub fn search(position: &Position, limit: AnalysisLimit) -> SearchResult {
+    analyze_batch(std::slice::from_ref(position), limit, 1).pop().unwrap()
+}
+
+/// Searches independent positions in parallel, each thread with its own small table,
+/// returning the results in the same order as the positions.
+pub fn analyze_batch(
+    positions: &[Position],
+    limit: AnalysisLimit,
+    number_threads: usize,
+) -> Vec<SearchResult> {
+    let next_position = AtomicUsize::new(0);
+    let results = Mutex::new((0..positions.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|s| {
+        for _ in 0..number_threads.clamp(1, positions.len().max(1)) {
+            s.spawn(|| {
+                let table = Arc::new(SearchTable::new(BATCH_TABLE_SIZE_MB));
+
+                loop {
+                    let index = next_position.fetch_add(1, Ordering::Relaxed);
+                    let Some(position) = positions.get(index) else {
+                        break;
+                    };
+
+                    let (depth, time_constraint, max_nodes) = match limit {
+                        AnalysisLimit::Depth(depth) => (depth, None, None),
+                        AnalysisLimit::Time(move_time) => (
+                            MAX_DEPTH,
+                            Some(TimeConstraint {
+                                initial_instant: Instant::now(),
+                                move_time,
+                                soft_time: None,
+                            }),
+                            None,
+                        ),
+                        AnalysisLimit::Nodes(nodes) => (MAX_DEPTH, None, Some(nodes)),
+                    };
+                    let constraint = SearchConstraint {
+                        time_constraint,
+                        global_stop: Arc::new(AtomicBool::new(false)),
+                        threads_stop: Arc::new(AtomicBool::new(false)),
+                        ponder_mode: Arc::new(AtomicBool::new(false)),
+                        number_threads: Arc::new(AtomicU16::new(1)),
+                        max_nodes,
+                        ..Default::default()
+                    };
+
+                    table.clear();
+                    let current_guess = position.value() * position.side_to_move.sign();
+                    let result = iterative_deepening(
+                        position,
+                        current_guess,
+                        depth,
+                        table.clone(),
+                        &constraint,
+                        &NoObserver,
+                    );
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter().map(|result| result.unwrap()).collect()
 }
 
 pub fn pvs_aspiration_iterative(
+    position: &Position,
+    current_guess: ValueScore,
+    depth: Depth,
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+    observer: &dyn SearchObserver,
+) -> SearchResult {
+    iterative_deepening(position, current_guess, depth, table, constraint, observer)
+}
+
+pub fn iterative_deepening(
     position: &Position,
     mut current_guess: ValueScore,
     depth: Depth,
     table: Arc<SearchTable>,
     constraint: &SearchConstraint,
-) -> Option<Move> {
-    let mut moves = position.moves(MoveStage::All);
+    observer: &dyn SearchObserver,
+) -> SearchResult {
+    let moves = position.moves(MoveStage::All);
+    let mut result = SearchResult::default();
 
     if moves.is_empty() {
-        return None;
+        return result;
     }
 
     table.prepare_for_new_search();
+    constraint.seldepth.store(0, Ordering::Relaxed);
+    constraint.searched_nodes.store(0, Ordering::Relaxed);
+    *constraint.root_move.lock().unwrap() = None;
 
+    let search_start = Instant::now();
     let number_threads = constraint.number_threads.load(std::sync::atomic::Ordering::Relaxed);
     let mut current_depth = 1;
     let mut current_best_move = None;
+    let mut last_completed = None;
+    let mut previous_best = None;
+    let mut last_lines = Vec::new();
+    let mut stopped_on_soft_time = false;
+    let multi_pv = (constraint.multi_pv.max(1) as usize).min(moves.len());
+    let mut last_count = 0;
+    let mut branching_factor = None;
+    let mut best_move_changes = 0.0;
+    let mut best_move_effort = 0.0;
 
     while constraint.pondering() || current_depth <= depth {
         let time = std::time::Instant::now();
 
-        let search_result = thread::scope(|s| {
-            // We must tell threads that it is ok to run.
-            constraint.threads_stop.store(false, Ordering::Release);
+        // Only the tree of the last iteration is worth dumping.
+        if let Some(tree_dump) = &constraint.tree_dump {
+            tree_dump.lock().unwrap().clear();
+        }
 
-            if number_threads == 1 || current_depth == 1 {
-                // It is important to at least get a move with depth == 1, so do the simplest thing possible.
-                return pvs::pvs_aspiration::<true>(
-                    position,
-                    current_guess,
-                    current_depth,
-                    table.clone(),
-                    constraint,
-                );
+        // Each variation after the first is the best root move, excluding those already found.
+        let mut lines: Vec<(Score, Depth, Move, Vec<Move>)> = Vec::with_capacity(multi_pv);
+        let mut count = 0;
+        let mut line_guess = current_guess;
+        while lines.len() < multi_pv {
+            let line_constraint;
+            let line_constraint = if lines.is_empty() {
+                constraint
+            } else {
+                line_constraint = SearchConstraint {
+                    excluded_root_moves: lines.iter().map(|(_, _, mov, _)| *mov).collect(),
+                    ..constraint.clone()
+                };
+                &line_constraint
+            };
+
+            let line_depth = multi_pv_line_depth(current_depth, lines.len());
+            let Some((score, nodes, pv)) = search_iteration(
+                position,
+                line_guess,
+                line_depth,
+                table.clone(),
+                line_constraint,
+                number_threads,
+            ) else {
+                break;
+            };
+            count += nodes;
+            if lines.is_empty() {
+                best_move_effort =
+                    constraint.best_move_effort.load(Ordering::Relaxed) as f64 / 1000.0;
             }
 
-            // Start threads.
-            // The main thread will signal others to stop.
-            let handles = (0..number_threads)
-                .map(|i| {
-                    let table = table.clone();
-                    let pvs_function = if i == 0 {
-                        pvs::pvs_aspiration::<true>
-                    } else {
-                        pvs::pvs_aspiration::<false>
-                    };
-                    s.spawn(move || {
-                        pvs_function(position, current_guess, current_depth, table, constraint)
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            // Wait for the threads to stop and return the result of the main thread.
-            let results = handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>();
-            results[0]
-        });
+            let pv = extend_pv(position, pv, line_depth, &table);
+            let Some(line_move) = pv.first().copied().or_else(|| table.get_hash_move(position))
+            else {
+                break;
+            };
+            if let Score::Value(score) = score {
+                line_guess = score;
+            }
+            lines.push((score, line_depth, line_move, pv));
+        }
+        result.nodes += count;
 
-        if search_result.is_none() {
-            // The search could not finish in time.
+        // The search could not finish in time. Lines completed before the interruption are
+        // still reported and used, and only the unfinished ones are dropped.
+        let interrupted = lines.len() < multi_pv;
+        if lines.is_empty() {
             break;
         }
 
-        let (score, count) = search_result.unwrap();
+        // The effective branching factor is how many times more nodes each new iteration takes.
+        if !interrupted {
+            if last_count > 0 {
+                branching_factor = Some(count as f64 / last_count as f64);
+            }
+            last_count = count;
+        }
 
+        let (score, _, best_move, _) = lines[0];
         if let Score::Value(score) = score {
             current_guess = score;
         }
 
         let elapsed = time.elapsed();
         if current_depth < MAX_DEPTH {
-            print_iter_info(position, current_depth, score, count, time.elapsed(), &table);
+            for (i, (score, line_depth, line_move, pv)) in lines.iter().enumerate() {
+                if constraint.mate_limit.is_some() && !matches!(score, Score::Mate(_, _)) {
+                    continue;
+                }
+                observer.on_iteration(&SearchInfo {
+                    depth: *line_depth,
+                    seldepth: constraint.seldepth.load(Ordering::Relaxed),
+                    multi_pv: (multi_pv > 1).then_some(i + 1),
+                    score: *score,
+                    side_to_move: position.side_to_move,
+                    wdl: wdl(*score, position),
+                    nodes: count,
+                    time: elapsed,
+                    hashfull: table.hashfull_millis(),
+                    pv: pv.clone(),
+                    refutation: extend_pv(
+                        &position.make_move(*line_move),
+                        pv.iter().skip(1).take(REFUTATION_PLIES as usize).copied().collect(),
+                        REFUTATION_PLIES,
+                        &table,
+                    ),
+                });
+            }
         }
 
+        if let (Some(best_move), Some((Score::Value(previous_score), _))) =
+            (current_best_move, last_completed)
+        {
+            previous_best = Some((best_move, previous_score));
+        }
+
+        best_move_changes /= 2.0;
+        if current_best_move.is_some_and(|mov| mov != best_move) {
+            best_move_changes += 1.0;
+        }
+
+        last_completed = Some((score, current_depth));
         current_depth = (current_depth + 1).min(MAX_DEPTH);
-        current_best_move = table.get_hash_move(position);
+        current_best_move = Some(best_move);
+        last_lines = lines.iter().map(|(score, _, mov, _)| (*mov, *score)).collect();
+        result.pv = lines.swap_remove(0).3;
+
+        if interrupted {
+            break;
+        }
 
+        let soft_time_over =
+            constraint.soft_time_over(soft_time_scale(best_move_changes, best_move_effort));
         if !constraint.pondering()
             && (moves.len() == 1
-                || matches!(score, Score::Mate(_, _))
-                || elapsed > constraint.remaining_time().unwrap_or(elapsed))
+                || mate_found(score, position.side_to_move, constraint.mate_limit)
+                || elapsed > constraint.remaining_time().unwrap_or(elapsed)
+                || soft_time_over
+                || constraint.soft_nodes_over())
         {
             // There is no need to keep going if we have only one move or found a mate.
             // If our remaining time is less that the time it took to finish the last iteration,
             // we should stop: it is very likely that the next iteration will take more time.
+            // A settled best move is also not worth the time a new iteration would take,
+            // though the time left before the hard limit may still go to verifying it.
+            stopped_on_soft_time = soft_time_over;
             break;
         }
     }
 
+    // An infinite search holds its best move back until it is told to stop.
+    while constraint.infinite && !constraint.global_stop.load(Ordering::Acquire) {
+        thread::sleep(INFINITE_POLL_INTERVAL);
+    }
+
     if let Some(best_move) = current_best_move.or(table.get_hash_move(position)) {
-        // Best move found, as expected.
-        print!("bestmove {}", best_move);
+        // Verify close calls with the time left before the hard limit, if the soft limit
+        // ended the search after a completed iteration.
+        let best_move = match last_completed {
+            Some((Score::Value(score), depth))
+                if stopped_on_soft_time && depth > 1 && multi_pv == 1 =>
+            {
+                verify_best_move(position, best_move, score, depth, table.clone(), constraint)
+            }
+            _ => best_move,
+        };
 
-        // Tell operator we'd like to ponder on this next move next, while the opponent is thinking.
-        let new_position = position.make_move(best_move);
-        if let Some(ponder_move) = table.get_hash_move(&new_position) {
-            println!(" ponder {}", ponder_move);
-        } else {
-            println!();
-        }
+        // Do not trust a sudden score drop that comes with a new best move.
+        let best_move = match (previous_best, last_completed) {
+            (Some((previous_move, previous_score)), Some((Score::Value(score), depth)))
+                if multi_pv == 1
+                    && previous_move != best_move
+                    && score < previous_score.saturating_sub(BLUNDER_MARGIN) =>
+            {
+                check_root_blunder(
+                    position,
+                    best_move,
+                    previous_move,
+                    previous_score,
+                    depth,
+                    table.clone(),
+                    constraint,
+                )
+            }
+            _ => best_move,
+        };
 
-        Some(best_move)
+        // Pick randomly among moves close to the best one, to avoid repeating the same games.
+        // A limited strength picks among all the lines instead, also giving up good moves.
+        let best_move = match (constraint.strength, constraint.variety_margin, last_completed) {
+            (Some(strength), _, _) if !last_lines.is_empty() => {
+                let mut rng = constraint.rng(position.zobrist_hash());
+                strength.pick_move(&last_lines, position.side_to_move, &mut rng)
+            }
+            (_, Some(margin), Some((Score::Value(score), depth))) => {
+                let rng = constraint.rng(position.zobrist_hash());
+                pick_varied_move(
+                    position,
+                    best_move,
+                    score,
+                    depth,
+                    margin,
+                    table.clone(),
+                    constraint,
+                    rng,
+                )
+            }
+            _ => best_move,
+        };
+
+        // Best move found, as expected.
+        // We'd like to ponder on the expected reply, while the opponent is thinking.
+        result.best_move = Some(best_move);
+        result.ponder_move = table.get_hash_move(&position.make_move(best_move));
     } else {
         // This cannot have happened if we reached depth > 1,
         // unless we have a bug in the transposition table.
@@ -169,9 +646,249 @@ pub fn pvs_aspiration_iterative(
         }
 
         // We are in time trouble. Return a "panic" perceived best move.
-        moves.sort_by_cached_key(|m| -evaluate_move(position, *m));
-        println!("bestmove {}", moves[0]);
+        let best_move = moves.into_iter().max_by_key(|mov| evaluate_move(position, *mov)).unwrap();
+        result.best_move = Some(best_move);
+    }
+
+    if let Some((score, depth)) = last_completed {
+        result.score = Some(score);
+        result.depth = depth;
+    }
+    result.seldepth = constraint.seldepth.load(Ordering::Relaxed);
+    result.time = search_start.elapsed();
+    result.branching_factor = branching_factor;
+
+    observer.on_best_move(&result);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        analyze_batch, iterative_deepening, mate_found, multi_pv_line_depth,
+        observer::{NoObserver, SearchInfo, SearchObserver},
+        search, verify_best_move, AnalysisLimit, SearchResult, MAX_DEPTH, REFUTATION_PLIES,
+    };
+    use crate::{
+        evaluation::Score,
+        moves::{gen::MoveStage, Move},
+        position::{
+            fen::{FromFen, KIWIPETE_WHITE_FEN, START_FEN},
+            Color, Position,
+        },
+        search::{
+            constraint::{SearchConstraint, TimeConstraint},
+            table::SearchTable,
+        },
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicU16, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn analyze_batch_keeps_order() {
+        let positions = [START_FEN, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", KIWIPETE_WHITE_FEN]
+            .iter()
+            .map(|fen| Position::from_fen(fen).unwrap())
+            .collect::<Vec<_>>();
+
+        let results = analyze_batch(&positions, AnalysisLimit::Depth(4), 2);
+
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| result.best_move.is_some()));
+        assert_eq!(results[1].best_move.unwrap().to_string(), "a1a8");
+        assert_eq!(results[1].score, Some(Score::Mate(Color::White, 1)));
+        assert_eq!(results[0].pv.first(), results[0].best_move.as_ref());
+    }
+
+    #[test]
+    fn node_limit_stops_deepening() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let result = search(&position, AnalysisLimit::Nodes(5000));
 
-        Some(moves[0])
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= 5000);
+        assert!(result.depth < search(&position, AnalysisLimit::Nodes(200000)).depth);
+    }
+
+    #[test]
+    fn max_nodes_aborts_the_iteration() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let table = Arc::new(SearchTable::new(1));
+        let constraint = SearchConstraint {
+            number_threads: Arc::new(AtomicU16::new(1)),
+            max_nodes: Some(3000),
+            ..Default::default()
+        };
+
+        let result = iterative_deepening(&position, 0, MAX_DEPTH, table, &constraint, &NoObserver);
+
+        assert!(result.best_move.is_some());
+        assert!(constraint.searched_nodes.load(Ordering::Relaxed) <= 3000);
+    }
+
+    #[test]
+    fn mate_limit_waits_for_a_short_enough_mate() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        assert!(mate_found(Score::Mate(Color::White, 1), Color::White, Some(1)));
+        assert!(!mate_found(Score::Mate(Color::White, 3), Color::White, Some(2)));
+        assert!(!mate_found(Score::Mate(Color::Black, 1), Color::White, Some(2)));
+        assert!(mate_found(Score::Mate(Color::Black, 1), Color::White, None));
+
+        let table = Arc::new(SearchTable::new(1));
+        let constraint = SearchConstraint {
+            number_threads: Arc::new(AtomicU16::new(1)),
+            mate_limit: Some(1),
+            ..Default::default()
+        };
+        let observer = RecordingObserver::default();
+
+        let result = iterative_deepening(&position, 0, 5, table, &constraint, &observer);
+
+        assert_eq!(result.score, Some(Score::Mate(Color::White, 1)));
+        assert_eq!(*observer.depths.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn verification_switches_to_a_better_runner_up() {
+        let position = Position::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let table = Arc::new(SearchTable::new(1));
+        let moves = position.moves(MoveStage::All);
+        let quiet_move = *moves.iter().find(|mov| mov.to_string() == "e1f1").unwrap();
+
+        // Without a clock, there is no time left to verify the best move with.
+        let unconstrained = SearchConstraint::default();
+        let best_move =
+            verify_best_move(&position, quiet_move, 0, 3, table.clone(), &unconstrained);
+        assert_eq!(best_move, quiet_move);
+
+        // With time left, the runner-up is searched and takes the queen.
+        let constraint = SearchConstraint {
+            time_constraint: Some(TimeConstraint {
+                initial_instant: Instant::now(),
+                move_time: Duration::from_secs(10),
+                soft_time: Some(Duration::from_secs(1)),
+            }),
+            ..Default::default()
+        };
+        let best_move = verify_best_move(&position, quiet_move, 0, 3, table, &constraint);
+        assert_eq!(best_move.to_string(), "d2d5");
+    }
+
+    #[test]
+    fn multi_pv_lower_lines_go_shallower() {
+        let depths = (0..8).map(|line| multi_pv_line_depth(10, line)).collect::<Vec<_>>();
+        assert_eq!(depths, vec![10, 9, 9, 8, 8, 8, 8, 7]);
+        assert_eq!(multi_pv_line_depth(1, 5), 1);
+    }
+
+    #[test]
+    fn multi_pv_keeps_best_line_first() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let table = Arc::new(SearchTable::new(1));
+        let constraint = SearchConstraint {
+            number_threads: Arc::new(AtomicU16::new(1)),
+            multi_pv: 3,
+            ..Default::default()
+        };
+
+        let result = iterative_deepening(&position, 0, 3, table, &constraint, &NoObserver);
+
+        assert_eq!(result.best_move.unwrap().to_string(), "a1a8");
+        assert_eq!(result.score, Some(Score::Mate(Color::White, 1)));
+        assert_eq!(result.pv.first(), result.best_move.as_ref());
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        depths: Mutex<Vec<u8>>,
+        best_move: Mutex<Option<String>>,
+    }
+
+    impl SearchObserver for RecordingObserver {
+        fn on_iteration(&self, info: &SearchInfo) {
+            assert_eq!(info.pv.first().map(|m| m.to_string()), Some("a1a8".to_string()));
+            assert_eq!(info.mate_in(), Some(1));
+            assert!(info.refutation.is_empty());
+            self.depths.lock().unwrap().push(info.depth);
+        }
+
+        fn on_best_move(&self, result: &SearchResult) {
+            *self.best_move.lock().unwrap() = result.best_move.map(|mov| mov.to_string());
+        }
+    }
+
+    #[test]
+    fn observer_receives_progress() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let table = Arc::new(SearchTable::new(1));
+        let constraint =
+            SearchConstraint { number_threads: Arc::new(AtomicU16::new(1)), ..Default::default() };
+        let observer = RecordingObserver::default();
+
+        iterative_deepening(&position, 0, 3, table, &constraint, &observer);
+
+        // A mate is found on the first iteration, so the search stops there.
+        assert_eq!(*observer.depths.lock().unwrap(), vec![1]);
+        assert_eq!(observer.best_move.lock().unwrap().as_deref(), Some("a1a8"));
+    }
+
+    #[test]
+    fn infinite_search_waits_for_stop() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let table = Arc::new(SearchTable::new(1));
+        let constraint = SearchConstraint {
+            number_threads: Arc::new(AtomicU16::new(1)),
+            infinite: true,
+            ..Default::default()
+        };
+        let observer = RecordingObserver::default();
+
+        let start = Instant::now();
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                constraint.global_stop.store(true, Ordering::Release);
+            });
+            iterative_deepening(&position, 0, 3, table, &constraint, &observer);
+        });
+
+        // The mate is found at once, but the best move is only reported when stopped.
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(observer.best_move.lock().unwrap().as_deref(), Some("a1a8"));
+    }
+
+    #[derive(Default)]
+    struct RefutationObserver {
+        lines: Mutex<Vec<(Vec<Move>, Vec<Move>)>>,
+    }
+
+    impl SearchObserver for RefutationObserver {
+        fn on_iteration(&self, info: &SearchInfo) {
+            self.lines.lock().unwrap().push((info.pv.clone(), info.refutation.clone()));
+        }
+    }
+
+    #[test]
+    fn refutation_follows_the_pv() {
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let table = Arc::new(SearchTable::new(4));
+        let constraint =
+            SearchConstraint { number_threads: Arc::new(AtomicU16::new(1)), ..Default::default() };
+        let observer = RefutationObserver::default();
+
+        iterative_deepening(&position, 0, 5, table, &constraint, &observer);
+
+        let lines = observer.lines.lock().unwrap();
+        assert_eq!(lines.len(), 5);
+        for (pv, refutation) in lines.iter().filter(|(pv, _)| pv.len() > 1) {
+            assert!(refutation.len() <= REFUTATION_PLIES as usize);
+            assert!(refutation.starts_with(&pv[1..]));
+        }
     }
 }
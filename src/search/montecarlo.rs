@@ -0,0 +1,135 @@
+// Experimental Monte-Carlo analysis. Root moves are sampled with UCB1, and each sample plays
+// random moves to a fixed depth before scoring the leaf with the quiescence search. How often
+// each move is sampled gives a probability distribution over the moves, to set against the
+// single choice of the alpha-beta search.
+
+use super::{constraint::SearchConstraint, quiesce::quiesce, Depth};
+use crate::{
+    evaluation::ValueScore,
+    moves::{gen::MoveStage, Move},
+    position::Position,
+};
+use rand::Rng;
+
+pub const DEFAULT_PLAYOUTS: usize = 2000;
+pub const DEFAULT_PLAYOUT_DEPTH: Depth = 4;
+
+// Weight of the exploration of rarely sampled moves against the exploitation of good ones.
+const EXPLORATION: f64 = 0.5;
+
+// Centipawns of advantage that make a win about 73% likely.
+const WIN_PROBABILITY_SCALE: f64 = 400.0;
+
+/// Samples of a root move.
+#[derive(Debug, Copy, Clone)]
+pub struct PlayoutStats {
+    pub mov: Move,
+    pub visits: usize,
+    /// Sum of the expected scores of the samples, from 0 to 1 each.
+    pub total: f64,
+}
+
+impl PlayoutStats {
+    /// Average expected score of the side to move at the root, from 0 to 1.
+    pub fn score(&self) -> f64 {
+        self.total / self.visits.max(1) as f64
+    }
+
+    fn upper_bound(&self, all_visits: usize) -> f64 {
+        if self.visits == 0 {
+            return f64::INFINITY;
+        }
+        self.score() + EXPLORATION * ((all_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+}
+
+fn win_probability(score: ValueScore) -> f64 {
+    1.0 / (1.0 + (-(score as f64) / WIN_PROBABILITY_SCALE).exp())
+}
+
+/// Expected score of the side to move after random moves to the given depth.
+fn playout(
+    position: &Position,
+    depth: Depth,
+    constraint: &SearchConstraint,
+    rng: &mut impl Rng,
+) -> f64 {
+    let mut position = *position;
+
+    for ply in 0..depth {
+        let moves = position.moves(MoveStage::All);
+        if moves.is_empty() {
+            let score = if position.is_check() { 0.0 } else { 0.5 };
+            return if ply.is_multiple_of(2) { score } else { 1.0 - score };
+        }
+        position = position.make_move(moves[rng.gen_range(0..moves.len())]);
+    }
+
+    let (score, _) =
+        quiesce(&mut position, ValueScore::MIN + 1, ValueScore::MAX, constraint, depth);
+    let score = win_probability(score);
+    if depth.is_multiple_of(2) {
+        score
+    } else {
+        1.0 - score
+    }
+}
+
+/// Samples the root moves with the given number of playouts, the most sampled first.
+pub fn monte_carlo(
+    position: &Position,
+    playouts: usize,
+    depth: Depth,
+    constraint: &SearchConstraint,
+) -> Vec<PlayoutStats> {
+    let mut rng = constraint.rng(position.zobrist_hash());
+    let mut stats = position
+        .moves(MoveStage::All)
+        .into_iter()
+        .map(|mov| PlayoutStats { mov, visits: 0, total: 0.0 })
+        .collect::<Vec<_>>();
+
+    if stats.is_empty() {
+        return stats;
+    }
+
+    for all_visits in 0..playouts {
+        let index = (0..stats.len())
+            .max_by(|a, b| {
+                stats[*a].upper_bound(all_visits).total_cmp(&stats[*b].upper_bound(all_visits))
+            })
+            .unwrap();
+
+        // The playout scores the position for the opponent, who moves next.
+        let child = position.make_move(stats[index].mov);
+        let score = 1.0 - playout(&child, depth.saturating_sub(1), constraint, &mut rng);
+        stats[index].visits += 1;
+        stats[index].total += score;
+    }
+
+    stats.sort_by_key(|stats| std::cmp::Reverse(stats.visits));
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::monte_carlo;
+    use crate::{
+        moves::gen::MoveStage,
+        position::{fen::FromFen, Position},
+        search::constraint::SearchConstraint,
+    };
+
+    #[test]
+    fn mate_is_sampled_the_most() {
+        let position = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let constraint = SearchConstraint { seed: Some(1), ..Default::default() };
+
+        let stats = monte_carlo(&position, 500, 2, &constraint);
+
+        assert_eq!(stats.len(), position.moves(MoveStage::All).len());
+        assert_eq!(stats.iter().map(|stats| stats.visits).sum::<usize>(), 500);
+        assert_eq!(stats[0].mov.to_string(), "a1a8");
+        assert_eq!(stats[0].score(), 1.0);
+    }
+}
@@ -1,16 +1,22 @@
-use super::{table::SearchTable, Depth};
+use super::{see, table::SearchTable, Depth};
 use crate::{
     evaluation::{moves::evaluate_move, Evaluable, ValueScore},
-    moves::{gen::MoveStage, Move},
+    moves::{
+        gen::{CheckInfo, MoveStage},
+        Move,
+    },
     position::{board::Piece, Position},
 };
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng};
 use std::sync::Arc;
 
 type ScoredVec<Move> = Vec<(Move, ValueScore)>;
 
 const RANDOM_FACTOR: ValueScore = 1000;
 
+// Scales the capture history down to a nudge between captures of a similar value.
+const CAPTURE_HISTORY_DIVISOR: ValueScore = 16;
+
 pub struct MovePicker<const QUIESCE: bool> {
     index: usize,
     moves: ScoredVec<Move>,
@@ -18,22 +24,29 @@ pub struct MovePicker<const QUIESCE: bool> {
     position: Position,
     table: Option<Arc<SearchTable>>,
     ply: Depth,
+    check_info: CheckInfo,
+    quiet_checks: bool,
 }
 
 impl MovePicker<true> {
-    pub fn new(position: &Position, is_check: bool) -> Self {
-        let moves = position.moves(if is_check {
-            MoveStage::All
+    /// Picks the captures and promotions, or the evasions when in check.
+    /// With `quiet_checks`, non-captures that give check follow the captures.
+    pub fn new(position: &Position, check_info: CheckInfo, quiet_checks: bool) -> Self {
+        let stage = if check_info.is_check() {
+            MoveStage::Evasions
         } else {
             MoveStage::CapturesAndPromotions
-        });
+        };
+        let moves = position.moves_with_info(stage, &check_info);
         Self {
             index: 0,
             moves: decorate_moves_with_score(&moves, |mov| evaluate_move(position, mov)),
-            stage: MoveStage::CapturesAndPromotions,
+            stage,
             position: *position,
             table: None,
             ply: 0,
+            check_info,
+            quiet_checks,
         }
     }
 }
@@ -42,33 +55,61 @@ impl std::iter::Iterator for MovePicker<true> {
     type Item = Move;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(mov) = find_next_max_and_swap(&mut self.moves, &mut self.index) {
+            return Some(mov);
+        }
+
+        if !self.quiet_checks || self.stage != MoveStage::CapturesAndPromotions {
+            return None;
+        }
+
+        // Checks whose piece is left en prise are refuted at once, so only safe ones are tried,
+        // the safest first. This keeps checks from blowing up the quiescence tree.
+        self.stage = MoveStage::NonCaptures;
+        self.moves = self
+            .position
+            .moves_with_info(MoveStage::NonCaptures, &self.check_info)
+            .into_iter()
+            .filter(|mov| mov.gives_check(&self.position))
+            .map(|mov| (mov, see::see::<false>(mov, &self.position.board)))
+            .filter(|(_, see)| *see >= 0)
+            .collect();
+        self.index = 0;
+
         find_next_max_and_swap(&mut self.moves, &mut self.index)
     }
 }
 
 impl MovePicker<false> {
-    pub fn new(position: &Position, table: Arc<SearchTable>, ply: Depth, shuffle: bool) -> Self {
-        let moves = if !shuffle {
-            if let Some(hash_move) = table.get_hash_move(position) {
-                vec![(hash_move, ValueScore::MAX)]
-            } else {
-                vec![]
-            }
-        } else {
+    pub fn new(
+        position: &Position,
+        table: Arc<SearchTable>,
+        ply: Depth,
+        shuffle: Option<StdRng>,
+        check_info: CheckInfo,
+    ) -> Self {
+        let stage = if shuffle.is_none() { MoveStage::HashMove } else { MoveStage::All };
+        let moves = if let Some(mut rng) = shuffle {
             position
-                .moves(MoveStage::All)
+                .moves_with_info(MoveStage::All, &check_info)
                 .into_iter()
-                .map(|m| (m, thread_rng().gen_range(0..RANDOM_FACTOR)))
+                .map(|m| (m, rng.gen_range(0..RANDOM_FACTOR)))
                 .collect::<Vec<_>>()
+        } else if let Some(hash_move) = table.get_hash_move(position) {
+            vec![(hash_move, ValueScore::MAX)]
+        } else {
+            vec![]
         };
 
         Self {
             index: 0,
             moves,
-            stage: if !shuffle { MoveStage::HashMove } else { MoveStage::All },
+            stage,
             position: *position,
             table: Some(table),
             ply,
+            check_info,
+            quiet_checks: false,
         }
     }
 }
@@ -82,11 +123,35 @@ impl std::iter::Iterator for MovePicker<false> {
         }
 
         match self.stage {
+            MoveStage::HashMove if self.check_info.is_check() => {
+                // All evasions are generated at once, since there are only a few of them.
+                self.stage = MoveStage::Evasions;
+                let evasions = self.position.moves_with_info(MoveStage::Evasions, &self.check_info);
+
+                let killers = self.table.as_ref().unwrap().get_killers(self.ply);
+                self.moves = decorate_moves_with_score(&evasions, |mov| {
+                    if killers[1] == Some(mov) || killers[0] == Some(mov) {
+                        Piece::Queen.value()
+                    } else {
+                        evaluate_move(&self.position, mov)
+                    }
+                });
+
+                self.index = 0;
+                self.next()
+            }
             MoveStage::HashMove => {
                 self.stage = MoveStage::CapturesAndPromotions;
+                let table = self.table.as_ref().unwrap();
                 self.moves = decorate_moves_with_score(
-                    &self.position.moves(MoveStage::CapturesAndPromotions),
-                    |mov| evaluate_move(&self.position, mov),
+                    &self
+                        .position
+                        .moves_with_info(MoveStage::CapturesAndPromotions, &self.check_info),
+                    |mov| {
+                        evaluate_move(&self.position, mov)
+                            + table.get_capture_history(&self.position, mov)
+                                / CAPTURE_HISTORY_DIVISOR
+                    },
                 );
 
                 self.index = 0;
@@ -94,7 +159,8 @@ impl std::iter::Iterator for MovePicker<false> {
             }
             MoveStage::CapturesAndPromotions => {
                 self.stage = MoveStage::NonCaptures;
-                let all_non_capture_moves = self.position.moves(MoveStage::NonCaptures);
+                let all_non_capture_moves =
+                    self.position.moves_with_info(MoveStage::NonCaptures, &self.check_info);
 
                 let killers = self.table.as_ref().unwrap().get_killers(self.ply);
                 self.moves = decorate_moves_with_score(&all_non_capture_moves, |mov| {
@@ -125,15 +191,24 @@ fn find_next_max_and_swap(moves: &mut ScoredVec<Move>, index: &mut usize) -> Opt
         return None;
     }
 
-    let mut best_score = moves[*index].1;
-
+    // Select the best remaining move, without sorting the whole list:
+    // most nodes only consume the first few moves before a cutoff.
+    let mut best_index = *index;
     for i in (*index + 1)..moves.len() {
-        if moves[i].1 > best_score {
-            best_score = moves[i].1;
-            moves.swap(i, *index);
+        if moves[i].1 > moves[best_index].1 {
+            best_index = i;
         }
     }
+    moves.swap(best_index, *index);
 
     *index += 1;
     Some(moves[*index - 1].0)
 }
+
+pub fn ordered_moves(position: &Position) -> impl Iterator<Item = Move> {
+    let mut moves = decorate_moves_with_score(&position.moves(MoveStage::All), |mov| {
+        evaluate_move(position, mov)
+    });
+    let mut index = 0;
+    std::iter::from_fn(move || find_next_max_and_swap(&mut moves, &mut index))
+}
@@ -0,0 +1,51 @@
+use super::{nodes_per_second, Depth, SearchResult};
+use crate::{evaluation::Score, moves::Move, position::Color};
+use std::time::Duration;
+
+/// Progress of a search, reported for every line of each completed iteration.
+pub struct SearchInfo {
+    pub depth: Depth,
+    pub seldepth: Depth,
+    /// Index of the line, starting at 1, when more than one line is searched.
+    pub multi_pv: Option<usize>,
+    pub score: Score,
+    pub side_to_move: Color,
+    /// Chances of a win, a draw and a loss for the side to move, in thousandths.
+    pub wdl: (u16, u16, u16),
+    pub nodes: usize,
+    /// Time spent on this iteration.
+    pub time: Duration,
+    /// Transposition table occupancy, in permille.
+    pub hashfull: usize,
+    /// Best line found by the main thread, completed by the table where it was cut short.
+    pub pv: Vec<Move>,
+    /// Best line of the opponent after the first move of the variation, extended with what the
+    /// table knows, so that it may go deeper than the variation itself.
+    pub refutation: Vec<Move>,
+}
+
+impl SearchInfo {
+    /// Moves to mate, negative if the side to move is getting mated.
+    pub fn mate_in(&self) -> Option<i16> {
+        match self.score {
+            Score::Mate(color, moves) if color == self.side_to_move => Some(moves as i16),
+            Score::Mate(_, moves) => Some(-(moves as i16)),
+            Score::Value(_) => None,
+        }
+    }
+
+    pub fn nps(&self) -> usize {
+        nodes_per_second(self.nodes, self.time)
+    }
+}
+
+/// Receives the progress of a search. Both methods do nothing by default.
+pub trait SearchObserver {
+    fn on_iteration(&self, _info: &SearchInfo) {}
+    fn on_best_move(&self, _result: &SearchResult) {}
+}
+
+/// Observer for searches whose progress is not of interest.
+pub struct NoObserver;
+
+impl SearchObserver for NoObserver {}
@@ -0,0 +1,59 @@
+use super::{
+    pvs::{
+        FUTILITY_MARGINS, LMR_DIVISOR, NULL_MOVE_DEPTH_REDUCTION, REVERSE_FUTILITY_MARGIN,
+        WINDOW_SIZE,
+    },
+    Depth, MAX_DEPTH,
+};
+use crate::evaluation::ValueScore;
+use std::{array, sync::LazyLock};
+
+// Late moves past this index are reduced like the last one.
+const LMR_MAX_MOVES: usize = 64;
+
+/// Products of the logarithms of the depth and the move index, the base of the late move
+/// reduction before the tunable divisor.
+static LMR_LOGARITHMS: LazyLock<[[f64; LMR_MAX_MOVES]; MAX_DEPTH as usize + 1]> =
+    LazyLock::new(|| {
+        array::from_fn(|depth| {
+            array::from_fn(|index| ((depth as f64).ln() * (index as f64).ln()).max(0.0))
+        })
+    });
+
+/// The tunable constants of the search. Like the evaluation parameters, they are read through
+/// the constraint, so that self-play can pit different sets against each other.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SearchParams {
+    /// Late moves are reduced by the product of the logarithms of the depth and the move index
+    /// over this divisor, before the adjustments of the node and the move.
+    pub lmr_divisor: f64,
+    pub null_move_reduction: Depth,
+    /// Initial half width of the aspiration window, widened on each failed search.
+    pub aspiration_window: ValueScore,
+    /// Positional gain a move may still bring at depths one and two before being pruned.
+    pub futility_margins: [ValueScore; 2],
+    /// Positional loss per ply of depth the opponent may still inflict on a node
+    /// whose static evaluation is above beta, before the node is cut off.
+    pub reverse_futility_margin: ValueScore,
+}
+
+pub const DEFAULT_SEARCH_PARAMS: SearchParams = SearchParams {
+    lmr_divisor: LMR_DIVISOR,
+    null_move_reduction: NULL_MOVE_DEPTH_REDUCTION,
+    aspiration_window: WINDOW_SIZE,
+    futility_margins: FUTILITY_MARGINS,
+    reverse_futility_margin: REVERSE_FUTILITY_MARGIN,
+};
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        DEFAULT_SEARCH_PARAMS
+    }
+}
+
+impl SearchParams {
+    pub fn late_move_reduction(&self, depth: Depth, move_index: usize) -> i16 {
+        let logarithms = LMR_LOGARITHMS[(depth as usize).min(MAX_DEPTH as usize)];
+        (logarithms[move_index.min(LMR_MAX_MOVES - 1)] / self.lmr_divisor) as i16
+    }
+}
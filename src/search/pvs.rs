@@ -1,19 +1,48 @@
 use super::{
     constraint::SearchConstraint,
+    evalcache::evaluate,
     history::BranchHistory,
     movepick::MovePicker,
-    quiesce,
+    quiesce, see,
     table::{ScoreType, SearchTable},
     Depth, MAX_DEPTH,
 };
 use crate::{
-    evaluation::{position::MAX_POSITIONAL_GAIN, Evaluable, Score, ValueScore, MATE_SCORE},
+    evaluation::{position::MAX_POSITIONAL_GAIN, Evaluable, Score, ValueScore},
+    moves::Move,
     position::{board::Piece, Color, Position},
 };
-use std::{cell::OnceCell, sync::Arc};
+use std::{
+    cell::OnceCell,
+    sync::{atomic::Ordering, Arc},
+};
+
+pub const NULL_MOVE_DEPTH_REDUCTION: Depth = 3;
+pub const WINDOW_SIZE: ValueScore = 100;
+pub const LMR_DIVISOR: f64 = 4.0;
+pub const FUTILITY_MARGINS: [ValueScore; 2] = [MAX_POSITIONAL_GAIN, 2 * MAX_POSITIONAL_GAIN];
+pub const REVERSE_FUTILITY_MARGIN: ValueScore = MAX_POSITIONAL_GAIN / 2;
+
+const REVERSE_FUTILITY_DEPTH: Depth = 4;
+
+// The hash move is extended when the other moves fall this far below its score per ply of depth.
+// It must have been searched nearly as deep as the node, which must be deep enough for the
+// verification search to pay off.
+const SINGULAR_DEPTH: Depth = 8;
+const SINGULAR_MARGIN: ValueScore = 2;
+const SINGULAR_HASH_DEPTH_MARGIN: Depth = 3;
 
-const NULL_MOVE_DEPTH_REDUCTION: Depth = 3;
-const WINDOW_SIZE: ValueScore = 100;
+// Nodes without a hash move are searched one ply shallower from this depth on.
+const IIR_DEPTH: Depth = 4;
+
+// Captures losing more material than this margin per ply of depth are pruned near the leaves.
+// Captures that often caused cutoffs are allowed to lose a bit more.
+const SEE_PRUNING_DEPTH: Depth = 3;
+const SEE_PRUNING_MARGIN: ValueScore = 100;
+const SEE_PRUNING_HISTORY_DIVISOR: ValueScore = 32;
+
+// Late quiet moves are reduced a ply less for each this much of their history, or more if negative.
+const LMR_HISTORY_DIVISOR: ValueScore = 2048;
 
 fn may_be_zugzwang(position: &Position) -> bool {
     let king_pawn_bb =
@@ -25,9 +54,32 @@ fn may_be_zugzwang(position: &Position) -> bool {
     white_pieces_bb.count_ones() < 2 || black_pieces_bb.count_ones() < 2
 }
 
+#[inline(always)]
+fn note_tree<const MAIN_THREAD: bool>(
+    constraint: &SearchConstraint,
+    ply: Depth,
+    reason: &'static str,
+) {
+    if MAIN_THREAD {
+        if let Some(tree_dump) = &constraint.tree_dump {
+            tree_dump.lock().unwrap().note(ply, reason);
+        }
+    }
+}
+
+#[inline(always)]
+fn set_tree_move<const MAIN_THREAD: bool>(constraint: &SearchConstraint, mov: Move) {
+    if MAIN_THREAD {
+        if let Some(tree_dump) = &constraint.tree_dump {
+            tree_dump.lock().unwrap().set_next_move(mov);
+        }
+    }
+}
+
 #[inline(always)]
 fn pvs_recurse<const MAIN_THREAD: bool>(
     position: &mut Position,
+    mov: Move,
     current_depth: Depth,
     alpha: ValueScore,
     beta: ValueScore,
@@ -43,26 +95,39 @@ fn pvs_recurse<const MAIN_THREAD: bool>(
 
     if do_zero_window {
         // We expect this tree to not raise alpha, so we search with tight bounds.
-        let (score, nodes) = pvs::<false, MAIN_THREAD, true>(
-            position,
-            current_depth.saturating_add(extension).saturating_sub(reduction + 1),
-            -alpha - 1,
-            -alpha,
-            table.clone(),
-            constraint,
-            history,
-            current_ply.saturating_add(1),
-        );
-        count += nodes;
-        let score = -score;
-        if score <= alpha || score >= beta {
-            // We did not exceed alpha, so our fast search is ok.
-            return (score, count);
+        // A reduced search may only raise alpha for lack of depth, so it is repeated
+        // without the reduction before its score is trusted.
+        let mut reduction = reduction;
+        loop {
+            set_tree_move::<MAIN_THREAD>(constraint, mov);
+            let (score, nodes) = pvs::<false, MAIN_THREAD, true>(
+                position,
+                current_depth.saturating_add(extension).saturating_sub(reduction + 1),
+                -alpha - 1,
+                -alpha,
+                table.clone(),
+                constraint,
+                history,
+                current_ply.saturating_add(1),
+                None,
+            );
+            count += nodes;
+            let score = -score;
+            if reduction > 0 && score > alpha {
+                reduction = 0;
+                continue;
+            }
+            if score <= alpha || score >= beta {
+                // We did not exceed alpha, so our fast search is ok.
+                return (score, count);
+            }
+            break;
         }
     }
 
     // We found a better move, so we must search with full window to confirm.
     // We also eliminate the reduction to avoid missing deep lines.
+    set_tree_move::<MAIN_THREAD>(constraint, mov);
     let (score, nodes) = pvs::<false, MAIN_THREAD, true>(
         position,
         current_depth.saturating_add(extension).saturating_sub(1),
@@ -72,12 +137,56 @@ fn pvs_recurse<const MAIN_THREAD: bool>(
         constraint,
         history,
         current_ply.saturating_add(1),
+        None,
     );
     count += nodes;
     (-score, count)
 }
 
 fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
+    position: &mut Position,
+    depth: Depth,
+    alpha: ValueScore,
+    beta: ValueScore,
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+    history: &mut BranchHistory,
+    ply: Depth,
+    excluded_move: Option<Move>,
+) -> (ValueScore, usize) {
+    let tree_dump = if MAIN_THREAD { constraint.tree_dump.as_ref() } else { None };
+
+    if let Some(tree_dump) = tree_dump {
+        tree_dump.lock().unwrap().enter(ply, depth, alpha, beta);
+        let (score, count) = pvs_node::<ROOT, MAIN_THREAD, ALLOW_NMR>(
+            position,
+            depth,
+            alpha,
+            beta,
+            table.clone(),
+            constraint,
+            history,
+            ply,
+            excluded_move,
+        );
+        tree_dump.lock().unwrap().leave(ply, Some(score), table.get_hash_move(position));
+        return (score, count);
+    }
+
+    pvs_node::<ROOT, MAIN_THREAD, ALLOW_NMR>(
+        position,
+        depth,
+        alpha,
+        beta,
+        table,
+        constraint,
+        history,
+        ply,
+        excluded_move,
+    )
+}
+
+fn pvs_node<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
     position: &mut Position,
     mut depth: Depth,
     mut alpha: ValueScore,
@@ -86,38 +195,55 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
     constraint: &SearchConstraint,
     history: &mut BranchHistory,
     ply: Depth,
+    excluded_move: Option<Move>,
 ) -> (ValueScore, usize) {
+    // The best line of a previous sibling must not be taken for ours.
+    history.clear_pv(ply);
+
     // Max depth reached; search for quiet position
     if depth == 0 {
+        note_tree::<MAIN_THREAD>(constraint, ply, "quiescence");
         return quiesce::quiesce(position, alpha, beta, constraint, ply);
     }
 
     // Time limit reached
     if constraint.should_stop_search() {
+        note_tree::<MAIN_THREAD>(constraint, ply, "stopped");
         return (alpha, 1);
     }
+    constraint.note_node();
 
     // Detect history-related draws
     let repeated_times = history.repeated(position);
     let twofold_repetition = repeated_times >= 2;
     let threefold_repetition = repeated_times >= 3;
     if position.halfmove_clock >= 100 || threefold_repetition {
-        return (0, 1);
+        if threefold_repetition {
+            history.note_repetition_draw();
+        }
+        note_tree::<MAIN_THREAD>(constraint, ply, "draw");
+        return (constraint.draw_score(ply), 1);
     }
+    let repetition_draws = history.repetition_draws();
 
     // Get known score from transposition table.
     // We disable this in the root node, as we want to explore all moves nevertheless.
     // Also, in case of a twofold repetition, this score may not hold due to possible threefold repetitions.
-    if !ROOT && !twofold_repetition {
+    // A search without some move cannot use the score of the full search either.
+    if !ROOT && !twofold_repetition && excluded_move.is_none() {
         if let Some((score, score_type)) = table.get_table_score(position, depth, ply) {
             match score_type {
-                ScoreType::Exact => return (score, 1),
+                ScoreType::Exact => {
+                    note_tree::<MAIN_THREAD>(constraint, ply, "table exact");
+                    return (score, 1);
+                }
                 ScoreType::LowerBound => alpha = alpha.max(score),
                 ScoreType::UpperBound => beta = beta.min(score),
             }
 
             // Beta cutoff: position is too good
             if alpha >= beta {
+                note_tree::<MAIN_THREAD>(constraint, ply, "table cutoff");
                 return (alpha, 1);
             }
         }
@@ -126,18 +252,20 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
     // Mate distance pruning: don't keep searching if this will lead to longer mates.
     if Score::is_mate(alpha) {
         if alpha > 0 {
-            let mating_value = -MATE_SCORE - ply as ValueScore;
+            let mating_value = Score::mate_in(ply);
             if mating_value < beta {
                 beta = mating_value;
                 if alpha >= mating_value {
+                    note_tree::<MAIN_THREAD>(constraint, ply, "mate distance");
                     return (mating_value, 1);
                 }
             }
         } else {
-            let mating_value = MATE_SCORE + ply as ValueScore;
+            let mating_value = Score::mated_in(ply);
             if mating_value > alpha {
                 alpha = mating_value;
                 if beta <= mating_value {
+                    note_tree::<MAIN_THREAD>(constraint, ply, "mate distance");
                     return (mating_value, 1);
                 }
             }
@@ -148,9 +276,42 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
     let mut count = 1;
 
     // Position and node type considerations.
-    let is_check = position.is_check();
+    let check_info = position.check_info();
+    let is_check = check_info.is_check();
     let may_be_zug = may_be_zugzwang(position);
 
+    // Internal iterative reduction: without a hash move, the move ordering is poor and the
+    // search expensive. A shallower search is cheaper, and fills the table for the next iteration.
+    if !ROOT
+        && excluded_move.is_none()
+        && depth >= IIR_DEPTH
+        && table.get_hash_move(position).is_none()
+    {
+        depth -= 1;
+    }
+
+    // The static evaluation is useful for pruning techniques,
+    // but might not be needed.
+    let static_evaluation = OnceCell::new();
+
+    // Reverse futility pruning: if we are far above beta, even after conceding a margin
+    // for each ply left, the opponent is unlikely to bring the score back.
+    if !ROOT
+        && !is_check
+        && depth <= REVERSE_FUTILITY_DEPTH
+        && !may_be_zug
+        && !Score::is_mate(beta)
+        && excluded_move.is_none()
+    {
+        let evaluation = *static_evaluation.get_or_init(|| evaluate(position, constraint));
+        if evaluation - constraint.search_params.reverse_futility_margin * depth as ValueScore
+            >= beta
+        {
+            note_tree::<MAIN_THREAD>(constraint, ply, "reverse futility");
+            return (evaluation, count);
+        }
+    }
+
     // Null move pruning: if we "pass" our turn and still get a beta cutoff,
     // this position is far too good to be true.
     // We must not allow repeated null moves, otherwise we'll end up
@@ -159,37 +320,57 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
         && ALLOW_NMR
         && !is_check
         && !twofold_repetition
-        && depth > NULL_MOVE_DEPTH_REDUCTION
+        && depth > constraint.search_params.null_move_reduction
         && !may_be_zug
     {
+        // Passing forfeits the right to capture en passant.
+        let en_passant_square = position.en_passant_square.take();
         position.side_to_move = position.side_to_move.opposite();
         let (score, nodes) = pvs::<false, MAIN_THREAD, false>(
             position,
-            depth - NULL_MOVE_DEPTH_REDUCTION,
+            depth - constraint.search_params.null_move_reduction,
             -beta,
             -alpha,
             table.clone(),
             constraint,
             history,
             ply,
+            None,
         );
         position.side_to_move = position.side_to_move.opposite();
+        position.en_passant_square = en_passant_square;
 
         count += nodes;
         let score = -score;
 
         if score >= beta {
+            note_tree::<MAIN_THREAD>(constraint, ply, "null move");
             return (beta, count);
         }
     }
 
     // Prepare move generation and sorting. This is lazy and works in stages.
-    let mut picker =
-        MovePicker::<false>::new(position, table.clone(), ply, ROOT && !MAIN_THREAD).peekable();
+    // Root moves already reported as better variations are left out when searching for the next one.
+    let mut picker = MovePicker::<false>::new(
+        position,
+        table.clone(),
+        ply,
+        (ROOT && !MAIN_THREAD).then(|| constraint.rng(depth as u64)),
+        check_info,
+    )
+    .filter(|mov| !ROOT || !constraint.excluded_root_moves.contains(mov))
+    .filter(|mov| Some(*mov) != excluded_move)
+    .peekable();
+
+    // With the excluded move being the only one, there is nothing better than the bound.
+    if picker.peek().is_none() && excluded_move.is_some() {
+        return (alpha, count);
+    }
 
     // Detect checkmate and stalemate
     if picker.peek().is_none() {
-        let score = if is_check { MATE_SCORE + ply as ValueScore } else { 0 };
+        let score = if is_check { Score::mated_in(ply) } else { constraint.draw_score(ply) };
+        note_tree::<MAIN_THREAD>(constraint, ply, if is_check { "checkmate" } else { "stalemate" });
         return (score, count);
     }
 
@@ -198,42 +379,123 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
         depth = depth.saturating_add(1).min(MAX_DEPTH);
     }
 
-    // The static evaluation is useful for pruning techniques,
-    // but might not be needed.
-    let static_evaluation = OnceCell::new();
+    // Singular extension: if a shallower search without the hash move falls well below its score,
+    // the hash move is the only good one, and worth a deeper look.
+    // If that search still beats beta, more than one move does, so we cut off at once (multicut).
+    let mut singular_move = None;
+    if !ROOT
+        && excluded_move.is_none()
+        && depth >= SINGULAR_DEPTH
+        && (ply as usize) < 2 * depth as usize
+    {
+        if let Some((hash_move, hash_score, score_type, hash_depth)) =
+            table.get_hash_entry(position, ply)
+        {
+            if score_type != ScoreType::UpperBound
+                && hash_depth + SINGULAR_HASH_DEPTH_MARGIN >= depth
+                && !Score::is_mate(hash_score)
+            {
+                let singular_beta = hash_score - SINGULAR_MARGIN * depth as ValueScore;
+                let (score, nodes) = pvs_node::<false, false, false>(
+                    position,
+                    (depth - 1) / 2,
+                    singular_beta - 1,
+                    singular_beta,
+                    table.clone(),
+                    constraint,
+                    history,
+                    ply,
+                    Some(hash_move),
+                );
+                count += nodes;
+
+                if score < singular_beta {
+                    singular_move = Some(hash_move);
+                } else if singular_beta >= beta {
+                    note_tree::<MAIN_THREAD>(constraint, ply, "multicut");
+                    return (beta, count);
+                }
+            }
+        }
+    }
+
+    // The node is improving if its static evaluation rose since our previous move.
+    // If it is not, a cutoff is less likely, so late moves are reduced more.
+    // In check, the static evaluation means little, so the node is assumed to improve.
+    let evaluation =
+        (!is_check).then(|| *static_evaluation.get_or_init(|| evaluate(position, constraint)));
+    let improving = history.note_static_evaluation(ply, evaluation);
+    let is_pv = beta - alpha > 1;
 
     // We need to keep track of the original alpha and best moves, to store
     // the correct node type and move in the hash table later.
     let original_alpha = alpha;
     let mut best_move = *picker.peek().unwrap();
 
+    // Moves that did not cause a cutoff are punished in the history if a later move does.
+    let mut tried_captures = Vec::new();
+    let mut tried_quiets = Vec::new();
+    // How much the main thread spent on its best root move tells how settled that move is.
+    let mut best_move_nodes = 0;
+
     for (i, mov) in picker.enumerate() {
         // Extended futility pruning: discard moves without potential
         if depth <= 2 && i > 0 && !may_be_zug {
-            let move_potential = MAX_POSITIONAL_GAIN * depth as ValueScore
+            let move_potential = constraint.search_params.futility_margins[depth as usize - 1]
                 + mov
                     .flag()
                     .is_capture()
                     .then(|| position.board.piece_at(mov.to()).unwrap_or(Piece::Pawn).value())
                     .unwrap_or(0);
-            if static_evaluation.get_or_init(|| position.value() * position.side_to_move.sign())
-                + move_potential
+            if static_evaluation.get_or_init(|| evaluate(position, constraint)) + move_potential
                 < alpha
             {
+                if MAIN_THREAD {
+                    if let Some(tree_dump) = &constraint.tree_dump {
+                        tree_dump.lock().unwrap().skip(ply, mov, "futility");
+                    }
+                }
+                continue;
+            }
+        }
+
+        // SEE pruning: discard captures that lose too much material to be worth a deep look.
+        if depth <= SEE_PRUNING_DEPTH && i > 0 && !is_check && mov.flag().is_capture() {
+            let margin = SEE_PRUNING_MARGIN * depth as ValueScore
+                + table.get_capture_history(position, mov) / SEE_PRUNING_HISTORY_DIVISOR;
+            if see::see::<true>(mov, &position.board) < -margin {
+                if MAIN_THREAD {
+                    if let Some(tree_dump) = &constraint.tree_dump {
+                        tree_dump.lock().unwrap().skip(ply, mov, "see");
+                    }
+                }
                 continue;
             }
         }
 
         // Late move reduction: we assume our move ordering is good, and are less interested in
-        // expected non-PV nodes.
-        let late_move_reduction =
-            if depth > 2 && !is_check && mov.flag().is_quiet() && i > 0 { 1 } else { 0 };
+        // expected non-PV nodes. Checks and moves that often caused cutoffs are reduced less.
+        let late_move_reduction = if depth > 2 && !is_check && mov.flag().is_quiet() && i > 0 {
+            let reduction = constraint.search_params.late_move_reduction(depth, i)
+                + !is_pv as i16
+                + !improving as i16
+                - mov.gives_check(position) as i16
+                - table.get_quiet_history(position, mov) / LMR_HISTORY_DIVISOR;
+            reduction.clamp(0, depth as i16 - 2) as Depth
+        } else {
+            0
+        };
+
+        if ROOT && MAIN_THREAD {
+            *constraint.root_move.lock().unwrap() = Some((mov, i + 1));
+        }
 
-        let mut new_position = position.make_move(mov);
+        let undo = position.make_move_in_place(mov);
 
-        history.visit_position(&new_position, mov.flag().is_reversible());
+        history.visit_position(position, mov.flag().is_reversible());
         let (score, nodes) = pvs_recurse::<MAIN_THREAD>(
-            &mut new_position,
+            position,
+            mov,
             depth,
             alpha,
             beta,
@@ -243,32 +505,64 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
             ply,
             i > 0,
             late_move_reduction,
-            0,
+            if singular_move == Some(mov) { 1 } else { 0 },
         );
         history.leave_position();
+        position.unmake_move(mov, undo);
 
         count += nodes;
 
         if score > alpha {
             // We found a new best move.
             best_move = mov;
+            best_move_nodes = nodes;
             alpha = score;
+            if is_pv {
+                history.update_pv(ply, mov);
+            }
 
             if score >= beta {
-                if MAIN_THREAD && mov.flag().is_quiet() {
+                // Each thread has its own killer moves and histories, so all of them keep these.
+                if mov.flag().is_quiet() {
                     // Killer moves are prioritized in move ordering.
                     // It assumes that similar "refutation" moves at siblings will be useful.
                     table.put_killer_move(ply, mov);
                 }
 
+                // Deeper cutoffs are more reliable, so they weigh more.
+                let bonus = depth as ValueScore * depth as ValueScore;
+                table.put_capture_history(position, mov, bonus);
+                for capture in &tried_captures {
+                    table.put_capture_history(position, *capture, -bonus);
+                }
+                table.put_quiet_history(position, mov, bonus);
+                for quiet in &tried_quiets {
+                    table.put_quiet_history(position, *quiet, -bonus);
+                }
+
                 // This position is now far too good to be true.
                 // We can safely skip remaining moves.
+                note_tree::<MAIN_THREAD>(constraint, ply, "beta cutoff");
                 break;
             }
         }
+
+        if mov.flag().is_capture() {
+            tried_captures.push(mov);
+        } else {
+            tried_quiets.push(mov);
+        }
     }
 
-    if !constraint.should_stop_search() {
+    if ROOT && MAIN_THREAD && !constraint.should_stop_search() {
+        let effort = best_move_nodes * 1000 / count.max(1);
+        constraint.best_move_effort.store(effort as u16, Ordering::Relaxed);
+    }
+
+    if !constraint.should_stop_search() && excluded_move.is_none() {
+        // A score reached through a repetition draw does not hold for other paths to this position.
+        // The best move is still worth keeping for move ordering, but the score is never trusted.
+        let path_dependent = history.repetition_draws() > repetition_draws;
         table.insert_entry(
             position,
             alpha,
@@ -280,7 +574,7 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
                 ScoreType::Exact
             },
             best_move,
-            depth,
+            if path_dependent { 0 } else { depth },
             ply,
             ROOT,
         );
@@ -289,18 +583,49 @@ fn pvs<const ROOT: bool, const MAIN_THREAD: bool, const ALLOW_NMR: bool>(
     (alpha, count)
 }
 
+pub fn root_move_reaches(
+    position: &Position,
+    mov: Move,
+    depth: Depth,
+    threshold: ValueScore,
+    table: Arc<SearchTable>,
+    constraint: &SearchConstraint,
+) -> bool {
+    let mut new_position = position.make_move(mov);
+    let mut history = BranchHistory::new(constraint.game_history.clone());
+    history.visit_position(&new_position, mov.flag().is_reversible());
+
+    // A zero window search is enough to tell whether the move is above the threshold.
+    let (score, _) = pvs::<false, true, true>(
+        &mut new_position,
+        depth.saturating_sub(1),
+        -threshold,
+        -threshold + 1,
+        table,
+        constraint,
+        &mut history,
+        1,
+        None,
+    );
+    constraint.flush_nodes();
+
+    -score >= threshold
+}
+
 pub fn pvs_aspiration<const MAIN_THREAD: bool>(
     position: &Position,
     guess: ValueScore,
     depth: Depth,
     table: Arc<SearchTable>,
     constraint: &SearchConstraint,
-) -> Option<(Score, usize)> {
+) -> Option<(Score, usize, Vec<Move>)> {
     let depth = depth.min(MAX_DEPTH);
     let mut position = *position;
+    let mut history = BranchHistory::new(constraint.game_history.clone());
     let mut all_count = 0;
-    let mut lower_bound = guess - WINDOW_SIZE;
-    let mut upper_bound = guess + WINDOW_SIZE;
+    let window_size = constraint.search_params.aspiration_window;
+    let mut lower_bound = guess.saturating_sub(window_size);
+    let mut upper_bound = guess.saturating_add(window_size);
 
     for cof in 1.. {
         let (score, count) = pvs::<true, MAIN_THREAD, true>(
@@ -310,10 +635,13 @@ pub fn pvs_aspiration<const MAIN_THREAD: bool>(
             upper_bound,
             table.clone(),
             constraint,
-            &mut BranchHistory(constraint.game_history.clone()),
+            &mut history,
             0,
+            None,
         );
         all_count += count;
+        constraint.nodes.fetch_add(count, Ordering::Relaxed);
+        constraint.flush_nodes();
 
         // Searched failed as result of outside order: this is not valid.
         if constraint.should_stop_search() {
@@ -324,14 +652,14 @@ pub fn pvs_aspiration<const MAIN_THREAD: bool>(
         if score <= lower_bound {
             lower_bound = std::cmp::max(
                 ValueScore::MIN + 1,
-                lower_bound.saturating_sub(WINDOW_SIZE.saturating_mul(cof)),
+                lower_bound.saturating_sub(window_size.saturating_mul(cof)),
             );
             continue;
         }
 
         // Search failed high; increase upper bound and try again
         if score >= upper_bound {
-            upper_bound = upper_bound.saturating_add(WINDOW_SIZE.saturating_mul(cof));
+            upper_bound = upper_bound.saturating_add(window_size.saturating_mul(cof));
             continue;
         }
 
@@ -340,22 +668,11 @@ pub fn pvs_aspiration<const MAIN_THREAD: bool>(
             constraint.signal_root_finished();
         }
 
-        return Some(if Score::is_mate(score) {
-            let plys_to_mate = (MATE_SCORE.abs() - score.abs()) as u8;
-            (
-                Score::Mate(
-                    if score > 0 {
-                        position.side_to_move
-                    } else {
-                        position.side_to_move.opposite()
-                    },
-                    (plys_to_mate + 1) / 2,
-                ),
-                all_count,
-            )
-        } else {
-            (Score::Value(score), all_count)
-        });
+        return Some((
+            Score::from_search(score, position.side_to_move),
+            all_count,
+            history.pv(0).to_vec(),
+        ));
     }
 
     unreachable!()
@@ -364,7 +681,10 @@ pub fn pvs_aspiration<const MAIN_THREAD: bool>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{position::fen::FromFen, search::table::DEFAULT_TABLE_SIZE_MB};
+    use crate::{
+        position::fen::FromFen,
+        search::{history::HistoryEntry, table::DEFAULT_TABLE_SIZE_MB},
+    };
 
     fn expect_pvs_aspiration(
         fen: &str,
@@ -376,9 +696,10 @@ mod tests {
         let table = Arc::new(SearchTable::new(DEFAULT_TABLE_SIZE_MB));
         let constraint = SearchConstraint::default();
 
-        let score =
-            pvs_aspiration::<true>(&position, 0, depth, table.clone(), &constraint).unwrap().0;
+        let (score, _, line) =
+            pvs_aspiration::<true>(&position, 0, depth, table.clone(), &constraint).unwrap();
         let pv = table.get_pv(&position, depth);
+        assert_eq!(line.first(), pv.first());
 
         assert!(pv.len() >= expected_moves.len());
 
@@ -394,6 +715,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn repetition_draws_stay_out_of_table() {
+        // Both sides shuffle back and forth, so that the black king can repeat
+        // the starting position for the third time.
+        let mut game = vec![Position::from_fen("8/7k/8/8/8/8/8/Q6K w - - 0 1").unwrap()];
+        for mov in ["a1a2", "h7h6", "a2a1", "h6h7", "a1a2", "h7h6", "a2a1"] {
+            game.push(game.last().unwrap().make_move_str(mov).unwrap());
+        }
+        let game_history = game
+            .iter()
+            .map(|position| HistoryEntry { hash: position.zobrist_hash(), reversible: true })
+            .collect();
+
+        let table = Arc::new(SearchTable::new(DEFAULT_TABLE_SIZE_MB));
+        let constraint = SearchConstraint { game_history, ..Default::default() };
+        let root = *game.last().unwrap();
+        let (score, _, _) =
+            pvs_aspiration::<true>(&root, 0, 4, table.clone(), &constraint).unwrap();
+
+        // The draw only holds in this game, so it must not be found by other paths.
+        assert_eq!(score, Score::Value(0));
+        assert_eq!(table.get_hash_move(&root).unwrap().to_string(), "h6h7");
+        assert_eq!(table.get_table_score(&root, 1, 0), None);
+    }
+
     #[test]
     fn mate_us_1() {
         expect_pvs_aspiration(
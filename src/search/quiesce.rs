@@ -1,28 +1,47 @@
-use super::{constraint::SearchConstraint, movepick::MovePicker, see, Depth};
+use super::{constraint::SearchConstraint, evalcache::evaluate, movepick::MovePicker, see, Depth};
 use crate::{
-    evaluation::{position::MAX_POSITIONAL_GAIN, Evaluable, ValueScore, MATE_SCORE},
+    evaluation::{position::MAX_POSITIONAL_GAIN, Evaluable, Score, ValueScore},
     position::{board::Piece, Position},
 };
 
+const QUIET_CHECK_PLIES: Depth = 2;
+
 pub fn quiesce(
-    position: &Position,
+    position: &mut Position,
+    alpha: ValueScore,
+    beta: ValueScore,
+    constraint: &SearchConstraint,
+    ply: Depth,
+) -> (ValueScore, usize) {
+    quiesce_staged(position, alpha, beta, constraint, ply, 0)
+}
+
+/// `quiesce_ply` counts the plies since the horizon, where the search is the least selective.
+fn quiesce_staged(
+    position: &mut Position,
     mut alpha: ValueScore,
     beta: ValueScore,
     constraint: &SearchConstraint,
     ply: Depth,
+    quiesce_ply: Depth,
 ) -> (ValueScore, usize) {
+    let first_ply = quiesce_ply == 0;
+
     // Time limit reached
     if constraint.should_stop_search() {
         return (alpha, 1);
     }
+    constraint.note_node();
+    constraint.note_ply_reached(ply);
 
     // If we are in check, the position is certainly not quiet,
     // so we must search all check evasions. Otherwise, search only captures
-    let is_check = position.is_check();
+    let check_info = position.check_info();
+    let is_check = check_info.is_check();
     let static_evaluation = if is_check {
         alpha
     } else {
-        let static_evaluation = position.value() * position.side_to_move.sign();
+        let static_evaluation = evaluate(position, constraint);
 
         // Standing pat: captures are not forced
         alpha = alpha.max(static_evaluation);
@@ -40,11 +59,14 @@ pub fn quiesce(
         static_evaluation
     };
 
-    let mut picker = MovePicker::<true>::new(position, is_check).peekable();
+    // Quiet checks are only searched close to the horizon, since they do not resolve anything.
+    // Letting each side check once catches short mating sequences that the horizon would cut.
+    let quiet_checks = quiesce_ply < QUIET_CHECK_PLIES;
+    let mut picker = MovePicker::<true>::new(position, check_info, quiet_checks).peekable();
 
     // Stable position reached
     if picker.peek().is_none() {
-        let score = if is_check { MATE_SCORE + ply as ValueScore } else { static_evaluation };
+        let score = if is_check { Score::mated_in(ply) } else { static_evaluation };
         return (score, 1);
     }
 
@@ -58,14 +80,36 @@ pub fn quiesce(
                 continue;
             }
 
-            // Static exchange evaluation: if we lose material, there is no point in searching further.
-            if see::see::<true>(mov, &position.board) < 0 {
+            // Static exchange evaluation: past the horizon, only captures of higher-valued
+            // pieces and winning or equal captures are searched, to keep the quiescence tree
+            // bounded in wild positions. Right at the horizon, all captures are searched.
+            let moving_piece = position.board.piece_at(mov.from()).unwrap_or(Piece::Pawn);
+            if !first_ply
+                && captured_piece.value() <= moving_piece.value()
+                && see::see::<true>(mov, &position.board) < 0
+            {
                 continue;
             }
         }
 
-        let (score, nodes) =
-            quiesce(&position.make_move(mov), -beta, -alpha, constraint, ply.saturating_add(1));
+        // Underpromotions are only worth a look right at the horizon.
+        if !is_check
+            && !first_ply
+            && mov.promotion_piece().is_some_and(|piece| piece != Piece::Queen)
+        {
+            continue;
+        }
+
+        let undo = position.make_move_in_place(mov);
+        let (score, nodes) = quiesce_staged(
+            position,
+            -beta,
+            -alpha,
+            constraint,
+            ply.saturating_add(1),
+            quiesce_ply.saturating_add(1),
+        );
+        position.unmake_move(mov, undo);
         let score = -score;
         count += nodes;
 
@@ -16,11 +16,17 @@ fn least_valuable(bb: Bitboard, board: &Board) -> Option<(Piece, Square)> {
 
 pub fn see<const RETURN_EARLY: bool>(mov: Move, board: &Board) -> ValueScore {
     let (piece, color) = board.piece_color_at(mov.from()).unwrap();
-    let their_piece = board.piece_at(mov.to()).unwrap_or(Piece::Pawn);
+    let is_capture = mov.flag().is_capture();
+    let captured_value = match board.piece_at(mov.to()) {
+        Some(their_piece) => their_piece.value(),
+        // En passant captures a pawn away from the destination square.
+        None if is_capture => Piece::Pawn.value(),
+        None => 0,
+    };
 
     // If we are only querying a positive SEE, we can return immediately
     // if we are capturing a more valuable piece.
-    if RETURN_EARLY && (piece == Piece::Pawn || piece.value() <= their_piece.value()) {
+    if RETURN_EARLY && is_capture && (piece == Piece::Pawn || piece.value() <= captured_value) {
         return 0;
     }
 
@@ -32,7 +38,7 @@ pub fn see<const RETURN_EARLY: bool>(mov: Move, board: &Board) -> ValueScore {
 
     // Make our move.
     let mut on_square = piece;
-    let mut score = their_piece.value();
+    let mut score = captured_value;
     let mut current_color = color.opposite();
     let mut current_sign = -1;
     board.clear_square(mov.from());
@@ -164,4 +170,18 @@ mod tests {
         assert_eq!(super::see::<false>(*mov, &position.board), Piece::Pawn.value());
         assert!(super::see::<true>(*mov, &position.board) >= 0);
     }
+
+    #[test]
+    fn see_quiet() {
+        let position = Position::from_fen("4k3/3p4/8/8/3N4/8/8/4K3 w - - 0 1").unwrap();
+        let moves = position.moves(MoveStage::All);
+
+        let mov = moves.iter().find(|mov| mov.to_string() == "d4e6").unwrap();
+        assert_eq!(super::see::<false>(*mov, &position.board), -Piece::Knight.value());
+        assert!(super::see::<true>(*mov, &position.board) < 0);
+
+        let mov = moves.iter().find(|mov| mov.to_string() == "d4f5").unwrap();
+        assert_eq!(super::see::<false>(*mov, &position.board), 0);
+        assert!(super::see::<true>(*mov, &position.board) >= 0);
+    }
 }
@@ -0,0 +1,133 @@
+use crate::{evaluation::Score, moves::Move, position::Color};
+use rand::{rngs::StdRng, Rng};
+
+pub const MIN_ELO: u16 = 1000;
+pub const MAX_ELO: u16 = 2600;
+/// Skill level at which the strength is not limited.
+pub const MAX_SKILL_LEVEL: u8 = 20;
+/// Root lines searched with a limited strength, for the weaker moves to be picked from.
+pub const STRENGTH_LINES: u8 = 4;
+
+const NODES_AT_MIN_ELO: f64 = 400.0;
+/// Rating gained each time the nodes double, which is about the gain at low depths.
+const ELO_PER_NODES_DOUBLING: f64 = 120.0;
+/// Spread of the softmax over the root scores, in centipawns, at either end of the ratings.
+const MAX_TEMPERATURE: f64 = 150.0;
+const MIN_TEMPERATURE: f64 = 5.0;
+/// Value given to mates when picking a move, far above any regular score.
+const MATE_PICK_VALUE: f64 = 10000.0;
+
+/// Playing strength below the full one, given as a rating. Weaker play comes from a smaller
+/// search and from picking among the best root moves at random, favoring the better ones.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrengthLimit {
+    elo: u16,
+}
+
+impl StrengthLimit {
+    pub fn from_elo(elo: u16) -> Self {
+        Self { elo: elo.clamp(MIN_ELO, MAX_ELO) }
+    }
+
+    /// Strength for a skill level, spread evenly over the ratings.
+    /// The top level does not limit the strength.
+    pub fn from_skill_level(level: u8) -> Option<Self> {
+        (level < MAX_SKILL_LEVEL).then(|| {
+            let range = (MAX_ELO - MIN_ELO) as u32;
+            Self::from_elo(MIN_ELO + (range * level as u32 / MAX_SKILL_LEVEL as u32) as u16)
+        })
+    }
+
+    /// Share of the rating range above the minimum, from 0 to 1.
+    fn level(&self) -> f64 {
+        (self.elo - MIN_ELO) as f64 / (MAX_ELO - MIN_ELO) as f64
+    }
+
+    /// Nodes the search may take for a move.
+    pub fn nodes(&self) -> usize {
+        let doublings = (self.elo - MIN_ELO) as f64 / ELO_PER_NODES_DOUBLING;
+        (NODES_AT_MIN_ELO * doublings.exp2()) as usize
+    }
+
+    fn temperature(&self) -> f64 {
+        MAX_TEMPERATURE + (MIN_TEMPERATURE - MAX_TEMPERATURE) * self.level()
+    }
+
+    /// Picks one of the root moves with a softmax over their scores, so that moves much worse
+    /// than the best are seldom played, and more so the higher the rating.
+    pub fn pick_move(
+        &self,
+        lines: &[(Move, Score)],
+        side_to_move: Color,
+        rng: &mut StdRng,
+    ) -> Move {
+        let values = lines
+            .iter()
+            .map(|(_, score)| match score {
+                Score::Value(value) => *value as f64,
+                Score::Mate(color, _) if *color == side_to_move => MATE_PICK_VALUE,
+                Score::Mate(_, _) => -MATE_PICK_VALUE,
+            })
+            .collect::<Vec<_>>();
+
+        // Shifting by the best value keeps the weights in range.
+        let best_value = values.iter().copied().fold(f64::MIN, f64::max);
+        let weights = values
+            .iter()
+            .map(|value| ((value - best_value) / self.temperature()).exp())
+            .collect::<Vec<_>>();
+
+        let mut pick = rng.gen_range(0.0..weights.iter().sum::<f64>());
+        for ((mov, _), weight) in lines.iter().zip(weights) {
+            if pick < weight {
+                return *mov;
+            }
+            pick -= weight;
+        }
+        lines[0].0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StrengthLimit, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+    use crate::{evaluation::Score, moves::Move, position::Color};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn skill_levels_span_the_ratings() {
+        assert_eq!(StrengthLimit::from_skill_level(0), Some(StrengthLimit::from_elo(MIN_ELO)));
+        assert_eq!(StrengthLimit::from_skill_level(MAX_SKILL_LEVEL), None);
+        assert_eq!(StrengthLimit::from_elo(0), StrengthLimit::from_elo(MIN_ELO));
+        assert_eq!(StrengthLimit::from_elo(u16::MAX), StrengthLimit::from_elo(MAX_ELO));
+
+        let nodes = (0..MAX_SKILL_LEVEL)
+            .map(|level| StrengthLimit::from_skill_level(level).unwrap().nodes())
+            .collect::<Vec<_>>();
+        assert!(nodes.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn weaker_play_picks_worse_moves_more_often() {
+        let [best, second, worst] = [1, 2, 3].map(Move::new_raw);
+        let lines =
+            [(best, Score::Value(50)), (second, Score::Value(20)), (worst, Score::Value(-300))];
+
+        let picks = |elo| {
+            let mut rng = StdRng::seed_from_u64(7);
+            let strength = StrengthLimit::from_elo(elo);
+            (0..1000)
+                .map(|_| strength.pick_move(&lines, Color::White, &mut rng))
+                .filter(|mov| *mov != best)
+                .count()
+        };
+        assert!(picks(MIN_ELO) > picks(MAX_ELO));
+        assert!(picks(MIN_ELO) < 1000);
+
+        // Mates are never given up for regular moves.
+        let mut rng = StdRng::seed_from_u64(7);
+        let lines = [(second, Score::Value(100)), (best, Score::Mate(Color::White, 2))];
+        let strength = StrengthLimit::from_elo(MIN_ELO);
+        assert!((0..100).all(|_| strength.pick_move(&lines, Color::White, &mut rng) == best));
+    }
+}
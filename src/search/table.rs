@@ -2,23 +2,42 @@ use super::{Depth, MAX_DEPTH};
 use crate::{
     evaluation::{Score, ValueScore},
     moves::Move,
-    position::Position,
+    position::{board::Piece, Position},
 };
 use std::{
+    alloc::{self, Layout},
     array,
-    mem::transmute,
+    mem::{size_of, transmute},
+    ops::Deref,
+    ptr::{self, NonNull},
+    slice,
     sync::{
-        atomic::{AtomicU16, AtomicU64, Ordering},
-        RwLock,
+        atomic::{AtomicBool, AtomicI16, AtomicU16, AtomicU64, Ordering},
+        Arc, RwLock,
     },
+    thread,
 };
 
-pub const MAX_TABLE_SIZE_MB: usize = 2048;
+pub const MAX_TABLE_SIZE_MB: usize = 32768;
 pub const MIN_TABLE_SIZE_MB: usize = 1;
 pub const DEFAULT_TABLE_SIZE_MB: usize = 64;
 
 const NULL_KILLER: u16 = u16::MAX;
-const NULL_TT_ENTRY: u64 = u64::MAX;
+// No stored entry is all zeros, since its move would go from a1 to a1.
+const NULL_TT_ENTRY: u64 = 0;
+
+// The buckets are aligned to cache lines, or to huge pages when these are asked for.
+const CACHE_LINE_SIZE: usize = 64;
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+// Smaller tables are emptied by the calling thread alone, since spawning threads would take
+// longer than the work itself, and they are cleared often, such as once per analyzed position.
+const PARALLEL_CLEAR_MIN_SIZE: usize = 64 * 1024 * 1024;
+
+// History scores stay within this bound, so that recent cutoffs weigh more.
+pub const MAX_HISTORY: ValueScore = 4096;
+const CAPTURE_HISTORY_SIZE: usize = 6 * 64 * 6;
+const QUIET_HISTORY_SIZE: usize = 2 * 64 * 64;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ScoreType {
@@ -61,10 +80,6 @@ impl TableEntry {
         unsafe { transmute::<TableEntry, u64>(*self) }
     }
 
-    pub fn shift_score(&self, shift: ValueScore) -> Self {
-        TableEntry { score: self.score + shift, ..*self }
-    }
-
     fn score_type(&self) -> ScoreType {
         match self.data & 3 {
             0 => ScoreType::Exact,
@@ -87,33 +102,154 @@ impl TableEntry {
     }
 }
 
+const BUCKET_SIZE: usize = 4;
+
+/// Entries sharing an index of the table. Aligned so that a bucket never straddles
+/// cache lines, and all its entries are fetched at once.
+#[repr(align(32))]
+struct Bucket([AtomicU64; BUCKET_SIZE]);
+
+impl Bucket {
+    fn load(&self, slot: usize) -> Option<TableEntry> {
+        let entry = self.0[slot].load(Ordering::Relaxed);
+        if entry == NULL_TT_ENTRY {
+            None
+        } else {
+            Some(TableEntry::from_raw(entry))
+        }
+    }
+
+    fn store(&self, slot: usize, entry: TableEntry) {
+        self.0[slot].store(entry.raw(), Ordering::Relaxed)
+    }
+}
+
+/// The buckets of the table, allocated by hand to control their alignment, and emptied by
+/// several threads at once, which matters for tables of gigabytes.
+struct Buckets {
+    ptr: NonNull<Bucket>,
+    len: usize,
+    layout: Layout,
+}
+
+// The buckets are only ever accessed through atomics.
+unsafe impl Send for Buckets {}
+unsafe impl Sync for Buckets {}
+
+impl Buckets {
+    fn new(len: usize, huge_pages: bool) -> Self {
+        let align = if huge_pages { HUGE_PAGE_SIZE } else { CACHE_LINE_SIZE };
+        let layout = Layout::from_size_align(len * size_of::<Bucket>(), align).unwrap();
+        let Some(ptr) = NonNull::new(unsafe { alloc::alloc(layout) } as *mut Bucket) else {
+            alloc::handle_alloc_error(layout)
+        };
+
+        if huge_pages {
+            advise_huge_pages(ptr.as_ptr() as *mut u8, layout.size());
+        }
+
+        // Writing every byte also maps every page now, instead of during the search.
+        let address = ptr.as_ptr() as usize;
+        let threads = number_threads(layout.size());
+        if threads == 1 {
+            unsafe { ptr::write_bytes(ptr.as_ptr() as *mut u8, 0, layout.size()) };
+            return Self { ptr, len, layout };
+        }
+        let chunk_size = layout.size().div_ceil(threads);
+        thread::scope(|s| {
+            for start in (0..layout.size()).step_by(chunk_size) {
+                let len = chunk_size.min(layout.size() - start);
+                s.spawn(move || unsafe { ptr::write_bytes((address + start) as *mut u8, 0, len) });
+            }
+        });
+
+        Self { ptr, len, layout }
+    }
+
+    fn clear(&self) {
+        let threads = number_threads(self.layout.size());
+        if threads == 1 {
+            clear_buckets(self);
+            return;
+        }
+        thread::scope(|s| {
+            for chunk in self.chunks(self.len.div_ceil(threads)) {
+                s.spawn(move || clear_buckets(chunk));
+            }
+        });
+    }
+}
+
+impl Deref for Buckets {
+    type Target = [Bucket];
+
+    fn deref(&self) -> &[Bucket] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Buckets {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, self.layout) }
+    }
+}
+
+fn clear_buckets(buckets: &[Bucket]) {
+    buckets
+        .iter()
+        .flat_map(|bucket| &bucket.0)
+        .for_each(|entry| entry.store(NULL_TT_ENTRY, Ordering::Relaxed));
+}
+
+/// Threads to empty a table of the given size in bytes with.
+fn number_threads(size: usize) -> usize {
+    if size < PARALLEL_CLEAR_MIN_SIZE {
+        return 1;
+    }
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Asks the kernel to back the memory with transparent huge pages, which need fewer
+/// TLB entries. It is only a hint, and other systems keep regular pages.
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(ptr: *mut u8, size: usize) {
+    const MADV_HUGEPAGE: i32 = 14;
+    extern "C" {
+        fn madvise(addr: *mut u8, len: usize, advice: i32) -> i32;
+    }
+    unsafe {
+        madvise(ptr, size, MADV_HUGEPAGE);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_ptr: *mut u8, _size: usize) {}
+
+/// Entries are atomic and the table is never resized, so it is probed and written by
+/// every search thread without locking. Resizing means building a new `SearchTable`.
 struct TranspositionTable {
-    data: Vec<AtomicU64>,
-    age: bool,
+    data: Buckets,
+    age: AtomicBool,
 }
 
 impl TranspositionTable {
-    pub fn new(size_mb: usize) -> Self {
+    pub fn new(size_mb: usize, huge_pages: bool) -> Self {
         let data_len = Self::calculate_data_len(size_mb);
-        Self { data: (0..data_len).map(|_| AtomicU64::new(NULL_TT_ENTRY)).collect(), age: false }
+        Self { data: Buckets::new(data_len, huge_pages), age: AtomicBool::new(false) }
     }
 
     fn calculate_data_len(size_mb: usize) -> usize {
-        let element_size = std::mem::size_of::<Option<TableEntry>>();
+        let element_size = size_of::<Bucket>();
         let size = size_mb * 1024 * 1024;
         size / element_size
     }
 
-    pub fn set_size(&mut self, size_mb: usize) {
-        let data_len = Self::calculate_data_len(size_mb);
-        self.data = (0..data_len).map(|_| AtomicU64::new(NULL_TT_ENTRY)).collect();
-    }
-
     pub fn hashfull_millis(&self) -> usize {
         // The hash keys are disperse, so a small sample should suffice for a relevant statistic.
         self.data
             .iter()
-            .take(10000)
+            .take(10000 / BUCKET_SIZE)
+            .flat_map(|bucket| &bucket.0)
             .filter(|entry| entry.load(Ordering::Relaxed) != NULL_TT_ENTRY)
             .count()
             / 10
@@ -121,74 +257,124 @@ impl TranspositionTable {
 
     pub fn get(&self, position: &Position) -> Option<TableEntry> {
         let hash = position.zobrist_hash();
-        let entry = self.load_tt_entry(hash as usize % self.data.len());
-        entry.filter(|entry| entry.same_hash(hash))
+        let bucket = &self.data[hash as usize % self.data.len()];
+        (0..BUCKET_SIZE).filter_map(|slot| bucket.load(slot)).find(|entry| entry.same_hash(hash))
     }
 
+    /// Stores the entry in its bucket, over an older entry of the same position unless that one
+    /// is deeper and from the current search. Otherwise, an empty slot is taken, or else the
+    /// entry most worth losing: one from a previous search, and then the shallowest.
     pub fn insert(&self, position: &Position, entry: TableEntry, force: bool) {
         let hash = position.zobrist_hash();
-        let index = hash as usize % self.data.len();
-
-        if !force {
-            if let Some(old_entry) = self.load_tt_entry(index) {
-                if old_entry.depth() > entry.depth() && old_entry.age() == entry.age() {
-                    return;
-                }
+        let bucket = &self.data[hash as usize % self.data.len()];
+
+        if let Some((slot, old_entry)) = (0..BUCKET_SIZE)
+            .filter_map(|slot| bucket.load(slot).map(|old_entry| (slot, old_entry)))
+            .find(|(_, old_entry)| old_entry.same_hash(hash))
+        {
+            if !force && old_entry.depth() > entry.depth() && old_entry.age() == entry.age() {
+                return;
             }
+            bucket.store(slot, entry);
+            return;
         }
 
-        self.store_tt_entry(index, entry);
-    }
-
-    fn load_tt_entry(&self, index: usize) -> Option<TableEntry> {
-        let entry = self.data[index].load(Ordering::Relaxed);
-        if entry == NULL_TT_ENTRY {
-            None
-        } else {
-            Some(TableEntry::from_raw(entry))
-        }
-    }
-
-    fn store_tt_entry(&self, index: usize, entry: TableEntry) {
-        self.data[index].store(entry.raw(), Ordering::Relaxed)
+        let age = self.age.load(Ordering::Relaxed);
+        let victim = (0..BUCKET_SIZE)
+            .min_by_key(|slot| match bucket.load(*slot) {
+                None => (0, 0),
+                Some(old_entry) if old_entry.age() != age => (1, old_entry.depth()),
+                Some(old_entry) => (2, old_entry.depth()),
+            })
+            .unwrap();
+        bucket.store(victim, entry);
     }
 }
 
+/// The transposition table, shared by all search threads, along with the killer moves and
+/// histories of one thread. The tables of the helper threads share the transposition table,
+/// but keep their own move ordering data, so that threads do not overwrite each other's.
 pub struct SearchTable {
-    transposition: RwLock<TranspositionTable>,
+    transposition: Arc<TranspositionTable>,
     killer_moves: [AtomicU16; 3 * (MAX_DEPTH + 1) as usize],
+    capture_history: [AtomicI16; CAPTURE_HISTORY_SIZE],
+    quiet_history: [AtomicI16; QUIET_HISTORY_SIZE],
+    helpers: RwLock<Vec<Arc<SearchTable>>>,
 }
 
 impl SearchTable {
     pub fn new(size_mb: usize) -> Self {
+        Self::with_huge_pages(size_mb, false)
+    }
+
+    /// A table whose transposition entries are backed by huge pages, where supported.
+    pub fn with_huge_pages(size_mb: usize, huge_pages: bool) -> Self {
+        Self::sharing(Arc::new(TranspositionTable::new(size_mb, huge_pages)))
+    }
+
+    fn sharing(transposition: Arc<TranspositionTable>) -> Self {
         Self {
-            transposition: RwLock::new(TranspositionTable::new(size_mb)),
+            transposition,
             killer_moves: array::from_fn(|_| AtomicU16::new(NULL_KILLER)),
+            capture_history: array::from_fn(|_| AtomicI16::new(0)),
+            quiet_history: array::from_fn(|_| AtomicI16::new(0)),
+            helpers: RwLock::new(Vec::new()),
         }
     }
 
+    /// The table of the search thread with the given index, the main thread being the first.
+    /// Helper tables are created on first use, and kept for the next searches.
+    pub fn for_thread(self: &Arc<Self>, thread: usize) -> Arc<Self> {
+        if thread == 0 {
+            return self.clone();
+        }
+
+        if let Some(helper) = self.helpers.read().unwrap().get(thread - 1) {
+            return helper.clone();
+        }
+
+        let mut helpers = self.helpers.write().unwrap();
+        while helpers.len() < thread {
+            helpers.push(Arc::new(Self::sharing(self.transposition.clone())));
+        }
+        helpers[thread - 1].clone()
+    }
+
     pub fn prepare_for_new_search(&self) {
         // We flip the age bit to be able to replace all entries from previous searches.
         // This is both faster and more effective than clearing the table completely,
         // since we can profit from older entries that are still valid.
-        let mut tt = self.transposition.write().unwrap();
-        tt.age = !tt.age;
+        self.transposition.age.fetch_xor(true, Ordering::Relaxed);
 
         // Killer moves are no longer at the same ply, so we clear them.
-        self.killer_moves.iter().for_each(|entry| entry.store(NULL_KILLER, Ordering::Relaxed));
-    }
-
-    pub fn set_size(&self, size_mb: usize) {
-        self.transposition.write().unwrap().set_size(size_mb)
+        self.for_each_with_helpers(|table| {
+            table.killer_moves.iter().for_each(|entry| entry.store(NULL_KILLER, Ordering::Relaxed))
+        });
     }
 
     pub fn get_hash_move(&self, position: &Position) -> Option<Move> {
         self.transposition
-            .read()
-            .unwrap()
             .get(position)
             .map(|entry| entry.best_move)
-            .filter(|mov| mov.is_pseudo_legal(position))
+            .filter(|mov| mov.is_legal(position))
+    }
+
+    /// The hash move, with its score, score type and the depth it was searched to.
+    pub fn get_hash_entry(
+        &self,
+        position: &Position,
+        ply: Depth,
+    ) -> Option<(Move, ValueScore, ScoreType, Depth)> {
+        self.transposition.get(position).filter(|entry| entry.best_move.is_legal(position)).map(
+            |entry| {
+                (
+                    entry.best_move,
+                    Score::to_root_relative(entry.score, ply),
+                    entry.score_type(),
+                    entry.depth(),
+                )
+            },
+        )
     }
 
     pub fn get_table_score(
@@ -198,8 +384,6 @@ impl SearchTable {
         ply: Depth,
     ) -> Option<(ValueScore, ScoreType)> {
         self.transposition
-            .read()
-            .unwrap()
             .get(position)
             .and_then(|entry| {
                 if entry.depth() >= depth {
@@ -208,15 +392,8 @@ impl SearchTable {
                     None
                 }
             })
-            .map(|(score, score_type)| {
-                // Adjust the score to the current distance from the root.
-                if Score::is_mate(score) {
-                    let shift = if score < 0 { ply as ValueScore } else { -(ply as ValueScore) };
-                    (score + shift, score_type)
-                } else {
-                    (score, score_type)
-                }
-            })
+            // Adjust the score to the current distance from the root.
+            .map(|(score, score_type)| (Score::to_root_relative(score, ply), score_type))
     }
 
     pub fn insert_entry(
@@ -229,25 +406,17 @@ impl SearchTable {
         ply: Depth,
         is_root: bool,
     ) {
-        let tt = self.transposition.read().unwrap();
+        // The score stored should be independent of the path from root to this node,
+        // and only depend on the number of moves to mate.
         let entry = TableEntry::new(
-            score,
+            Score::to_node_relative(score, ply),
             score_type,
             best_move,
             depth,
             position.zobrist_hash(),
-            self.transposition.read().unwrap().age,
+            self.transposition.age.load(Ordering::Relaxed),
         );
-
-        // The score stored should be independent of the path from root to this node,
-        // and only depend on the number of moves to mate.
-        if Score::is_mate(entry.score) {
-            let shift = if entry.score > 0 { ply as ValueScore } else { -(ply as ValueScore) };
-            let entry = entry.shift_score(shift);
-            tt.insert(position, entry, is_root);
-        } else {
-            tt.insert(position, entry, is_root);
-        }
+        self.transposition.insert(position, entry, is_root);
     }
 
     pub fn put_killer_move(&self, ply: Depth, mov: Move) {
@@ -270,6 +439,33 @@ impl SearchTable {
         [self.load_killer(index), self.load_killer(index + 1)]
     }
 
+    /// Rewards a capture that caused a cutoff, or punishes one that did not, by the bonus.
+    /// Scores are kept bounded by shrinking them the closer they are to the bound.
+    pub fn put_capture_history(&self, position: &Position, mov: Move, bonus: ValueScore) {
+        if let Some(index) = capture_history_index(position, mov) {
+            add_history_bonus(&self.capture_history[index], bonus);
+        }
+    }
+
+    /// How often the capture caused cutoffs, from `-MAX_HISTORY` to `MAX_HISTORY`.
+    pub fn get_capture_history(&self, position: &Position, mov: Move) -> ValueScore {
+        capture_history_index(position, mov)
+            .map_or(0, |index| self.capture_history[index].load(Ordering::Relaxed) as ValueScore)
+    }
+
+    /// Like `put_capture_history`, for the moves that capture nothing.
+    pub fn put_quiet_history(&self, position: &Position, mov: Move, bonus: ValueScore) {
+        if let Some(index) = quiet_history_index(position, mov) {
+            add_history_bonus(&self.quiet_history[index], bonus);
+        }
+    }
+
+    /// How often the quiet move caused cutoffs, from `-MAX_HISTORY` to `MAX_HISTORY`.
+    pub fn get_quiet_history(&self, position: &Position, mov: Move) -> ValueScore {
+        quiet_history_index(position, mov)
+            .map_or(0, |index| self.quiet_history[index].load(Ordering::Relaxed) as ValueScore)
+    }
+
     pub fn get_pv(&self, position: &Position, mut depth: Depth) -> Vec<Move> {
         let mut pv = Vec::new();
         let mut position = *position;
@@ -287,17 +483,25 @@ impl SearchTable {
     }
 
     pub fn hashfull_millis(&self) -> usize {
-        self.transposition.read().unwrap().hashfull_millis()
+        self.transposition.hashfull_millis()
+    }
+
+    pub fn size_mb(&self) -> usize {
+        self.transposition.data.len() * size_of::<Bucket>() / (1024 * 1024)
     }
 
     pub fn clear(&self) {
-        self.transposition
-            .write()
-            .unwrap()
-            .data
-            .iter_mut()
-            .for_each(|entry| *entry = AtomicU64::new(NULL_TT_ENTRY));
-        self.killer_moves.iter().for_each(|entry| entry.store(NULL_KILLER, Ordering::Relaxed));
+        self.transposition.data.clear();
+        self.for_each_with_helpers(|table| {
+            table.killer_moves.iter().for_each(|entry| entry.store(NULL_KILLER, Ordering::Relaxed));
+            table.capture_history.iter().for_each(|entry| entry.store(0, Ordering::Relaxed));
+            table.quiet_history.iter().for_each(|entry| entry.store(0, Ordering::Relaxed));
+        });
+    }
+
+    fn for_each_with_helpers(&self, f: impl Fn(&Self)) {
+        f(self);
+        self.helpers.read().unwrap().iter().for_each(|helper| f(helper));
     }
 
     fn load_killer(&self, index: usize) -> Option<Move> {
@@ -314,21 +518,53 @@ impl SearchTable {
     }
 }
 
+fn add_history_bonus(entry: &AtomicI16, bonus: ValueScore) {
+    let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY) as i32;
+    let score = entry.load(Ordering::Relaxed) as i32;
+    let score = score + bonus - score * bonus.abs() / MAX_HISTORY as i32;
+    entry.store(score as i16, Ordering::Relaxed);
+}
+
+/// Captures are told apart by the moving piece, the destination and the captured piece.
+fn capture_history_index(position: &Position, mov: Move) -> Option<usize> {
+    if !mov.flag().is_capture() {
+        return None;
+    }
+    let piece = position.board.piece_at(mov.from())?;
+    // En passant captures a pawn away from the destination square.
+    let captured = position.board.piece_at(mov.to()).unwrap_or(Piece::Pawn);
+    Some((piece as usize * 64 + mov.to() as usize) * 6 + captured as usize)
+}
+
+/// Quiet moves are told apart by the side to move and the origin and destination squares.
+fn quiet_history_index(position: &Position, mov: Move) -> Option<usize> {
+    if mov.flag().is_capture() {
+        return None;
+    }
+    Some((position.side_to_move as usize * 64 + mov.from() as usize) * 64 + mov.to() as usize)
+}
+
 #[cfg(test)]
 mod tests {
-    use std::sync::atomic::Ordering;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
 
-    use super::{SearchTable, TableEntry, TranspositionTable};
+    use super::{
+        Buckets, SearchTable, TableEntry, TranspositionTable, BUCKET_SIZE, CACHE_LINE_SIZE,
+        HUGE_PAGE_SIZE,
+    };
     use crate::{
-        moves::Move,
+        moves::{gen::MoveStage, Move, MoveFlag},
         position::{
-            fen::{FromFen, START_FEN},
+            fen::{FromFen, KIWIPETE_WHITE_FEN, START_FEN},
             square::Square,
-            Position,
+            Color, Position,
         },
         search::{
-            table::{ScoreType, NULL_KILLER, NULL_TT_ENTRY},
-            MAX_DEPTH,
+            table::{ScoreType, MAX_HISTORY, NULL_KILLER, NULL_TT_ENTRY},
+            Depth, MAX_DEPTH,
         },
     };
 
@@ -366,10 +602,10 @@ mod tests {
 
     #[test]
     fn tt_raw_contents() {
-        let table = TranspositionTable::new(1);
+        let table = TranspositionTable::new(1, false);
         let position = Position::from_fen(START_FEN).unwrap();
 
-        assert_eq!(table.data[0].load(Ordering::Relaxed), NULL_TT_ENTRY);
+        assert_eq!(table.data[0].0[0].load(Ordering::Relaxed), NULL_TT_ENTRY);
         assert_eq!(table.get(&position), None);
 
         let first_move = Move::new(Square::E2, Square::E4, crate::moves::MoveFlag::DoublePawnPush);
@@ -379,12 +615,94 @@ mod tests {
         table.insert(&position, first_move_entry, false);
 
         assert_eq!(
-            table.data[position.zobrist_hash() as usize % table.data.len()].load(Ordering::Relaxed),
+            table.data[position.zobrist_hash() as usize % table.data.len()].0[0]
+                .load(Ordering::Relaxed),
             first_move_entry.raw()
         );
         assert_eq!(table.get(&position).unwrap().best_move, first_move);
     }
 
+    #[test]
+    fn buckets_are_aligned_and_empty() {
+        for (huge_pages, align) in [(false, CACHE_LINE_SIZE), (true, HUGE_PAGE_SIZE)] {
+            let buckets = Buckets::new(1000, huge_pages);
+            assert_eq!(buckets.as_ptr() as usize % align, 0);
+            assert!(buckets.iter().all(|bucket| bucket.load(0).is_none()));
+
+            buckets[999]
+                .store(0, TableEntry::new(1, ScoreType::Exact, Move::new_raw(1), 1, 0, true));
+            buckets.clear();
+            assert!(buckets[999].load(0).is_none());
+        }
+    }
+
+    #[test]
+    fn helper_tables_share_the_transposition_table() {
+        let table = Arc::new(SearchTable::new(1));
+        let helper = table.for_thread(2);
+        assert!(Arc::ptr_eq(&table.for_thread(0), &table));
+        assert!(Arc::ptr_eq(&table.for_thread(2), &helper));
+        assert!(!Arc::ptr_eq(&table.for_thread(1), &helper));
+
+        let position = Position::from_fen(START_FEN).unwrap();
+        let mov = Move::new(Square::E2, Square::E4, MoveFlag::DoublePawnPush);
+        helper.insert_entry(&position, 0, ScoreType::Exact, mov, 1, 0, true);
+        helper.put_killer_move(0, mov);
+        assert_eq!(table.get_hash_move(&position), Some(mov));
+        assert_eq!(table.get_killers(0), [None, None]);
+
+        table.clear();
+        assert_eq!(helper.get_hash_move(&position), None);
+        assert_eq!(helper.get_killers(0), [None, None]);
+    }
+
+    #[test]
+    fn bucket_replacement() {
+        // A single bucket, so that every position competes for it.
+        let table =
+            TranspositionTable { data: Buckets::new(1, false), age: AtomicBool::new(false) };
+        let start_position = Position::from_fen(START_FEN).unwrap();
+        let positions = start_position
+            .moves(MoveStage::All)
+            .into_iter()
+            .map(|mov| start_position.make_move(mov))
+            .take(BUCKET_SIZE + 2)
+            .collect::<Vec<_>>();
+        let entry = |position: &Position, depth, age| {
+            TableEntry::new(
+                0,
+                ScoreType::Exact,
+                Move::new_raw(0),
+                depth,
+                position.zobrist_hash(),
+                age,
+            )
+        };
+
+        // The entry of a previous search goes first, even if deeper.
+        table.insert(&positions[0], entry(&positions[0], 9, true), false);
+        for (depth, position) in positions[1..BUCKET_SIZE].iter().enumerate() {
+            table.insert(position, entry(position, depth as Depth + 2, false), false);
+        }
+        table.insert(&positions[BUCKET_SIZE], entry(&positions[BUCKET_SIZE], 1, false), false);
+        assert_eq!(table.get(&positions[0]), None);
+        assert!(table.get(&positions[BUCKET_SIZE]).is_some());
+
+        // Then the shallowest entry.
+        table.insert(
+            &positions[BUCKET_SIZE + 1],
+            entry(&positions[BUCKET_SIZE + 1], 5, false),
+            false,
+        );
+        assert_eq!(table.get(&positions[BUCKET_SIZE]), None);
+        assert!(positions[1..BUCKET_SIZE].iter().all(|position| table.get(position).is_some()));
+        assert!(table.get(&positions[BUCKET_SIZE + 1]).is_some());
+
+        // A shallower entry of a stored position does not replace it.
+        table.insert(&positions[1], entry(&positions[1], 0, false), false);
+        assert_eq!(table.get(&positions[1]).unwrap().depth(), 2);
+    }
+
     #[test]
     fn killers_raw_contents() {
         let table = SearchTable::new(1);
@@ -412,4 +730,78 @@ mod tests {
         assert_eq!(table.killer_moves[1].load(Ordering::Relaxed), third_move.raw());
         assert_eq!(table.get_killers(0), [Some(second_move), Some(third_move)]);
     }
+
+    #[test]
+    fn capture_history_is_bounded() {
+        let table = SearchTable::new(1);
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let capture = Move::new(Square::E5, Square::F7, MoveFlag::Capture);
+        let other_capture = Move::new(Square::E5, Square::G6, MoveFlag::Capture);
+        let quiet = Move::new(Square::E5, Square::D3, MoveFlag::Quiet);
+
+        assert_eq!(table.get_capture_history(&position, capture), 0);
+
+        table.put_capture_history(&position, capture, 100);
+        assert_eq!(table.get_capture_history(&position, capture), 100);
+        assert_eq!(table.get_capture_history(&position, other_capture), 0);
+
+        for _ in 0..1000 {
+            table.put_capture_history(&position, capture, 1000);
+            table.put_capture_history(&position, other_capture, -1000);
+        }
+        assert!((0..=MAX_HISTORY).contains(&table.get_capture_history(&position, capture)));
+        assert!(table.get_capture_history(&position, capture) > MAX_HISTORY / 2);
+        assert!(table.get_capture_history(&position, other_capture) < -MAX_HISTORY / 2);
+
+        table.put_capture_history(&position, quiet, 1000);
+        assert_eq!(table.get_capture_history(&position, quiet), 0);
+
+        table.clear();
+        assert_eq!(table.get_capture_history(&position, capture), 0);
+    }
+
+    #[test]
+    fn quiet_history_ignores_captures() {
+        let table = SearchTable::new(1);
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+        let quiet = Move::new(Square::E5, Square::D3, MoveFlag::Quiet);
+        let capture = Move::new(Square::E5, Square::F7, MoveFlag::Capture);
+
+        table.put_quiet_history(&position, quiet, 100);
+        table.put_quiet_history(&position, capture, 100);
+        assert_eq!(table.get_quiet_history(&position, quiet), 100);
+        assert_eq!(table.get_quiet_history(&position, capture), 0);
+        assert_eq!(table.get_capture_history(&position, capture), 0);
+
+        let black_position = Position { side_to_move: Color::Black, ..position };
+        assert_eq!(table.get_quiet_history(&black_position, quiet), 0);
+
+        table.clear();
+        assert_eq!(table.get_quiet_history(&position, quiet), 0);
+    }
+
+    #[test]
+    fn hash_move_collisions() {
+        let table = SearchTable::new(1);
+        let position = Position::from_fen(KIWIPETE_WHITE_FEN).unwrap();
+
+        // Entries with the same key as the position, but holding moves from elsewhere.
+        let colliding_moves = [
+            Move::new(Square::C3, Square::B5, MoveFlag::Capture),
+            Move::new(Square::E5, Square::D7, MoveFlag::Quiet),
+            Move::new(Square::B2, Square::B4, MoveFlag::Quiet),
+            Move::new(Square::E1, Square::G1, MoveFlag::QueensideCastle),
+            Move::new(Square::D5, Square::D6, MoveFlag::QueenPromotion),
+            Move::new(Square::E1, Square::E2, MoveFlag::Quiet),
+        ];
+
+        for mov in colliding_moves {
+            table.insert_entry(&position, 0, ScoreType::Exact, mov, 1, 0, true);
+            assert_eq!(table.get_hash_move(&position), None);
+        }
+
+        let mov = Move::new(Square::E1, Square::G1, MoveFlag::KingsideCastle);
+        table.insert_entry(&position, 0, ScoreType::Exact, mov, 1, 0, true);
+        assert_eq!(table.get_hash_move(&position), Some(mov));
+    }
 }
@@ -0,0 +1,152 @@
+use super::Depth;
+use crate::{evaluation::ValueScore, moves::Move};
+use std::fmt::Write;
+
+struct TreeNode {
+    parent: Option<usize>,
+    mov: Option<Move>,
+    depth: Depth,
+    alpha: ValueScore,
+    beta: ValueScore,
+    score: Option<ValueScore>,
+    best_move: Option<Move>,
+    reason: Option<&'static str>,
+}
+
+/// Records the nodes visited by the main search thread up to a given ply,
+/// so that pruning decisions can be inspected after the search.
+pub struct TreeDump {
+    max_ply: Depth,
+    nodes: Vec<TreeNode>,
+    stack: Vec<usize>,
+    next_move: Option<Move>,
+}
+
+impl TreeDump {
+    pub fn new(max_ply: Depth) -> Self {
+        Self { max_ply, nodes: Vec::new(), stack: Vec::new(), next_move: None }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.stack.clear();
+        self.next_move = None;
+    }
+
+    pub fn set_next_move(&mut self, mov: Move) {
+        self.next_move = Some(mov);
+    }
+
+    pub fn enter(&mut self, ply: Depth, depth: Depth, alpha: ValueScore, beta: ValueScore) {
+        let mov = self.next_move.take();
+        if ply > self.max_ply {
+            return;
+        }
+
+        self.nodes.push(TreeNode {
+            parent: self.stack.last().copied(),
+            mov,
+            depth,
+            alpha,
+            beta,
+            score: None,
+            best_move: None,
+            reason: None,
+        });
+        self.stack.push(self.nodes.len() - 1);
+    }
+
+    pub fn note(&mut self, ply: Depth, reason: &'static str) {
+        if ply > self.max_ply {
+            return;
+        }
+
+        if let Some(node) = self.stack.last().map(|index| &mut self.nodes[*index]) {
+            node.reason = Some(reason);
+        }
+    }
+
+    pub fn skip(&mut self, ply: Depth, mov: Move, reason: &'static str) {
+        self.set_next_move(mov);
+        self.enter(ply.saturating_add(1), 0, 0, 0);
+        self.note(ply.saturating_add(1), reason);
+        self.leave(ply.saturating_add(1), None, None);
+    }
+
+    pub fn leave(&mut self, ply: Depth, score: Option<ValueScore>, best_move: Option<Move>) {
+        if ply > self.max_ply {
+            return;
+        }
+
+        if let Some(index) = self.stack.pop() {
+            self.nodes[index].score = score;
+            self.nodes[index].best_move = best_move;
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n    node [shape=box];\n");
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut label = match (node.mov, node.parent) {
+                (Some(mov), _) => mov.to_string(),
+                (None, Some(_)) => "null".to_string(),
+                (None, None) => "root".to_string(),
+            };
+            if node.reason != Some("futility") {
+                write!(label, "\\ndepth {} [{}, {}]", node.depth, node.alpha, node.beta).unwrap();
+            }
+            if let Some(score) = node.score {
+                write!(label, "\\nscore {}", score).unwrap();
+            }
+            if let Some(best_move) = node.best_move {
+                write!(label, "\\nbest {}", best_move).unwrap();
+            }
+            if let Some(reason) = node.reason {
+                write!(label, "\\n{}", reason).unwrap();
+            }
+
+            writeln!(dot, "    n{} [label=\"{}\"];", index, label).unwrap();
+            if let Some(parent) = node.parent {
+                writeln!(dot, "    n{} -> n{};", parent, index).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeDump;
+    use crate::{
+        moves::gen::MoveStage,
+        position::{fen::FromFen, fen::START_FEN, Position},
+    };
+
+    #[test]
+    fn dump_nested_nodes() {
+        let position = Position::from_fen(START_FEN).unwrap();
+        let moves = position.moves(MoveStage::All);
+        let mut dump = TreeDump::new(1);
+
+        dump.enter(0, 2, -100, 100);
+        dump.set_next_move(moves[0]);
+        dump.enter(1, 1, -100, 100);
+        dump.set_next_move(moves[1]);
+        dump.enter(2, 0, -100, 100);
+        dump.leave(2, Some(5), None);
+        dump.note(1, "beta cutoff");
+        dump.leave(1, Some(-5), None);
+        dump.skip(0, moves[2], "futility");
+        dump.leave(0, Some(5), Some(moves[0]));
+
+        let dot = dump.to_dot();
+        assert_eq!(dot.matches("->").count(), 2);
+        assert!(dot.contains(&format!("{}\\ndepth 1 [-100, 100]\\nscore -5", moves[0])));
+        assert!(dot.contains("beta cutoff"));
+        assert!(dot.contains(&format!("{}\\nfutility", moves[2])));
+        assert!(!dot.contains(&moves[1].to_string()));
+    }
+}
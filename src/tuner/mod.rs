@@ -2,26 +2,45 @@
 // The next Camel major version will switch to NNUE, which won't require
 // Texel tuning anymore, so this is a temporary solution.
 
-use std::fs::read_to_string;
+use std::{fs::read_to_string, sync::Arc};
 
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
+    datagen,
     evaluation::{
-        self,
+        params::EvalParams,
         position::{
-            bishops::BISHOP_PAIR_BONUS,
-            king::SHELTER_PENALTY,
-            pawns::{DOUBLED_PAWNS_PENALTY, PASSED_PAWN_BONUS, PAWN_ISLAND_PENALTY},
-            rooks::{OPEN_FILE_BONUS, SEMI_OPEN_FILE_BONUS},
+            endgames::{probe_endgame, EndgameRule},
+            insufficient_material, midgame_ratio,
         },
+        psqt::{psqt_index, KING_FILE_BUCKETS, KING_FILE_PIECES},
         ValueScore,
     },
-    position::{fen::FromFen, Color, Position},
+    position::{board::Piece, fen::FromFen, Color, Position},
     search::{constraint::SearchConstraint, quiesce::quiesce},
 };
 
-const NUMBER_PARAMETERS: usize = 22;
+pub mod spsa;
+
+const NUMBER_PARAMETERS: usize = SCALAR_PARAMETERS + 2 * PSQT_PHASE_PARAMETERS;
+const SCALAR_PARAMETERS: usize = 45 + KING_FILE_PARAMETERS;
+const LABELED_EPD: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/books/quiet-labeled.epd");
+// Close to the scale that best fits the current evaluation to the bundled positions.
+const EVAL_TEST_K: f64 = 1.4;
+const KING_FILE_PARAMETERS: usize = KING_FILE_BUCKETS * KING_FILE_PIECES * 8;
+// The tables are kept symmetric, so only half of their files are tuned,
+// and pawns are never on the first or last ranks.
+const PSQT_PHASE_PARAMETERS: usize = 5 * 32 + 24;
+
+// Order of the piece values and midgame ratios among the parameters.
+const TUNED_PIECES: [Piece; 5] =
+    [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+const DEFAULT_EPOCHS: usize = 100;
+const LEARNING_RATE: f64 = 1.0;
+const ADAM_BETAS: (f64, f64) = (0.9, 0.999);
+const ADAM_EPSILON: f64 = 1e-8;
 
 struct PositionEntry {
     winner: Option<Color>,
@@ -42,101 +61,258 @@ impl PositionEntry {
     }
 }
 
-#[allow(static_mut_refs)]
-unsafe fn get_parameters(buf: &mut [ValueScore]) {
-    buf[0] = evaluation::PAWN_VALUE;
-    buf[1] = evaluation::KNIGHT_VALUE;
-    buf[2] = evaluation::BISHOP_VALUE;
-    buf[3] = evaluation::ROOK_VALUE;
-    buf[4] = evaluation::QUEEN_VALUE;
-    buf[5] = evaluation::position::PAWN_MIDGAME_RATIO;
-    buf[6] = evaluation::position::KNIGHT_MIDGAME_RATIO;
-    buf[7] = evaluation::position::BISHOP_MIDGAME_RATIO;
-    buf[8] = evaluation::position::ROOK_MIDGAME_RATIO;
-    buf[9] = evaluation::position::QUEEN_MIDGAME_RATIO;
-    buf[10] = BISHOP_PAIR_BONUS;
-    buf[11] = SHELTER_PENALTY;
-    buf[12] = DOUBLED_PAWNS_PENALTY;
-    buf[13] = PAWN_ISLAND_PENALTY;
-    buf[14..20].copy_from_slice(&PASSED_PAWN_BONUS[1..7]);
-    buf[20] = SEMI_OPEN_FILE_BONUS;
-    buf[21] = OPEN_FILE_BONUS;
-}
-
-unsafe fn set_parameters(parameters: &[ValueScore]) {
-    evaluation::PAWN_VALUE = parameters[0];
-    evaluation::KNIGHT_VALUE = parameters[1];
-    evaluation::BISHOP_VALUE = parameters[2];
-    evaluation::ROOK_VALUE = parameters[3];
-    evaluation::QUEEN_VALUE = parameters[4];
-    evaluation::position::PAWN_MIDGAME_RATIO = parameters[5];
-    evaluation::position::KNIGHT_MIDGAME_RATIO = parameters[6];
-    evaluation::position::BISHOP_MIDGAME_RATIO = parameters[7];
-    evaluation::position::ROOK_MIDGAME_RATIO = parameters[8];
-    evaluation::position::QUEEN_MIDGAME_RATIO = parameters[9];
-    BISHOP_PAIR_BONUS = parameters[10];
-    SHELTER_PENALTY = parameters[11];
-    DOUBLED_PAWNS_PENALTY = parameters[12];
-    PAWN_ISLAND_PENALTY = parameters[13];
-    PASSED_PAWN_BONUS[1] = parameters[14];
-    PASSED_PAWN_BONUS[2] = parameters[15];
-    PASSED_PAWN_BONUS[3] = parameters[16];
-    PASSED_PAWN_BONUS[4] = parameters[17];
-    PASSED_PAWN_BONUS[5] = parameters[18];
-    PASSED_PAWN_BONUS[6] = parameters[19];
-    SEMI_OPEN_FILE_BONUS = parameters[20];
-    OPEN_FILE_BONUS = parameters[21];
-}
-
-fn evaluation_error(entries: &[PositionEntry], k: f64) -> f64 {
-    let sigmoid = |x: f64| 1.0 / (1.0 + (10.0_f64).powf(-k * x / 400.0));
+fn get_parameters(params: &EvalParams) -> Vec<ValueScore> {
+    let mut buf = vec![0; NUMBER_PARAMETERS];
+    for (index, piece) in TUNED_PIECES.iter().enumerate() {
+        buf[index] = params.piece_values[*piece as usize];
+        buf[5 + index] = params.midgame_ratios[*piece as usize];
+    }
+    buf[10] = params.bishop_pair_bonus;
+    buf[11] = params.shelter_penalty;
+    buf[12] = params.doubled_pawns_penalty;
+    buf[13] = params.pawn_island_penalty;
+    buf[14..20].copy_from_slice(&params.passed_pawn_bonus[1..7]);
+    buf[20] = params.semi_open_file_bonus;
+    buf[21] = params.open_file_bonus;
+    buf[22..26].copy_from_slice(&params.pawn_storm_bonus[3..7]);
+    buf[26] = params.open_file_attack_bonus;
+    buf[27..33].copy_from_slice(&params.phalanx_pawn_bonus[1..7]);
+    buf[33..39].copy_from_slice(&params.supported_pawn_bonus[1..7]);
+    buf[39] = params.doubled_rooks_bonus;
+    buf[40] = params.battery_bonus;
+    buf[41] = params.bishop_xray_bonus;
+    buf[42] = params.trapped_bishop_penalty;
+    buf[43] = params.trapped_knight_penalty;
+    buf[44] = params.trapped_rook_penalty;
+    buf[45..45 + KING_FILE_PARAMETERS]
+        .copy_from_slice(params.king_file_psqt.as_flattened().as_flattened());
+
+    // Mirrored squares may start different, so they meet halfway.
+    for (phase, tables) in [&params.midgame_psqt, &params.endgame_psqt].into_iter().enumerate() {
+        for piece in Piece::list() {
+            for (index, value) in tables[*piece as usize].iter().enumerate() {
+                if let Some(parameter) = psqt_parameter(*piece, index) {
+                    buf[SCALAR_PARAMETERS + phase * PSQT_PHASE_PARAMETERS + parameter] += value;
+                }
+            }
+        }
+    }
+    buf[SCALAR_PARAMETERS..].iter_mut().for_each(|value| *value /= 2);
+
+    buf
+}
+
+fn eval_params(parameters: &[ValueScore]) -> EvalParams {
+    let mut params = EvalParams::default();
+    for (index, piece) in TUNED_PIECES.iter().enumerate() {
+        params.piece_values[*piece as usize] = parameters[index];
+        params.midgame_ratios[*piece as usize] = parameters[5 + index];
+    }
+    params.bishop_pair_bonus = parameters[10];
+    params.shelter_penalty = parameters[11];
+    params.doubled_pawns_penalty = parameters[12];
+    params.pawn_island_penalty = parameters[13];
+    params.passed_pawn_bonus[1..7].copy_from_slice(&parameters[14..20]);
+    params.semi_open_file_bonus = parameters[20];
+    params.open_file_bonus = parameters[21];
+    params.pawn_storm_bonus[3..7].copy_from_slice(&parameters[22..26]);
+    params.open_file_attack_bonus = parameters[26];
+    params.phalanx_pawn_bonus[1..7].copy_from_slice(&parameters[27..33]);
+    params.supported_pawn_bonus[1..7].copy_from_slice(&parameters[33..39]);
+    params.doubled_rooks_bonus = parameters[39];
+    params.battery_bonus = parameters[40];
+    params.bishop_xray_bonus = parameters[41];
+    params.trapped_bishop_penalty = parameters[42];
+    params.trapped_knight_penalty = parameters[43];
+    params.trapped_rook_penalty = parameters[44];
+    params
+        .king_file_psqt
+        .as_flattened_mut()
+        .as_flattened_mut()
+        .copy_from_slice(&parameters[45..45 + KING_FILE_PARAMETERS]);
+
+    for (phase, tables) in
+        [&mut params.midgame_psqt, &mut params.endgame_psqt].into_iter().enumerate()
+    {
+        for piece in Piece::list() {
+            for (index, value) in tables[*piece as usize].iter_mut().enumerate() {
+                if let Some(parameter) = psqt_parameter(*piece, index) {
+                    *value =
+                        parameters[SCALAR_PARAMETERS + phase * PSQT_PHASE_PARAMETERS + parameter];
+                }
+            }
+        }
+    }
+
+    params
+}
+
+/// Parameter of a table entry, among those of its phase, or none if it is not tuned.
+fn psqt_parameter(piece: Piece, index: usize) -> Option<usize> {
+    let (row, column) = (index / 8, index % 8);
+    let half_file = column.min(7 - column);
+    let row = match piece {
+        Piece::Pawn if row == 0 || row == 7 => return None,
+        Piece::Pawn => row - 1,
+        _ => row,
+    };
+
+    let piece_offset = Piece::list()
+        .iter()
+        .take_while(|other| **other != piece)
+        .map(|other| if *other == Piece::Pawn { 24 } else { 32 })
+        .sum::<usize>();
+    Some(piece_offset + row * 4 + half_file)
+}
+
+/// Reads positions labeled with the game result, as in `<fen> c9 "1-0";`.
+/// Files with the `bin` extension are read as written by `datagen` instead.
+fn read_entries(path: &str) -> Option<Vec<PositionEntry>> {
+    if path.ends_with(".bin") {
+        let entries = datagen::decode_entries(&std::fs::read(path).ok()?)?;
+        return Some(
+            entries
+                .into_iter()
+                .map(|entry| PositionEntry::new(entry.winner, entry.position))
+                .collect(),
+        );
+    }
+
+    let epd_file = read_to_string(path).ok()?;
+    epd_file
+        .lines()
+        .collect::<Vec<&str>>()
+        .par_iter()
+        .map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let fen = parts.iter().take(4).cloned().collect::<Vec<&str>>().join(" ");
+            let winner = match *parts.get(5)? {
+                "\"0-1\";" => Some(Color::Black),
+                "\"1-0\";" => Some(Color::White),
+                _ => None,
+            };
+            let position = Position::from_fen(&fen)?;
+            Some(PositionEntry::new(winner, position))
+        })
+        .collect()
+}
+
+fn sigmoid(evaluation: ValueScore, k: f64) -> f64 {
+    1.0 / (1.0 + (10.0_f64).powf(-k * evaluation as f64 / 400.0))
+}
+
+/// Evaluation of the position from the perspective of White, once captures are resolved.
+fn quiet_evaluation(position: &Position, constraint: &SearchConstraint) -> ValueScore {
+    quiesce(&mut position.clone(), ValueScore::MIN + 1, ValueScore::MAX, constraint, 0).0
+        * position.side_to_move.sign()
+}
+
+fn quiesce_constraint(params: &EvalParams) -> SearchConstraint {
+    SearchConstraint { eval_params: Arc::new(params.clone()), ..Default::default() }
+}
+
+fn evaluation_error(entries: &[PositionEntry], k: f64, params: &EvalParams) -> f64 {
+    let constraint = quiesce_constraint(params);
     let error = entries
         .par_iter()
         .map(|entry| {
-            let score = entry.score();
-            let evaluation = quiesce(
-                &entry.position,
-                ValueScore::MIN + 1,
-                ValueScore::MAX,
-                &SearchConstraint::default(),
-                0,
-            )
-            .0 * entry.position.side_to_move.sign();
-            (score - sigmoid(evaluation as f64)).powi(2)
+            (entry.score() - sigmoid(quiet_evaluation(&entry.position, &constraint), k)).powi(2)
         })
         .sum::<f64>();
     error / entries.len() as f64
 }
 
-pub fn texel_tune() -> Vec<ValueScore> {
-    let entries: Vec<PositionEntry> = {
-        let epd_file =
-            read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/books/quiet-labeled.epd"))
-                .expect("Could not read file");
-        epd_file
-            .lines()
-            .collect::<Vec<&str>>()
-            .par_iter()
-            .map(|line| {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                let fen = parts.iter().take(4).cloned().collect::<Vec<&str>>().join(" ");
-                let winner = match parts[5] {
-                    "\"0-1\";" => Some(Color::Black),
-                    "\"1-0\";" => Some(Color::White),
-                    _ => None,
-                };
-                let position = Position::from_fen(&fen).unwrap();
-                PositionEntry::new(winner, position)
-            })
-            .collect()
-    };
+/// Error and its gradient with respect to the table parameters, which the evaluation is linear in.
+/// Captures resolved by the quiescence search are not followed, since the positions are quiet.
+fn psqt_error_gradient(entries: &[PositionEntry], k: f64, params: &EvalParams) -> (f64, Vec<f64>) {
+    let constraint = quiesce_constraint(params);
+    let (error, gradient) = entries
+        .par_iter()
+        .fold(
+            || (0.0, vec![0.0; 2 * PSQT_PHASE_PARAMETERS]),
+            |(error, mut gradient), entry| {
+                let position = &entry.position;
+                let prediction = sigmoid(quiet_evaluation(position, &constraint), k);
+                let difference = entry.score() - prediction;
+
+                let replaced = insufficient_material(position)
+                    || matches!(probe_endgame(position), Some((EndgameRule::Evaluate(_), _)));
+                if !replaced {
+                    let slope = -2.0
+                        * difference
+                        * prediction
+                        * (1.0 - prediction)
+                        * k
+                        * std::f64::consts::LN_10
+                        / 400.0;
+                    let midgame_weight = midgame_ratio(position, params) as f64 / 255.0;
+
+                    for piece in Piece::list() {
+                        for color in Color::list() {
+                            for square in position.board.pieces_bb_color(*piece, *color) {
+                                let index = psqt_index(square, *color);
+                                if let Some(parameter) = psqt_parameter(*piece, index) {
+                                    let slope = slope * color.sign() as f64;
+                                    gradient[parameter] += slope * midgame_weight;
+                                    gradient[PSQT_PHASE_PARAMETERS + parameter] +=
+                                        slope * (1.0 - midgame_weight);
+                                }
+                            }
+                        }
+                    }
+                }
 
+                (error + difference.powi(2), gradient)
+            },
+        )
+        .reduce(
+            || (0.0, vec![0.0; 2 * PSQT_PHASE_PARAMETERS]),
+            |(error, gradient), (other_error, other_gradient)| {
+                let gradient = gradient.iter().zip(other_gradient).map(|(a, b)| a + b).collect();
+                (error + other_error, gradient)
+            },
+        );
+
+    let count = entries.len() as f64;
+    (error / count, gradient.into_iter().map(|slope| slope / count).collect())
+}
+
+pub struct TuneConfig {
+    /// Labeled positions, as EPD or as written by `datagen`.
+    pub path: String,
+    /// Only the first positions of the file are used, if set.
+    pub positions: Option<usize>,
+    /// Threads evaluating the positions, or as many as there are cores if zero.
+    pub threads: usize,
+    /// Optimizer steps over all positions.
+    pub epochs: usize,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        Self { path: LABELED_EPD.to_string(), positions: None, threads: 0, epochs: DEFAULT_EPOCHS }
+    }
+}
+
+/// Tunes the evaluation parameters on the configured positions, returning the best values found.
+pub fn texel_tune(config: &TuneConfig) -> Option<Vec<ValueScore>> {
+    let mut entries = read_entries(&config.path)?;
+    if let Some(positions) = config.positions {
+        entries.truncate(positions);
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(config.threads).build().ok()?;
+    Some(pool.install(|| tune_entries(&entries, config.epochs)))
+}
+
+fn tune_entries(entries: &[PositionEntry], epochs: usize) -> Vec<ValueScore> {
     // Find k that minimizes the error.
     let mut k = 0.5;
     let mut best_error = f64::MAX;
     let mut best_k = k;
+    let default_params = EvalParams::default();
     while k < 2.0 {
-        let error = evaluation_error(&entries, k);
+        let error = evaluation_error(entries, k, &default_params);
         if error < best_error {
             best_error = error;
             best_k = k;
@@ -147,46 +323,126 @@ pub fn texel_tune() -> Vec<ValueScore> {
     println!("Best k: {:.2}", best_k);
     println!("Best error: {:.4}", best_error);
 
-    let mut improved = true;
-    let mut best_error = evaluation_error(&entries, k);
-    let mut best_parameters = (0..NUMBER_PARAMETERS).map(|_| 0).collect::<Vec<ValueScore>>();
-    unsafe { get_parameters(&mut best_parameters) };
-
-    unsafe {
-        while improved {
-            improved = false;
-
-            for idx in 0..NUMBER_PARAMETERS {
-                let mut parameters = best_parameters.clone();
-
-                if parameters[idx] < ValueScore::MAX {
-                    parameters[idx] += 1;
-                    set_parameters(&parameters);
-                    let error = evaluation_error(&entries, best_k);
-                    if error < best_error {
-                        best_error = error;
-                        improved = true;
-                        best_parameters = parameters;
-                    } else if parameters[idx] > 2 {
-                        parameters[idx] -= 2;
-                        set_parameters(&parameters);
-                        let error = evaluation_error(&entries, best_k);
-                        if error < best_error {
-                            best_error = error;
-                            improved = true;
-                            best_parameters = parameters;
-                        }
-                    }
-                }
+    // Adam, with the table gradients computed exactly and the others by finite differences.
+    let mut parameters = get_parameters(&default_params);
+    let mut weights = parameters.iter().map(|value| *value as f64).collect::<Vec<_>>();
+    let mut moments = vec![(0.0, 0.0); NUMBER_PARAMETERS];
+    let mut best_error = f64::MAX;
+    let mut best_parameters = parameters.clone();
 
-                set_parameters(&best_parameters);
-            }
+    for epoch in 1..=epochs {
+        let (error, psqt_gradient) =
+            psqt_error_gradient(entries, best_k, &eval_params(&parameters));
+        if error < best_error {
+            best_error = error;
+            best_parameters = parameters.clone();
+        }
 
-            print!("current values: {:?}", best_parameters);
-            println!("; error {:.8}", best_error);
+        // Each shifted parameter is evaluated with its own set, side by side.
+        let mut gradient = (0..SCALAR_PARAMETERS)
+            .into_par_iter()
+            .map(|idx| {
+                let mut shifted = parameters.clone();
+                shifted[idx] = parameters[idx].saturating_add(1);
+                let error_above = evaluation_error(entries, best_k, &eval_params(&shifted));
+                shifted[idx] = parameters[idx].saturating_sub(1);
+                let error_below = evaluation_error(entries, best_k, &eval_params(&shifted));
+                (error_above - error_below) / 2.0
+            })
+            .collect::<Vec<_>>();
+        gradient.extend(psqt_gradient);
+
+        for ((weight, (mean, variance)), slope) in
+            weights.iter_mut().zip(moments.iter_mut()).zip(gradient)
+        {
+            *mean = ADAM_BETAS.0 * *mean + (1.0 - ADAM_BETAS.0) * slope;
+            *variance = ADAM_BETAS.1 * *variance + (1.0 - ADAM_BETAS.1) * slope * slope;
+            let mean = *mean / (1.0 - ADAM_BETAS.0.powi(epoch as i32));
+            let variance = *variance / (1.0 - ADAM_BETAS.1.powi(epoch as i32));
+            *weight -= LEARNING_RATE * mean / (variance.sqrt() + ADAM_EPSILON);
         }
+
+        parameters = weights.iter().map(|weight| weight.round() as ValueScore).collect();
+
+        print!("current values: {:?}", parameters);
+        println!("; epoch {} error {:.8}", epoch, error);
+    }
+
+    let error = evaluation_error(entries, best_k, &eval_params(&parameters));
+    if error < best_error {
+        best_error = error;
+        best_parameters = parameters;
     }
 
     println!("Best error: {:.4}", best_error);
     best_parameters
 }
+
+/// Mean squared error of the current evaluation against the game results of labeled positions,
+/// read from the given file or else from the bundled quiet positions.
+/// The sigmoid scale is fixed, so that losses of different evaluations can be compared.
+pub fn evaluation_loss(path: Option<&str>) -> Option<f64> {
+    let entries = read_entries(path.unwrap_or(LABELED_EPD))?;
+    if entries.is_empty() {
+        return None;
+    }
+    Some(evaluation_error(&entries, EVAL_TEST_K, &EvalParams::default()))
+}
+
+/// Evaluation parameters from the output of a tuning run, a list such as `[100, 300, ...]`.
+pub fn parse_tuned_params(text: &str) -> Option<EvalParams> {
+    let parameters = text
+        .trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split(',')
+        .map(|value| value.trim().parse::<ValueScore>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    (parameters.len() == NUMBER_PARAMETERS).then(|| eval_params(&parameters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        eval_params, get_parameters, parse_tuned_params, psqt_parameter, PSQT_PHASE_PARAMETERS,
+    };
+    use crate::{evaluation::params::EvalParams, position::board::Piece};
+
+    #[test]
+    fn psqt_parameters_cover_mirrored_halves() {
+        let mut squares = vec![0; PSQT_PHASE_PARAMETERS];
+        for piece in Piece::list() {
+            for index in 0..64 {
+                if let Some(parameter) = psqt_parameter(*piece, index) {
+                    assert_eq!(
+                        psqt_parameter(*piece, index / 8 * 8 + 7 - index % 8),
+                        Some(parameter)
+                    );
+                    squares[parameter] += 1;
+                }
+            }
+        }
+
+        assert!(squares.iter().all(|count| *count == 2));
+        assert_eq!(psqt_parameter(Piece::Pawn, 3), None);
+    }
+
+    #[test]
+    fn parameters_round_trip() {
+        let parameters = get_parameters(&EvalParams::default());
+        let params = eval_params(&parameters);
+        assert_eq!(get_parameters(&params), parameters);
+        assert_eq!(params.piece_values, EvalParams::default().piece_values);
+        assert_eq!(params.passed_pawn_bonus, EvalParams::default().passed_pawn_bonus);
+    }
+
+    #[test]
+    fn tuned_params_are_parsed() {
+        let parameters = get_parameters(&EvalParams::default());
+        let params = parse_tuned_params(&format!("{:?}\n", parameters)).unwrap();
+        assert_eq!(get_parameters(&params), parameters);
+
+        assert!(parse_tuned_params("[1, 2, 3]").is_none());
+        assert!(parse_tuned_params(&format!("{:?}", &parameters[1..])).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+// SPSA tuning of the search parameters. Each iteration perturbs all parameters at once
+// in a random direction, and moves them towards the side that scores better in self-play.
+
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{
+    datagen::{
+        random_opening,
+        selfplay::{self, Adjudication, Player},
+    },
+    evaluation::ValueScore,
+    position::{Color, Position},
+    search::{params::SearchParams, table::SearchTable, Depth},
+};
+
+const TABLE_SIZE_MB: usize = 4;
+// Games between close parameter sets are decided sooner than in data generation,
+// since only their result matters.
+const ADJUDICATION: Adjudication = Adjudication { score: 1000, plies: 6 };
+
+// Decay of the learning rate and of the perturbations over the iterations.
+const LEARNING_RATE_DECAY: f64 = 0.602;
+const PERTURBATION_DECAY: f64 = 0.101;
+
+const DEFAULT_ITERATIONS: usize = 200;
+const DEFAULT_PAIRS: usize = 8;
+const DEFAULT_NODES: usize = 5000;
+const DEFAULT_RANDOM_PLIES: usize = 8;
+const DEFAULT_LEARNING_RATE: f64 = 2.0;
+
+struct SpsaParameter {
+    name: &'static str,
+    min: f64,
+    max: f64,
+    /// Initial perturbation, which is also the unit the parameter moves in.
+    step: f64,
+}
+
+const SPSA_PARAMETERS: [SpsaParameter; 6] = [
+    SpsaParameter { name: "lmr_divisor", min: 1.0, max: 8.0, step: 0.5 },
+    SpsaParameter { name: "null_move_reduction", min: 1.0, max: 6.0, step: 0.5 },
+    SpsaParameter { name: "aspiration_window", min: 10.0, max: 300.0, step: 10.0 },
+    SpsaParameter { name: "futility_margin_1", min: 50.0, max: 600.0, step: 20.0 },
+    SpsaParameter { name: "futility_margin_2", min: 100.0, max: 1000.0, step: 30.0 },
+    SpsaParameter { name: "reverse_futility_margin", min: 30.0, max: 300.0, step: 10.0 },
+];
+
+fn get_parameters(params: &SearchParams) -> Vec<f64> {
+    vec![
+        params.lmr_divisor,
+        params.null_move_reduction as f64,
+        params.aspiration_window as f64,
+        params.futility_margins[0] as f64,
+        params.futility_margins[1] as f64,
+        params.reverse_futility_margin as f64,
+    ]
+}
+
+fn search_params(parameters: &[f64]) -> SearchParams {
+    let parameters = parameters
+        .iter()
+        .zip(SPSA_PARAMETERS.iter())
+        .map(|(value, parameter)| value.clamp(parameter.min, parameter.max))
+        .collect::<Vec<_>>();
+    SearchParams {
+        lmr_divisor: parameters[0],
+        null_move_reduction: parameters[1].round() as Depth,
+        aspiration_window: parameters[2].round() as ValueScore,
+        futility_margins: [
+            parameters[3].round() as ValueScore,
+            parameters[4].round() as ValueScore,
+        ],
+        reverse_futility_margin: parameters[5].round() as ValueScore,
+    }
+}
+
+pub struct SpsaConfig {
+    pub iterations: usize,
+    /// Pairs of games played between the perturbed sets in each iteration.
+    /// The games of a pair start from the same opening, with colors swapped.
+    pub pairs: usize,
+    /// Nodes searched for each move, as a soft limit.
+    pub nodes: usize,
+    /// Random moves played from the start position to make up each opening.
+    pub random_plies: usize,
+    /// Threads playing the games, or as many as there are cores if zero.
+    pub threads: usize,
+    pub seed: u64,
+    pub learning_rate: f64,
+}
+
+impl Default for SpsaConfig {
+    fn default() -> Self {
+        Self {
+            iterations: DEFAULT_ITERATIONS,
+            pairs: DEFAULT_PAIRS,
+            nodes: DEFAULT_NODES,
+            random_plies: DEFAULT_RANDOM_PLIES,
+            threads: 0,
+            seed: 0,
+            learning_rate: DEFAULT_LEARNING_RATE,
+        }
+    }
+}
+
+/// Plays a game from the opening, each side searching with its own parameters and table.
+fn play_game(
+    opening: &Position,
+    white: &SearchParams,
+    black: &SearchParams,
+    nodes: usize,
+    tables: &[Arc<SearchTable>; 2],
+) -> Option<Color> {
+    tables.iter().for_each(|table| table.clear());
+    selfplay::play_game(
+        opening,
+        Player { params: white, table: &tables[0] },
+        Player { params: black, table: &tables[1] },
+        nodes,
+        ADJUDICATION,
+        |_, _| {},
+    )
+}
+
+/// Points of the first set over both games of a pair, from 0 to 2.
+fn play_pair(
+    opening: &Position,
+    first: &SearchParams,
+    second: &SearchParams,
+    nodes: usize,
+    tables: &[Arc<SearchTable>; 2],
+) -> f64 {
+    let points = |winner: Option<Color>, first_color: Color| match winner {
+        Some(color) if color == first_color => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    };
+    points(play_game(opening, first, second, nodes, tables), Color::White)
+        + points(play_game(opening, second, first, nodes, tables), Color::Black)
+}
+
+fn opening(config: &SpsaConfig, iteration: usize, pair: usize) -> Position {
+    let mut rng = StdRng::seed_from_u64(
+        config.seed ^ ((iteration * config.pairs + pair) as u64).wrapping_mul(0x9E3779B97F4A7C15),
+    );
+    loop {
+        if let Some(position) = random_opening(&mut rng, config.random_plies) {
+            return position;
+        }
+    }
+}
+
+/// Tunes the search parameters with self-play, printing their values after each iteration.
+/// Returns the final values, or none if the threads could not be started.
+pub fn spsa_tune(config: &SpsaConfig) -> Option<SearchParams> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(config.threads).build().ok()?;
+    Some(pool.install(|| tune_search(config)))
+}
+
+fn tune_search(config: &SpsaConfig) -> SearchParams {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut values = get_parameters(&SearchParams::default());
+    // Stabilizes the first iterations, which would otherwise take the largest steps.
+    let stability = config.iterations as f64 / 10.0;
+
+    for iteration in 1..=config.iterations {
+        let learning_rate =
+            config.learning_rate / (iteration as f64 + stability).powf(LEARNING_RATE_DECAY);
+        let perturbation = 1.0 / (iteration as f64).powf(PERTURBATION_DECAY);
+
+        let directions =
+            values.iter().map(|_| if rng.gen() { 1.0 } else { -1.0 }).collect::<Vec<f64>>();
+        let shifted = |sign: f64| {
+            let shifted = values
+                .iter()
+                .zip(SPSA_PARAMETERS.iter())
+                .zip(directions.iter())
+                .map(|((value, parameter), direction)| {
+                    value + sign * perturbation * parameter.step * direction
+                })
+                .collect::<Vec<_>>();
+            search_params(&shifted)
+        };
+        let (plus, minus) = (shifted(1.0), shifted(-1.0));
+
+        let points = (0..config.pairs)
+            .into_par_iter()
+            .map_init(
+                || [(); 2].map(|_| Arc::new(SearchTable::new(TABLE_SIZE_MB))),
+                |tables, pair| {
+                    play_pair(
+                        &opening(config, iteration, pair),
+                        &plus,
+                        &minus,
+                        config.nodes,
+                        tables,
+                    )
+                },
+            )
+            .sum::<f64>();
+        // From -1, if the shifted down set won all games, to 1 if the shifted up set did.
+        let result = (points - config.pairs as f64) / config.pairs.max(1) as f64;
+
+        for ((value, parameter), direction) in
+            values.iter_mut().zip(SPSA_PARAMETERS.iter()).zip(directions.iter())
+        {
+            *value = (*value + learning_rate * parameter.step * result * direction)
+                .clamp(parameter.min, parameter.max);
+        }
+
+        let current = values
+            .iter()
+            .zip(SPSA_PARAMETERS.iter())
+            .map(|(value, parameter)| format!("{} {:.2}", parameter.name, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("iteration {}: result {:+.3}; {}", iteration, result, current);
+    }
+
+    search_params(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_parameters, search_params, SPSA_PARAMETERS};
+    use crate::search::params::SearchParams;
+
+    #[test]
+    fn parameters_round_trip() {
+        let parameters = get_parameters(&SearchParams::default());
+        assert_eq!(parameters.len(), SPSA_PARAMETERS.len());
+        assert_eq!(search_params(&parameters), SearchParams::default());
+    }
+
+    #[test]
+    fn parameters_are_kept_in_bounds() {
+        let params = search_params(&[0.0, 9.6, 1000.0, 0.0, 0.0, 500.0]);
+        assert_eq!(params.lmr_divisor, 1.0);
+        assert_eq!(params.null_move_reduction, 6);
+        assert_eq!(params.aspiration_window, 300);
+        assert_eq!(params.futility_margins, [50, 100]);
+        assert_eq!(params.reverse_futility_margin, 300);
+    }
+}
@@ -179,3 +179,73 @@ fn perft_chess960_9() {
 fn perft_chess960_10() {
     expect_perft("bbq1nr1r/pppppk1p/2n2p2/6p1/P4P2/4P1P1/1PPP3P/BBQNNRKR w HF - 1 9", 5, 10316716);
 }
+
+/// Flips the board vertically and swaps the colors, which must not change the node count.
+fn mirror_fen(fen: &str) -> String {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    let swap_case = |s: &str| {
+        s.chars()
+            .map(|c| if c.is_lowercase() { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+            .collect::<String>()
+    };
+
+    let board = fields[0].split('/').rev().map(swap_case).collect::<Vec<_>>().join("/");
+    let side_to_move = if fields[1] == "w" { "b" } else { "w" };
+    let castling_rights = swap_case(fields[2]);
+    let en_passant = match fields[3] {
+        "-" => "-".to_string(),
+        square => {
+            let rank = if &square[1..] == "3" { "6" } else { "3" };
+            format!("{}{}", &square[..1], rank)
+        }
+    };
+
+    format!(
+        "{} {} {} {} {} {}",
+        board, side_to_move, castling_rights, en_passant, fields[4], fields[5]
+    )
+}
+
+fn expect_perft_mirrored(fen: &str, depth: u8, nodes: u64) {
+    expect_perft(fen, depth, nodes);
+    expect_perft(&mirror_fen(fen), depth, nodes);
+}
+
+#[test]
+fn perft_chess960_mirrored() {
+    expect_perft_mirrored(
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        4,
+        326672,
+    );
+    expect_perft_mirrored(
+        "1rqkbnrb/pp1ppp1p/1n4p1/B1p5/3PP3/4N3/PPP2PPP/NRQK2RB w GBgb - 0 9",
+        4,
+        590970,
+    );
+    expect_perft_mirrored(
+        "rbqnbknr/pp1pppp1/8/2p5/3P3p/5N1P/PPP1PPPR/RBQNBK2 w Aha - 0 9",
+        4,
+        819631,
+    );
+}
+
+// Double Chess960 positions, where each side has its own starting array.
+// There are no published results for these, so the counts guard against regressions
+// and are cross-checked by searching the color-flipped position.
+
+#[test]
+fn perft_dfrc_1() {
+    expect_perft_mirrored("nrbbqknr/pppppppp/8/8/8/8/PPPPPPPP/RKBNQBRN w GAhb - 0 1", 5, 4065980);
+}
+
+#[test]
+fn perft_dfrc_2() {
+    expect_perft_mirrored("rqknbbrn/pppppppp/8/8/8/8/PPPPPPPP/BBNNRKRQ w GEga - 0 1", 5, 4965757);
+}
+
+#[test]
+fn perft_dfrc_3() {
+    // Kings next to a castling rook on one wing, with the other rook far away.
+    expect_perft_mirrored("rk4rn/pppppppp/8/8/8/8/PPPPPPPP/NR4KR w HBga - 0 1", 4, 322813);
+}
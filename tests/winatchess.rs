@@ -2,6 +2,8 @@ use camel::{
     position::{fen::FromFen, Position},
     search::{
         constraint::{SearchConstraint, TimeConstraint},
+        observer::NoObserver,
+        params::SearchParams,
         pvs_aspiration_iterative,
         table::{SearchTable, DEFAULT_TABLE_SIZE_MB},
         MAX_DEPTH,
@@ -9,8 +11,8 @@ use camel::{
 };
 use std::{
     sync::{
-        atomic::{AtomicBool, AtomicU16},
-        Arc,
+        atomic::{AtomicBool, AtomicU16, AtomicU64, AtomicU8, AtomicUsize},
+        Arc, Mutex,
     },
     time::{Duration, Instant},
 };
@@ -32,12 +34,31 @@ fn expect_search(fen: &str, mov: &str) {
             time_constraint: Some(TimeConstraint {
                 initial_instant: Instant::now(),
                 move_time: duration,
+                soft_time: None,
             }),
             global_stop: Arc::new(AtomicBool::new(false)),
             threads_stop: Arc::new(AtomicBool::new(false)),
             ponder_mode: Arc::new(AtomicBool::new(false)),
+            infinite: false,
             number_threads: Arc::new(AtomicU16::new(SCENARIO_THREADS)),
             game_history: vec![],
+            variety_margin: None,
+            tree_dump: None,
+            time_credit_millis: Arc::new(AtomicU64::new(0)),
+            contempt: 0,
+            multi_pv: 1,
+            excluded_root_moves: vec![],
+            seldepth: Arc::new(AtomicU8::new(0)),
+            nodes: Arc::new(AtomicUsize::new(0)),
+            best_move_effort: Arc::new(AtomicU16::new(0)),
+            seed: None,
+            max_nodes: None,
+            searched_nodes: Arc::new(AtomicUsize::new(0)),
+            root_move: Arc::new(Mutex::new(None)),
+            mate_limit: None,
+            strength: None,
+            eval_params: Arc::default(),
+            search_params: SearchParams::default(),
         };
 
         let result = pvs_aspiration_iterative(
@@ -46,9 +67,10 @@ fn expect_search(fen: &str, mov: &str) {
             MAX_DEPTH,
             table.clone(),
             &constraint,
+            &NoObserver,
         );
 
-        if result.unwrap().to_string() == mov {
+        if result.best_move.unwrap().to_string() == mov {
             return;
         }
     }